@@ -0,0 +1,64 @@
+use bellpepper::gadgets::{
+    boolean::{AllocatedBit, Boolean},
+    num::AllocatedNum,
+    sha256::sha256,
+};
+use bellpepper_core::ConstraintSystem;
+use nova_snark::{provider::PallasEngine, traits::Engine};
+use validate_btc_header::btc_validation::{
+    difficulty_update::calculate_difficulty_update,
+    header_step::BlockHeader,
+    median::{median_time_past, MEDIAN_TIME_SPAN},
+    sha256d::sha256d_header,
+};
+use validate_btc_header::util::profile::ProfilingConstraintSystem;
+
+type F = <PallasEngine as Engine>::Scalar;
+
+fn main() {
+    println!("Constraints of a block header step, by consensus rule");
+    println!("=========================================================");
+    print!("{}", BlockHeader::<F>::cost_report().unwrap());
+    println!();
+
+    println!("median_time_past");
+    println!("=========================================================");
+    let mut cs = ProfilingConstraintSystem::<F>::new(1);
+    let timestamps = (0..MEDIAN_TIME_SPAN)
+        .map(|i| {
+            AllocatedNum::alloc(cs.namespace(|| format!("timestamp {}", i)), || {
+                Ok(F::from(i as u64))
+            })
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    median_time_past(&mut cs, &timestamps, 32).unwrap();
+    print!("{}", cs.report());
+    println!();
+
+    println!("calculate_difficulty_update");
+    println!("=========================================================");
+    let mut cs = ProfilingConstraintSystem::<F>::new(1);
+    let prev_target =
+        AllocatedNum::alloc(cs.namespace(|| "previous target"), || Ok(F::from(1))).unwrap();
+    let time_sum = AllocatedNum::alloc(cs.namespace(|| "time sum"), || Ok(F::from(1))).unwrap();
+    calculate_difficulty_update(&mut cs, &prev_target, &time_sum).unwrap();
+    print!("{}", cs.report());
+    println!();
+
+    println!("SHA256d of an 80 byte header, generic and specialized");
+    println!("=========================================================");
+    let mut cs = ProfilingConstraintSystem::<F>::new(1);
+    let header = {
+        let mut cs = cs.namespace(|| "header bits");
+        (0..640)
+            .map(|i| {
+                Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("{}", i)), None).unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+    let hash = sha256(cs.namespace(|| "SHA 256"), &header).unwrap();
+    sha256(cs.namespace(|| "SHA 256d"), &hash).unwrap();
+    sha256d_header(cs.namespace(|| "sha256d_header"), &header).unwrap();
+    print!("{}", cs.report());
+}
@@ -0,0 +1,67 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination};
+use nova_snark::{provider::PallasEngine, traits::Engine};
+use validate_btc_header::mp::poly::Polynomial;
+use validate_btc_header::util::profile::{Cost, ProfilingConstraintSystem};
+
+type F = <PallasEngine as Engine>::Scalar;
+
+fn alloc_polynomial<CS: ConstraintSystem<F>>(mut cs: CS, n: usize) -> Polynomial<F> {
+    Polynomial {
+        coefficients: (0..n)
+            .map(|i| {
+                LinearCombination::zero()
+                    + cs.alloc(|| format!("coeff {}", i), || Ok(F::from(1)))
+                        .unwrap()
+            })
+            .collect(),
+        values: None,
+    }
+}
+
+fn constant_polynomial<CS: ConstraintSystem<F>>(n: usize) -> Polynomial<F> {
+    Polynomial {
+        coefficients: (0..n)
+            .map(|i| LinearCombination::zero() + (F::from(i as u64 + 1), CS::one()))
+            .collect(),
+        values: None,
+    }
+}
+
+/// The cost of multiplying a polynomial with `n` coefficients by `other`
+fn product_cost<G>(n: usize, other: G) -> Cost
+where
+    G: FnOnce(&mut ProfilingConstraintSystem<F>) -> Polynomial<F>,
+{
+    let mut cs = ProfilingConstraintSystem::<F>::new(1);
+    let a = alloc_polynomial(cs.namespace(|| "a"), n);
+    let b = other(&mut cs);
+    a.alloc_product(cs.namespace(|| "product"), &b).unwrap();
+    cs.cost_of("product")
+}
+
+fn main() {
+    println!("Product of two polynomials with n coefficients");
+    println!("=========================================================");
+    println!(
+        "{:>4}  {:>8} {:>9} {:>9}",
+        "n", "constr.", "variables", "nonzeros"
+    );
+    for n in [2, 3, 4, 6, 8, 12, 16, 32] {
+        let cost = product_cost(n, |cs| alloc_polynomial(cs.namespace(|| "b"), n));
+        println!(
+            "{:>4}  {:>8} {:>9} {:>9}",
+            n, cost.constraints, cost.aux, cost.nonzeros
+        );
+    }
+    println!();
+
+    println!("Product with a constant polynomial, e.g. a modulus");
+    println!("=========================================================");
+    for n in [4, 8] {
+        let cost = product_cost(n, |_| constant_polynomial::<ProfilingConstraintSystem<F>>(n));
+        println!(
+            "{:>4}  {:>8} constraints {:>9} variables",
+            n, cost.constraints, cost.aux
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use flate2::{write::ZlibEncoder, Compression};
+use validate_btc_header::btc_validation::witness::HeaderChainState;
+use validate_btc_header::prover::{
+    Bn256Grumpkin, CurveCycle, HeaderChainProver, PallasVesta, Scalar,
+};
+
+// The genesis block and block 1
+const BLOCKS: [[u64; 10]; 2] = [
+    [
+        0x0100000000000000,
+        0x0000000000000000,
+        0x0000000000000000,
+        0x0000000000000000,
+        0x000000003ba3edfd,
+        0x7a7b12b27ac72c3e,
+        0x67768f617fc81bc3,
+        0x888a51323a9fb8aa,
+        0x4b1e5e4a29ab5f49,
+        0xffff001d1dac2b7c,
+    ],
+    [
+        0x010000006fe28c0a,
+        0xb6f1b372c1a6a246,
+        0xae63f74f931e8365,
+        0xe15a089c68d61900,
+        0x00000000982051fd,
+        0x1e4ba744bbbe680e,
+        0x1fee14677ba1a3c3,
+        0x540bf7b1cdb606e8,
+        0x57233e0e61bc6649,
+        0xffff001d01e36299,
+    ],
+];
+
+fn run<C: CurveCycle>() {
+    let param_gen_timer = Instant::now();
+    println!("Producing public parameters and keys...");
+    let prover = HeaderChainProver::<C>::setup().unwrap();
+    println!(
+        "HeaderChainProver::setup, took {:?} ",
+        param_gen_timer.elapsed()
+    );
+
+    let state = HeaderChainState::<Scalar<C>>::before_genesis();
+    println!("Proving {} headers...", BLOCKS.len());
+    let start = Instant::now();
+    let proof = prover.prove(&state, BLOCKS.to_vec()).unwrap();
+    println!("HeaderChainProver::prove, took {:?}", start.elapsed());
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    bincode::serialize_into(&mut encoder, &proof.snark).unwrap();
+    println!(
+        "CompressedSNARK::len {:?} bytes",
+        encoder.finish().unwrap().len()
+    );
+
+    println!("Verifying...");
+    let start = Instant::now();
+    let res = proof.verify(prover.verifier_key());
+    println!(
+        "HeaderChainProof::verify: {:?}, took {:?}",
+        res.is_ok(),
+        start.elapsed()
+    );
+    assert!(res.is_ok());
+}
+
+fn main() {
+    let curve = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "pallas".to_string());
+    println!("Header chain proof on the {} cycle", curve);
+    println!("=========================================================");
+    match curve.as_str() {
+        "pallas" => run::<PallasVesta>(),
+        "bn254" => run::<Bn256Grumpkin>(),
+        _ => panic!("unknown cycle {}, expected pallas or bn254", curve),
+    }
+    println!("=========================================================");
+}
@@ -76,7 +76,7 @@ fn main() {
 
     // Block no. 123456
     let input = vec![[0x010000009500c43a, 0x25c624520b5100ad, 0xf82cb9f9da72fd24, 0x47a496bc600b0000, 0x000000006cd86237, 0x0395dedf1da2841c, 0xcda0fc489e3039de, 0x5f1ccddef0e83499, 0x1a65600ea6c8cb4d, 0xb3936a1ae3143991]]; 
-    let primary_circuit_sequence = C1::new_blocks(input.clone());
+    let primary_circuit_sequence = C1::new_blocks(input.clone()).unwrap();
 
     // z_0 is hash of 123455 = 0000000000000b60bc96a44724fd72daf9b92cf8ad00510b5224c6253ac40095
     let z0_primary = BlockHeader::initial_z_i_scalars();
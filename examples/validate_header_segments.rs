@@ -0,0 +1,82 @@
+use std::thread;
+use std::time::Instant;
+
+use validate_btc_header::btc_validation::segment::{Segment, SegmentBundle, SegmentParams};
+use validate_btc_header::btc_validation::witness::HeaderChainState;
+use validate_btc_header::verifier::{PallasVesta, Scalar};
+
+// The genesis block and blocks 1 and 2
+const BLOCKS: [[u64; 10]; 3] = [
+    [
+        0x0100000000000000,
+        0x0000000000000000,
+        0x0000000000000000,
+        0x0000000000000000,
+        0x000000003ba3edfd,
+        0x7a7b12b27ac72c3e,
+        0x67768f617fc81bc3,
+        0x888a51323a9fb8aa,
+        0x4b1e5e4a29ab5f49,
+        0xffff001d1dac2b7c,
+    ],
+    [
+        0x010000006fe28c0a,
+        0xb6f1b372c1a6a246,
+        0xae63f74f931e8365,
+        0xe15a089c68d61900,
+        0x00000000982051fd,
+        0x1e4ba744bbbe680e,
+        0x1fee14677ba1a3c3,
+        0x540bf7b1cdb606e8,
+        0x57233e0e61bc6649,
+        0xffff001d01e36299,
+    ],
+    [
+        0x010000004860eb18,
+        0xbf1b1620e37e9490,
+        0xfc8a427514416fd7,
+        0x5159ab86688e9a83,
+        0x00000000d5fdcc54,
+        0x1e25de1c7a5added,
+        0xf24858b8bb665c9f,
+        0x36ef744ee42c3160,
+        0x22c90f9bb0bc6649,
+        0xffff001d08d2bd61,
+    ],
+];
+
+fn main() {
+    println!("Block header chain proven in parallel segments");
+    println!("=========================================================");
+    type F = Scalar<PallasVesta>;
+
+    let param_gen_timer = Instant::now();
+    println!("Producing public parameters...");
+    let pp = SegmentParams::<PallasVesta>::setup().unwrap();
+    println!("PublicParams::setup, took {:?} ", param_gen_timer.elapsed());
+
+    let mut state = HeaderChainState::<F>::before_genesis();
+    let z0 = state.z();
+    let segments = Segment::split(&mut state, BLOCKS.to_vec(), 1).unwrap();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    println!(
+        "Proving {} segments on {} threads...",
+        segments.len(),
+        threads
+    );
+    let start = Instant::now();
+    let bundle = SegmentBundle::prove(&pp, &segments, threads).unwrap();
+    println!("SegmentBundle::prove, took {:?}", start.elapsed());
+
+    println!("Verifying the segments and their boundaries...");
+    let start = Instant::now();
+    let res = bundle.verify(&pp, &z0);
+    println!(
+        "SegmentBundle::verify: {:?}, took {:?}",
+        res.is_ok(),
+        start.elapsed()
+    );
+    assert_eq!(res.unwrap(), state.z());
+    println!("=========================================================");
+}
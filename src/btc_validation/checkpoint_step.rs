@@ -0,0 +1,262 @@
+use std::marker::PhantomData;
+
+use bellpepper_core::{
+    boolean::{AllocatedBit, Boolean},
+    num::AllocatedNum,
+    ConstraintSystem, SynthesisError,
+};
+use ff::{PrimeField, PrimeFieldBits};
+use nova_snark::errors::NovaError;
+use nova_snark::traits::circuit::StepCircuit;
+use nova_snark::traits::snark::default_ck_hint;
+use nova_snark::{PublicParams, RecursiveSNARK};
+use num_bigint::{BigInt, Sign};
+
+use crate::ecc::schnorr;
+use crate::ecc::secp256k1::{AllocatedPoint, Point};
+use crate::util::pack::{self, BitOrder};
+use crate::verifier::{z0_secondary, CurveCycle, Scalar, C2};
+
+/// Step checking a BIP-340 signature of the tip hash `z[0]` by a fixed signer,
+/// such as a federation attesting checkpoints. The state is left unchanged.
+///
+/// The signed message is the 32 bytes of the block hash, in the order in which
+/// SHA256d outputs them. The step has the arity of `BlockHeader` and is proven on its own as
+/// the prefix of a header chain, see `CheckpointProof`: the chain is proven from the state the
+/// checkpoint leaves unchanged. Interleaving the two in one chain of proofs would need
+/// non-uniform IVC, since a Nova step circuit is fixed.
+#[derive(Clone, Debug)]
+pub struct CheckpointStep<F>
+where
+    F: PrimeField,
+{
+    public_key: [u8; 32],
+    block_hash: Option<[u8; 32]>,
+    signature: Option<[u8; 64]>,
+    marker: PhantomData<F>,
+}
+
+impl<F> CheckpointStep<F>
+where
+    F: PrimeField + PrimeFieldBits,
+{
+    /// Step checking `signature` of `block_hash` by the x-only key `public_key`
+    pub fn new(public_key: [u8; 32], block_hash: [u8; 32], signature: [u8; 64]) -> Self {
+        Self {
+            public_key,
+            block_hash: Some(block_hash),
+            signature: Some(signature),
+            marker: PhantomData,
+        }
+    }
+
+    /// Step without a witness, for the shape of the circuit of `public_key`
+    pub fn blank(public_key: [u8; 32]) -> Self {
+        Self {
+            public_key,
+            block_hash: None,
+            signature: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F> StepCircuit<F> for CheckpointStep<F>
+where
+    F: PrimeField + PrimeFieldBits,
+{
+    fn arity(&self) -> usize {
+        18
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        z: &[AllocatedNum<F>],
+    ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
+        let message = tip_message(cs, &z[0], self.block_hash)?;
+
+        let public_key = Point::lift_x(&BigInt::from_bytes_be(Sign::Plus, &self.public_key))
+            .ok_or(SynthesisError::Unsatisfiable)?;
+        let public_key = AllocatedPoint::constant::<CS>(&public_key)?;
+        schnorr::verify_signature(
+            cs.namespace(|| "signature"),
+            &public_key,
+            &message,
+            self.signature.as_ref(),
+        )?;
+
+        Ok(z.to_vec())
+    }
+}
+
+/// Bits of the block hash `block_hash`, most significant bit of every byte first, constrained to
+/// be the tip hash `tip`
+fn tip_message<F, CS>(cs: &mut CS, tip: &AllocatedNum<F>, block_hash: Option<[u8; 32]>) -> Result<Vec<Boolean>, SynthesisError>
+where
+    F: PrimeField,
+    CS: ConstraintSystem<F>,
+{
+    let message = (0..256)
+        .map(|i| {
+            let bit = block_hash.map(|h| (h[i / 8] >> (7 - i % 8)) & 1 == 1);
+            Ok(Boolean::from(AllocatedBit::alloc(
+                cs.namespace(|| format!("hash bit {}", i)),
+                bit,
+            )?))
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+    // Like the hash in z[0], the hash bytes are read as a little-endian number.
+    // The tip is below the proof of work limit, so the top three bits are zero, which makes
+    // the packing injective.
+    for (i, bit) in message[8 * 31..8 * 31 + 3].iter().enumerate() {
+        Boolean::enforce_equal(cs.namespace(|| format!("hash bit {} is zero", 255 - i)), bit, &Boolean::constant(false))?;
+    }
+    let hash = pack::pack_num::<F, CS>(&message, BitOrder::LeBytes);
+    cs.enforce(
+        || "signed hash equals the tip hash",
+        |_| hash.num,
+        |lc| lc + CS::one(),
+        |lc| lc + tip.get_variable(),
+    );
+    Ok(message)
+}
+
+/// Public parameters of the checkpoint step of one signer on the cycle `C`
+pub struct CheckpointParams<C: CurveCycle> {
+    pp: PublicParams<C::E1, C::E2, CheckpointStep<Scalar<C>>, C2<C>>,
+}
+
+impl<C: CurveCycle> CheckpointParams<C> {
+    /// Parameters of the checkpoints signed by the x-only key `public_key`
+    pub fn setup(public_key: [u8; 32]) -> Result<Self, NovaError> {
+        let pp = PublicParams::setup(
+            &CheckpointStep::blank(public_key),
+            &C2::<C>::default(),
+            &*default_ck_hint(),
+            &*default_ck_hint(),
+        )?;
+        Ok(Self { pp })
+    }
+}
+
+/// Proof of a single checkpoint step on the state `z0`, which shows that its tip is signed.
+/// A header chain proven from `z0` is verified against it with
+/// `SegmentBundle::verify_from_checkpoint`.
+pub struct CheckpointProof<C: CurveCycle> {
+    pub z0: Vec<Scalar<C>>,
+    pub snark: RecursiveSNARK<C::E1, C::E2, CheckpointStep<Scalar<C>>, C2<C>>,
+}
+
+impl<C: CurveCycle> CheckpointProof<C> {
+    pub fn prove(
+        params: &CheckpointParams<C>,
+        step: &CheckpointStep<Scalar<C>>,
+        z0: Vec<Scalar<C>>,
+    ) -> Result<Self, NovaError> {
+        let circuit_secondary = C2::<C>::default();
+        let mut snark = RecursiveSNARK::new(&params.pp, step, &circuit_secondary, &z0, &z0_secondary::<C>())?;
+        snark.prove_step(&params.pp, step, &circuit_secondary)?;
+        Ok(Self { z0, snark })
+    }
+
+    /// Verifies the proof, returns the checkpointed state
+    pub fn verify(&self, params: &CheckpointParams<C>) -> Result<Vec<Scalar<C>>, NovaError> {
+        let (z_out, _) = self.snark.verify(&params.pp, 1, &self.z0, &z0_secondary::<C>())?;
+        Ok(z_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btc_validation::header_step::BlockHeader;
+    use crate::btc_validation::segment::{Segment, SegmentBundle, SegmentError, SegmentParams};
+    use crate::btc_validation::synthetic::{regtest_pow_limit, SyntheticChain};
+    use crate::util::fuzz::FuzzingConstraintSystem;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::CheckingConstraintSystem;
+    use crate::verifier::PallasVesta;
+
+    // Block no. 123456
+    const BLOCK_123456: [u64; 10] = [0x010000009500c43a, 0x25c624520b5100ad, 0xf82cb9f9da72fd24, 0x47a496bc600b0000, 0x000000006cd86237, 0x0395dedf1da2841c, 0xcda0fc489e3039de, 0x5f1ccddef0e83499, 0x1a65600ea6c8cb4d, 0xb3936a1ae3143991];
+
+    fn synthesize_step(step: &CheckpointStep<Fr>, z: &[Fr]) -> (CheckingConstraintSystem<Fr>, Result<Vec<Fr>, SynthesisError>) {
+        let mut cs = CheckingConstraintSystem::<Fr>::new();
+        let z_in = z
+            .iter()
+            .enumerate()
+            .map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap())
+            .collect::<Vec<_>>();
+        let z_out = step
+            .synthesize(&mut cs, &z_in)
+            .map(|z| z.iter().map(|v| v.get_value().unwrap()).collect());
+        (cs, z_out)
+    }
+
+    #[test]
+    fn test_checkpoint_step() {
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let block = BlockHeader::<Fr>::new_blocks_from(&mut state, vec![BLOCK_123456]).unwrap().remove(0);
+        let z = state.z();
+
+        let sk = BigInt::from(0xfeed);
+        let hash = block.witness().header.hash();
+        let signature = schnorr::sign(&sk, &hash, &[0; 32]);
+        let step = CheckpointStep::new(schnorr::public_key(&sk), hash, signature);
+
+        let (cs, z_out) = synthesize_step(&step, &z);
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+        assert_eq!(z_out.unwrap(), z);
+
+        // a checkpoint of another tip is rejected
+        let (cs, _) = synthesize_step(&step, &BlockHeader::<Fr>::initial_z_i_scalars());
+        assert_eq!(cs.which_is_unsatisfied(), Some("signed hash equals the tip hash"));
+    }
+
+    #[test]
+    fn test_tip_message_soundness() {
+        // Given the tip, the signed message is unique
+        let mut state = BlockHeader::<Fr>::initial_state();
+        BlockHeader::<Fr>::new_blocks_from(&mut state, vec![BLOCK_123456]).unwrap();
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let tip = AllocatedNum::alloc(cs.namespace(|| "tip"), || Ok(state.z()[0])).unwrap();
+        cs.fix("tip");
+        let message = tip_message(&mut cs, &tip, Some(state.tip_hash)).unwrap();
+        for (i, bit) in message.iter().enumerate() {
+            cs.output_boolean(&format!("message bit {}", i), bit);
+        }
+        cs.assert_sound();
+    }
+
+    #[test]
+    fn test_checkpointed_bundle() {
+        // a regtest chain is proven in two segments from a checkpoint after its first block
+        let mut chain = SyntheticChain::<Scalar<PallasVesta>>::regtest();
+        chain.mine(1, 600);
+        let checkpoint = chain.state.clone();
+        chain.mine(2, 600);
+
+        let sk = BigInt::from(0xfeed);
+        let signature = schnorr::sign(&sk, &checkpoint.tip_hash, &[0; 32]);
+        let step = CheckpointStep::new(schnorr::public_key(&sk), checkpoint.tip_hash, signature);
+        let checkpoint_params = CheckpointParams::<PallasVesta>::setup(schnorr::public_key(&sk)).unwrap();
+        let proof = CheckpointProof::prove(&checkpoint_params, &step, checkpoint.z()).unwrap();
+
+        let params = SegmentParams::<PallasVesta>::setup_with(regtest_pow_limit()).unwrap();
+        let segments = Segment::split(&mut checkpoint.clone(), chain.headers[1..].to_vec(), 1).unwrap();
+        let bundle = SegmentBundle::prove(&params, &segments, 1).unwrap();
+        assert_eq!(bundle.verify_from_checkpoint(&params, &checkpoint_params, &proof).unwrap(), chain.state.z());
+
+        // the bundle must start from the checkpointed state
+        let segments = Segment::split(&mut chain.start.clone(), chain.headers[..1].to_vec(), 1).unwrap();
+        let bundle = SegmentBundle::prove(&params, &segments, 1).unwrap();
+        assert!(matches!(bundle.verify_from_checkpoint(&params, &checkpoint_params, &proof), Err(SegmentError::InitialStateMismatch)));
+
+        // the checkpoint proof must be of the claimed state
+        let mut forged = proof;
+        forged.z0 = chain.start.z();
+        assert!(matches!(bundle.verify_from_checkpoint(&params, &checkpoint_params, &forged), Err(SegmentError::Checkpoint(_))));
+    }
+}
@@ -0,0 +1,272 @@
+use bellpepper::util_cs::witness_cs::WitnessCS;
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use generic_array::typenum::U24;
+use neptune::circuit2::poseidon_hash_allocated;
+use neptune::poseidon::{Poseidon, PoseidonConstants};
+use nova_snark::traits::circuit::StepCircuit;
+
+use crate::util::poseidon::constant_length_constants;
+
+/// Largest full state that can be committed to with a single Poseidon hash
+pub const MAX_COMMITTED_ARITY: usize = 24;
+
+fn commitment_constants<F: PrimeField>(len: usize) -> PoseidonConstants<F, U24> {
+    assert!(len <= MAX_COMMITTED_ARITY, "state of {} elements is too large to commit to", len);
+    constant_length_constants::<F, U24>(len)
+}
+
+/// Poseidon commitment to the full step state `state`
+pub fn commit_state<F: PrimeField>(state: &[F]) -> F {
+    Poseidon::new_with_preimage(state, &commitment_constants(state.len())).hash()
+}
+
+/// Compact public state: the commitment to `state` followed by the headline values
+/// `state[i]` for every `i` in `headlines`
+pub fn compact_state<F: PrimeField>(state: &[F], headlines: &[usize]) -> Vec<F> {
+    let mut compact = vec![commit_state(state)];
+    compact.extend(headlines.iter().map(|i| state[*i]));
+    compact
+}
+
+/// Checks that the full state `state` is the one committed to in the compact state `compact`
+pub fn open_state<F: PrimeField>(compact: &[F], state: &[F], headlines: &[usize]) -> bool {
+    compact == compact_state(state, headlines).as_slice()
+}
+
+/// Runs `step` on the full state `z` outside of any circuit and returns the next full state
+pub fn next_state<F: PrimeField, C: StepCircuit<F>>(step: &C, z: &[F]) -> Result<Vec<F>, SynthesisError> {
+    let mut cs = WitnessCS::<F>::new();
+    let z = z.iter().enumerate().map(|(i, v)| {
+        AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v))
+    }).collect::<Result<Vec<_>, _>>()?;
+    let z_out = step.synthesize(&mut cs, &z)?;
+    z_out.iter().map(|v| v.get_value().ok_or(SynthesisError::AssignmentMissing)).collect()
+}
+
+/// Wraps a step circuit so that its public state is smaller than its full state.
+/// The public state is a Poseidon commitment to the full state of `inner`, followed by
+/// the full state elements listed in `headlines`. The full state before the step is
+/// supplied as witness and opened against the commitment. The commitment is not blinded and
+/// does not hide the full state.
+#[derive(Clone, Debug)]
+pub struct CompactStep<F, C>
+where
+    F: PrimeField,
+    C: StepCircuit<F>,
+{
+    inner: C,
+    // Full state of `inner` before this step
+    state: Vec<F>,
+    headlines: Vec<usize>,
+}
+
+impl<F, C> CompactStep<F, C>
+where
+    F: PrimeField,
+    C: StepCircuit<F>,
+{
+    /// Wraps `inner`, which is run on the full state `state`.
+    /// The sizes are checked when the step is synthesized, see `is_well_formed`.
+    pub fn new(inner: C, state: Vec<F>, headlines: Vec<usize>) -> Self {
+        Self { inner, state, headlines }
+    }
+
+    /// Wraps `inner` with an all zero full state, for producing public parameters
+    pub fn new_shape(inner: C, headlines: Vec<usize>) -> Self {
+        let state = vec![F::ZERO; inner.arity()];
+        Self::new(inner, state, headlines)
+    }
+
+    /// Wraps the steps `inner`, starting from the full state `z0`.
+    /// Returns the wrapped steps and the full state after the last step.
+    pub fn new_steps(inner: Vec<C>, z0: Vec<F>, headlines: &[usize]) -> Result<(Vec<Self>, Vec<F>), SynthesisError> {
+        let mut state = z0;
+        let mut steps = Vec::with_capacity(inner.len());
+        for step in inner {
+            let next = next_state(&step, &state)?;
+            steps.push(Self::new(step, state, headlines.to_vec()));
+            state = next;
+        }
+        Ok((steps, state))
+    }
+
+    pub fn state(&self) -> &[F] {
+        &self.state
+    }
+
+    /// Whether the full state has the arity of `inner` and can be committed to,
+    /// and every headline is an element of it
+    pub fn is_well_formed(&self) -> bool {
+        let arity = self.inner.arity();
+        self.state.len() == arity
+            && arity <= MAX_COMMITTED_ARITY
+            && self.headlines.iter().all(|i| *i < arity)
+    }
+}
+
+impl<F, C> StepCircuit<F> for CompactStep<F, C>
+where
+    F: PrimeField,
+    C: StepCircuit<F>,
+{
+    fn arity(&self) -> usize {
+        1 + self.headlines.len()
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        z: &[AllocatedNum<F>],
+    ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
+        if !self.is_well_formed() || z.len() != self.arity() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let constants = commitment_constants::<F>(self.inner.arity());
+
+        // Open the commitment in z[0] to the full state
+        let z_full = self.state.iter().enumerate().map(|(i, v)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("full state {}", i)), || Ok(*v))
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        let commitment = poseidon_hash_allocated(cs.namespace(|| "commit state in"), z_full.clone(), &constants)?;
+        cs.enforce(
+            || "state commitment in",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + commitment.get_variable() - z[0].get_variable(),
+        );
+
+        for (j, i) in self.headlines.iter().enumerate() {
+            cs.enforce(
+                || format!("headline in {}", j),
+                |lc| lc,
+                |lc| lc,
+                |lc| lc + z_full[*i].get_variable() - z[j + 1].get_variable(),
+            );
+        }
+
+        let z_full_out = self.inner.synthesize(&mut cs.namespace(|| "inner step"), &z_full)?;
+        if z_full_out.len() != self.inner.arity() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let mut z_out = vec![poseidon_hash_allocated(cs.namespace(|| "commit state out"), z_full_out.clone(), &constants)?];
+        z_out.extend(self.headlines.iter().map(|i| z_full_out[*i].clone()));
+
+        Ok(z_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::compact::*;
+    use ff::Field;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use crate::util::scalar::Fr;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+
+    // z_{i+1} = (z_i[0] + 1, z_i[0] * z_i[1], z_i[2])
+    #[derive(Clone, Debug, Default)]
+    struct ToyStep;
+
+    impl StepCircuit<Fr> for ToyStep {
+        fn arity(&self) -> usize {
+            3
+        }
+
+        fn synthesize<CS: ConstraintSystem<Fr>>(
+            &self,
+            cs: &mut CS,
+            z: &[AllocatedNum<Fr>],
+        ) -> Result<Vec<AllocatedNum<Fr>>, SynthesisError> {
+            let count = AllocatedNum::alloc(cs.namespace(|| "count"), || Ok(z[0].get_value().unwrap() + Fr::ONE))?;
+            cs.enforce(
+                || "count = z[0] + 1",
+                |lc| lc + z[0].get_variable() + CS::one(),
+                |lc| lc + CS::one(),
+                |lc| lc + count.get_variable(),
+            );
+            let product = z[0].mul(cs.namespace(|| "product"), &z[1])?;
+            Ok(vec![count, product, z[2].clone()])
+        }
+    }
+
+    fn run_steps(steps: &[CompactStep<Fr, ToyStep>], z0: &[Fr]) -> (Vec<Fr>, bool) {
+        let mut z = z0.to_vec();
+        let mut satisfied = true;
+        for step in steps {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let z_in = z.iter().enumerate().map(|(i, v)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()
+            }).collect::<Vec<_>>();
+            let z_out = step.synthesize(&mut cs, &z_in).unwrap();
+            satisfied &= cs.is_satisfied();
+            z = z_out.iter().map(|v| v.get_value().unwrap()).collect();
+        }
+        (z, satisfied)
+    }
+
+    #[test]
+    fn test_compact_steps() {
+        let headlines = [0];
+        let z0 = vec![Fr::from(2u64), Fr::from(3u64), Fr::from(7u64)];
+        let (steps, z_full) = CompactStep::new_steps(vec![ToyStep; 3], z0.clone(), &headlines).unwrap();
+        assert_eq!(z_full, vec![Fr::from(5u64), Fr::from(72u64), Fr::from(7u64)]);
+
+        let (z, satisfied) = run_steps(&steps, &compact_state(&z0, &headlines));
+        assert!(satisfied);
+        assert_eq!(z.len(), 2);
+        assert_eq!(z[1], Fr::from(5u64));
+        assert!(open_state(&z, &z_full, &headlines));
+        assert!(!open_state(&z, &[Fr::from(5u64), Fr::from(72u64), Fr::from(8u64)], &headlines));
+    }
+
+    #[test]
+    fn test_compact_wrong_state() {
+        let headlines = [0];
+        let z0 = vec![Fr::from(2u64), Fr::from(3u64), Fr::from(7u64)];
+        let wrong_z0 = vec![Fr::from(2u64), Fr::from(4u64), Fr::from(7u64)];
+        let (steps, _) = CompactStep::new_steps(vec![ToyStep], wrong_z0, &headlines).unwrap();
+
+        let (_, satisfied) = run_steps(&steps, &compact_state(&z0, &headlines));
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn test_compact_malformed_step() {
+        // a malformed step fails to synthesize instead of panicking
+        let headlines = vec![0];
+        let z0 = vec![Fr::from(2u64), Fr::from(3u64), Fr::from(7u64)];
+        let steps = [
+            CompactStep::new(ToyStep, z0[..2].to_vec(), headlines.clone()),
+            CompactStep::new(ToyStep, z0.clone(), vec![3]),
+        ];
+        for step in steps {
+            assert!(!step.is_well_formed());
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let z_in = compact_state(&z0, &headlines).iter().enumerate().map(|(i, v)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()
+            }).collect::<Vec<_>>();
+            assert!(matches!(step.synthesize(&mut cs, &z_in), Err(SynthesisError::Unsatisfiable)));
+        }
+    }
+
+    #[test]
+    fn test_compact_step_soundness() {
+        let headlines = [0];
+        let z0 = vec![Fr::from(2u64), Fr::from(3u64), Fr::from(7u64)];
+        let (steps, _) = CompactStep::new_steps(vec![ToyStep], z0.clone(), &headlines).unwrap();
+
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let z_in = compact_state(&z0, &headlines).iter().enumerate().map(|(i, v)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()
+        }).collect::<Vec<_>>();
+        let z_out = steps[0].synthesize(&mut cs, &z_in).unwrap();
+        for (i, z) in z_out.iter().enumerate() {
+            cs.fix(&format!("z {}", i));
+            cs.output_num(&format!("z_out[{}]", i), z);
+        }
+        cs.assert_sound_where(|path| !["commit state in/", "commit state out/"].iter().any(|p| path.contains(p)));
+    }
+}
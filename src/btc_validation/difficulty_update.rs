@@ -1,10 +1,12 @@
-use bellpepper_core::{ConstraintSystem, SynthesisError};
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
 use bellpepper::gadgets::num::AllocatedNum;
 use ff::PrimeField;
+use num_bigint::BigInt;
 use crate::btc_validation::median;
+use crate::btc_validation::witness::TARGET_TIMESPAN;
 use crate::mp::bignat::BigNat;
+use crate::util::convert::nat_to_f;
 use crate::util::num;
-// use crate::util::convert::nat_to_f;
 // use bellpepper_nonnative::mp::bignat::Polynomial;
 
 pub fn verify_difficulty_update<Scalar, CS> 
@@ -112,6 +114,59 @@ CS: ConstraintSystem<Scalar>,
     Ok(t_new)
 }
 
+/// Target of the first block of an epoch before its compact encoding, as in
+/// `HeaderChainState::next_target`: `prev_target` scaled by the timespan of the previous epoch,
+/// `last_timestamp - first_timestamp` clamped to a factor of four of `TARGET_TIMESPAN`,
+/// and capped at `pow_limit`, which is `max_target()` on mainnet.
+/// `prev_target` must be well formed and the timestamps must fit in 32 bits.
+pub fn retarget<Scalar, CS>(
+    mut cs: CS,
+    prev_target: &BigNat<Scalar>,
+    first_timestamp: &AllocatedNum<Scalar>,
+    last_timestamp: &AllocatedNum<Scalar>,
+    pow_limit: &BigInt,
+) -> Result<BigNat<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let limb_width = prev_target.params.limb_width;
+    let constant = |n: BigInt, n_limbs: usize| BigNat::constant::<CS>(&n, limb_width, n_limbs);
+    let first = BigNat::from_num(cs.namespace(|| "first timestamp"), num::Num::from(first_timestamp.clone()), limb_width, 1)?;
+    let last = BigNat::from_num(cs.namespace(|| "last timestamp"), num::Num::from(last_timestamp.clone()), limb_width, 1)?;
+
+    // clamp(last - first, low, high) = clamp(last, first + low, first + high) - first,
+    // which does not underflow when the last timestamp is before the first one
+    let low = first
+        .add::<CS>(&constant(BigInt::from(TARGET_TIMESPAN / 4), 1)?)?
+        .carry(cs.namespace(|| "first + low"))?;
+    let high = first
+        .add::<CS>(&constant(BigInt::from(TARGET_TIMESPAN * 4), 1)?)?
+        .carry(cs.namespace(|| "first + high"))?;
+    let clamped = last.clamp(cs.namespace(|| "clamp"), &low, &high)?;
+    let timespan = num::Num::new(
+        clamped
+            .value
+            .as_ref()
+            .zip(first.value.as_ref())
+            .and_then(|(c, f)| nat_to_f(&(c - f))),
+        clamped.limbs.iter().enumerate().fold(LinearCombination::zero(), |lc, (i, limb)| {
+            lc + (nat_to_f(&(BigInt::from(1) << (i * limb_width))).unwrap(), limb)
+        }) - &first.limbs[0],
+    );
+    let timespan = BigNat::from_num(cs.namespace(|| "timespan"), timespan, limb_width, 1)?;
+
+    let scaled = prev_target.mult(cs.namespace(|| "target * timespan"), &timespan)?;
+    let (target, _) = scaled.div_rem(
+        cs.namespace(|| "divided by TARGET_TIMESPAN"),
+        &constant(BigInt::from(TARGET_TIMESPAN), 1)?,
+    )?;
+    target.min(
+        cs.namespace(|| "cap"),
+        &constant(pow_limit.clone(), prev_target.params.n_limbs)?,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::ops::{AddAssign, MulAssign};
@@ -119,6 +174,7 @@ mod tests {
     use crate::btc_validation::difficulty_update::*;
     use bellpepper_core::test_cs::TestConstraintSystem;
     use crate::util::scalar::Fr;
+    use crate::util::fuzz::FuzzingConstraintSystem;
 
     #[test]
     fn test_trivial_difficulty() {
@@ -248,4 +304,76 @@ mod tests {
         assert_eq!(target_u64[1], 0x3894871D1837E9E5);
         assert_eq!(target_u64[0], 0x04B6B1D1837E9E50);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_calc_new_target_soundness() {
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let prev_target = AllocatedNum::alloc(cs.namespace(|| "previous target"), || Ok(Fr::from_u128(0x058ebe << 100))).unwrap();
+        let time_sum = AllocatedNum::alloc(cs.namespace(|| "sum of timestamps"), || Ok(Fr::from(1_136_346u64))).unwrap();
+        let new_target = calculate_difficulty_update(cs.namespace(|| "calculates difficulty"), &prev_target, &time_sum).unwrap();
+        cs.fix("previous target");
+        cs.fix("sum of timestamps");
+        cs.output_bignat("new target", &new_target);
+        cs.assert_sound();
+    }
+
+    #[test]
+    fn test_retarget() {
+        use crate::btc_validation::witness::{bits_from_target, max_target, target_from_bits};
+        // blocks 796320 to 798335, retargeted at block 798336 to nBits 0x17053894
+        let timespan = 13 * 24 * 3600 + 3 * 3600 + 39 * 60 + 6;
+        let cases = [
+            (target_from_bits(0x17058ebe), 1000, 1000 + timespan),
+            // a timespan below a quarter of the expected one, or negative, is clamped
+            (target_from_bits(0x17058ebe), 1000, 1001),
+            (target_from_bits(0x17058ebe), 1000, 999),
+            // the target is capped at the maximum
+            (max_target(), 0, 5 * TARGET_TIMESPAN),
+        ];
+        for (prev_target, first, last) in cases {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let prev = BigNat::alloc_from_nat(cs.namespace(|| "previous target"), || Ok(prev_target.clone()), 64, 4).unwrap();
+            prev.assert_well_formed(cs.namespace(|| "previous target range")).unwrap();
+            let first_num = AllocatedNum::alloc(cs.namespace(|| "first"), || Ok(Fr::from(first))).unwrap();
+            let last_num = AllocatedNum::alloc(cs.namespace(|| "last"), || Ok(Fr::from(last))).unwrap();
+            let target = retarget(cs.namespace(|| "retarget"), &prev, &first_num, &last_num, &max_target()).unwrap();
+            assert!(cs.is_satisfied());
+
+            let expected_timespan = last.saturating_sub(first).clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4);
+            let expected = (&prev_target * expected_timespan / TARGET_TIMESPAN).min(max_target());
+            assert_eq!(target.value, Some(expected));
+        }
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let prev = BigNat::alloc_from_nat(cs.namespace(|| "previous target"), || Ok(target_from_bits(0x17058ebe)), 64, 4).unwrap();
+        let first_num = AllocatedNum::alloc(cs.namespace(|| "first"), || Ok(Fr::from(0u64))).unwrap();
+        let last_num = AllocatedNum::alloc(cs.namespace(|| "last"), || Ok(Fr::from(timespan))).unwrap();
+        let target = retarget(cs.namespace(|| "retarget"), &prev, &first_num, &last_num, &max_target()).unwrap();
+        assert_eq!(bits_from_target(target.value.as_ref().unwrap()), 0x17053894);
+    }
+
+    #[test]
+    fn test_retarget_soundness() {
+        use crate::btc_validation::witness::{max_target, target_from_bits};
+        // Given the previous target and the timestamps, the new target is unique,
+        // whether the timespan is kept, clamped or the target capped
+        let cases = [
+            (target_from_bits(0x17058ebe), 1000, 1000 + TARGET_TIMESPAN - 3600),
+            (target_from_bits(0x17058ebe), 1000, 999),
+            (max_target(), 0, 5 * TARGET_TIMESPAN),
+        ];
+        for (prev_target, first, last) in cases {
+            let mut cs = FuzzingConstraintSystem::<Fr>::new();
+            let prev = BigNat::alloc_from_nat(cs.namespace(|| "previous target"), || Ok(prev_target.clone()), 64, 4).unwrap();
+            prev.assert_well_formed(cs.namespace(|| "previous target range")).unwrap();
+            let first_num = AllocatedNum::alloc(cs.namespace(|| "first"), || Ok(Fr::from(first))).unwrap();
+            let last_num = AllocatedNum::alloc(cs.namespace(|| "last"), || Ok(Fr::from(last))).unwrap();
+            for input in ["previous target", "previous target range", "first", "last"] {
+                cs.fix(input);
+            }
+            let target = retarget(cs.namespace(|| "retarget"), &prev, &first_num, &last_num, &max_target()).unwrap();
+            cs.output_bignat("target", &target);
+            cs.assert_sound();
+        }
+    }
+}
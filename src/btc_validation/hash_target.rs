@@ -1,10 +1,10 @@
 use bellpepper::gadgets::boolean::Boolean;
 use bellpepper_core::{ConstraintSystem, SynthesisError};
-use bellpepper::gadgets::num::AllocatedNum;
 use ff::PrimeField;
-// use crate::util::num;
-use crate::{btc_validation::median, mp::bignat::BigNat};
+use crate::mp::bignat::BigNat;
 
+/// Checks if the block hash is less than the target, both given as two 128-bit halves.
+/// The halves are allocated as the limbs of two 256-bit `BigNat`s and compared limb-wise.
 pub fn verify_current_hash<Scalar, CS> 
 (   mut cs: CS, 
     block_hash_u: u128,
@@ -15,18 +15,12 @@ where
 Scalar: PrimeField,
 CS: ConstraintSystem<Scalar>,
 {
-    let hash_u = AllocatedNum::alloc(cs.namespace(|| "Most significant 128 bits of hash"), || Ok(Scalar::from_u128(block_hash_u))).unwrap();
-    let hash_l = AllocatedNum::alloc(cs.namespace(|| "Least significant 128 bits of hash"), || Ok(Scalar::from_u128(block_hash_l))).unwrap();
+    let hash = BigNat::alloc_from_limbs(cs.namespace(|| "hash"), || Ok(vec![Scalar::from_u128(block_hash_l), Scalar::from_u128(block_hash_u)]), None, 128, 2)?;
+    let target = BigNat::alloc_from_limbs(cs.namespace(|| "target"), || Ok(vec![Scalar::from_u128(target_l), Scalar::from_u128(target_u)]), None, 128, 2)?;
+    hash.assert_well_formed(cs.namespace(|| "hash limbs"))?;
+    target.assert_well_formed(cs.namespace(|| "target limbs"))?;
 
-    let target_upper = AllocatedNum::alloc(cs.namespace(|| "Most significant 128 bits of target"), || Ok(Scalar::from_u128(target_u))).unwrap();
-    let target_lower = AllocatedNum::alloc(cs.namespace(|| "Least significant 128 bits of target"), || Ok(Scalar::from_u128(target_l))).unwrap();
-
-    let res_upper_lt = median::less_than(&hash_u, &target_upper, 126usize).unwrap();
-    let res_upper_eq = BigNat::equals(cs.namespace(|| "Upper 128 bits equality check"), &hash_u, &target_upper).unwrap();
-    let res_lower_lt = median::less_than(&hash_l, &target_lower, 126usize).unwrap();
-
-    let partial_res = Boolean::and(cs.namespace(|| "Upper 128 bits equal and lower hash bits less than target lower bits"), &res_upper_eq, &res_lower_lt).unwrap();
-    return Boolean::or(cs.namespace(|| "Is hash less than target"), &res_upper_lt, &partial_res);
+    hash.is_less_than(cs.namespace(|| "Is hash less than target"), &target)
 }
 
 #[cfg(test)]
@@ -34,6 +28,7 @@ mod tests {
     use crate::btc_validation::hash_target::*;
     use bellpepper_core::test_cs::TestConstraintSystem;
     use crate::util::scalar::Fr;
+    use crate::util::fuzz::FuzzingConstraintSystem;
 
     #[test]
     fn test_hash_target() {
@@ -55,5 +50,34 @@ mod tests {
                                         tar_l).unwrap();
         
         assert_eq!(r2.get_value().unwrap(), true);
+        assert!(cs.is_satisfied());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hash_target_upper_halves() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+
+        // halves above 2^126 are compared correctly
+        let r = verify_current_hash(cs.namespace(|| "equal upper"), u128::MAX, 5, u128::MAX, 6).unwrap();
+        assert!(r.get_value().unwrap());
+
+        let r = verify_current_hash(cs.namespace(|| "larger upper"), u128::MAX, 0, u128::MAX - 1, u128::MAX).unwrap();
+        assert!(!r.get_value().unwrap());
+
+        let r = verify_current_hash(cs.namespace(|| "equal"), 1 << 127, 1, 1 << 127, 1).unwrap();
+        assert!(!r.get_value().unwrap());
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_hash_target_soundness() {
+        for (hash_u, target_u) in [(u128::MAX - 1, u128::MAX), (u128::MAX, u128::MAX), (1 << 127, 1)] {
+            let mut cs = FuzzingConstraintSystem::<Fr>::new();
+            let r = verify_current_hash(cs.namespace(|| "verify"), hash_u, 5, target_u, 6).unwrap();
+            cs.fix("verify/hash");
+            cs.fix("verify/target");
+            cs.output_boolean("hash < target", &r);
+            cs.assert_sound();
+        }
+    }
+}
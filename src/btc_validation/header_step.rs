@@ -1,25 +1,84 @@
+use std::cmp::max;
 use std::marker::PhantomData;
 
-use crate::btc_validation::{difficulty_update, median};
+use crate::btc_validation::{difficulty_update, median, mmr, sha256d};
+use crate::btc_validation::compact::{self, CompactStep};
+use crate::btc_validation::mmr::MerkleMountainRange;
+use crate::btc_validation::witness::{self, BlockHeaderWitness, HeaderChainState, RawHeader, WitnessError};
 
 use bellpepper_core::{
-    boolean,
+    boolean::{self, Boolean},
     num::AllocatedNum,
     ConstraintSystem, SynthesisError,
 };
 use ff::{PrimeField, PrimeFieldBits};
-use bellpepper::gadgets::sha256;
 use crate::mp::bignat::BigNat;
-use crate::util::convert::f_to_nat;
+use crate::util::convert::nat_to_f;
+use crate::util::num::Num;
+use crate::util::pack::{self, BitOrder};
+use crate::util::profile::{Cost, CostReport, ProfilingConstraintSystem};
+use crate::OptionExt;
 // use bellpepper::gadgets::num::{AllocatedNum, Num};
 use nova_snark::traits::circuit::StepCircuit;
+use num_bigint::BigInt;
+
+/// State elements exposed next to the state commitment in compact mode:
+/// tip hash, chainwork and block hash MMR root
+pub const COMPACT_HEADLINES: [usize; 3] = [0, 15, 16];
+
+/// Consensus rules checked by `BlockHeader::synthesize`, in the order of `BlockHeader::cost_report`
+pub const CONSENSUS_RULES: [&str; 10] = [
+    "header decoding",
+    "previous block hash",
+    "target from nBits",
+    "block hash (SHA256d)",
+    "proof of work",
+    "median time past",
+    "chain work",
+    "difficulty period",
+    "state update",
+    "block hash MMR",
+];
+
+/// Index in `CONSENSUS_RULES` of the rule checked by the top-level namespace or constraint `name`
+fn consensus_rule(name: &str) -> Option<usize> {
+    let rule = match name {
+        n if n.starts_with("dummy2 ") => 0,
+        "prev. hash from current block equals the last block hash" => 1,
+        n if n.starts_with("prev. hash bit ") => 1,
+        "Block target" | "target limbs" | "mantissa sign" | "exponent bits" | "target from threshold"
+        | "target matches threshold" => 2,
+        "SHA 256" | "SHA 256d" | "current block hash" => 3,
+        "Is PoW consensus achieved?" | "hash <= target" => 4,
+        "median time past" | "current timestamp" | "current timestamp from header" | "valid timestamp"
+        | "median < current timestamp" => 5,
+        "target + 1" | "block work" | "work or difficulty" | "block_work = quotient" | "total work"
+        | "z_out[15] = z_i[15] + block_work" => 6,
+        "0 = (target - z_i[12]) * z_i[14]" | "delta_inv" | "t" | "t = z_i[14] * delta_inv"
+        | "z_i[14] * (t - 1) == 0" | "start time epoch"
+        | "start_time_epoch - z_i[13] = (1 - t) * (curr_timestamp - z_i[13])" | "last_delta_inv" | "is_last"
+        | "(z_i[14] - 2015) * last_delta_inv = 1 - is_last" | "(z_i[14] - 2015) * is_last == 0"
+        | "target counter" | "z_out[14] = (z_i[14] + 1) * (1 - is_last)" | "previous target"
+        | "previous target bound" | "previous target <= pow limit" | "retarget" | "truncated retarget"
+        | "mantissa normalized" | "retarget mantissa normalized" => 7,
+        n if n.starts_with("mantissa matches retarget ") => 7,
+        n if n.starts_with("timestamp out ") => 8,
+        "current SHA256d hash out" | "current timestamp out" | "current target out" | "current start time epoch out" => 8,
+        n if n.starts_with("mmr peak ") => 9,
+        "append block hash" => 9,
+        _ => return None,
+    };
+    Some(rule)
+}
 
 #[derive(Clone, Debug)]
 pub struct BlockHeader <F>
 where
     F: PrimeField,
 {
-    block_head: [u64; 10],
+    witness: BlockHeaderWitness<F>,
+    /// Proof of work limit of the chain, a constant of the circuit
+    pow_limit: BigInt,
     marker: PhantomData<F>,
 }
 
@@ -27,87 +86,135 @@ impl<F> Default for BlockHeader <F>
 where
     F: PrimeField + PrimeFieldBits,
 {
+    /// Blank step of the mainnet circuit
     fn default() -> Self {
-        Self {
-            block_head: [0u64; 10],
-            marker: Default::default(),
-        }
+        Self::blank(witness::max_target())
     }
 }
 
+fn nat_to_scalar<F: PrimeField>(n: &BigInt) -> Result<F, SynthesisError> {
+    nat_to_f(n).ok_or(SynthesisError::Unsatisfiable)
+}
 
 impl<F> BlockHeader<F>
 where
     F: PrimeField + PrimeFieldBits,
 {
-    // Produces the intermediate blocks when a message is hashed
-    pub fn new_blocks(input: Vec<[u64;10]>) -> Vec<Self> {
-        // let block_seq = sha256_msg_block_sequence(input);
+    /// Step proving the header in `witness`, see `HeaderChainState::append`, on a chain whose
+    /// proof of work limit is `pow_limit`. The limit is a constant of the circuit, so the public
+    /// parameters of a chain are set up with `blank` at the same limit.
+    pub fn new(witness: BlockHeaderWitness<F>, pow_limit: BigInt) -> Self {
+        Self {
+            witness,
+            pow_limit,
+            marker: PhantomData,
+        }
+    }
+
+    /// Step without a witness, for the shape of the circuit of a chain whose proof of work
+    /// limit is `pow_limit`
+    pub fn blank(pow_limit: BigInt) -> Self {
+        Self::new(BlockHeaderWitness::default(), pow_limit)
+    }
+
+    /// Produces the steps for `input`, starting from the state after block 123455
+    pub fn new_blocks(input: Vec<[u64;10]>) -> Result<Vec<Self>, WitnessError> {
+        Self::new_blocks_from(&mut Self::initial_state(), input)
+    }
+
+    /// Produces the steps for `input`, continuing from `state`.
+    /// `state` is advanced past every header of `input`.
+    pub fn new_blocks_from(state: &mut HeaderChainState<F>, input: Vec<[u64;10]>) -> Result<Vec<Self>, WitnessError> {
         input
             .into_iter()
-            .map(|b| BlockHeader {
-                block_head: b,
-                marker: PhantomData,
+            .map(|b| {
+                let witness = state.append(RawHeader(b))?;
+                Ok(Self::new(witness, state.pow_limit.clone()))
             })
             .collect()
     }
 
+    /// Produces the steps for `input` in compact mode, where the public state is a commitment
+    /// to the full state plus the `COMPACT_HEADLINES` values. `state` is advanced as in `new_blocks_from`.
+    pub fn new_compact_blocks(state: &mut HeaderChainState<F>, input: Vec<[u64;10]>) -> Result<Vec<CompactStep<F, Self>>, WitnessError> {
+        input
+            .into_iter()
+            .map(|b| {
+                let z = state.z();
+                let step = Self::new(state.append(RawHeader(b))?, state.pow_limit.clone());
+                Ok(CompactStep::new(step, z, COMPACT_HEADLINES.to_vec()))
+            })
+            .collect()
+    }
 
-    pub fn initial_z_i_scalars() -> Vec<F>
-    {
-        let mut initial_z = Vec::new();
-
-        // let n = 0x0000000000000b60bc96a44724fd72daf9b92cf8ad00510b5224c6253ac40095;
-        let curr_hash = F::from_str_vartime("18283544428642297129396529020735695233361821945456783020785813").unwrap();
-        initial_z.push(curr_hash);
+    pub fn initial_compact_z_i_scalars() -> Vec<F> {
+        compact::compact_state(&Self::initial_z_i_scalars(), &COMPACT_HEADLINES)
+    }
 
-        // previous 11 timestamps
-        let t1 = F::from_str_vartime("1305191152").unwrap(); //123445
-        initial_z.push(t1);
-        
-        let t2 = F::from_str_vartime("1305191688").unwrap(); //123446
-        initial_z.push(t2);
-        
-        let t3 = F::from_str_vartime("1305193319").unwrap(); //123447
-        initial_z.push(t3);
-        
-        let t4 = F::from_str_vartime("1305194571").unwrap(); //123448
-        initial_z.push(t4);
-        
-        let t5 = F::from_str_vartime("1305194986").unwrap(); //123449
-        initial_z.push(t5);
-        
-        let t6 = F::from_str_vartime("1305195947").unwrap(); //123450
-        initial_z.push(t6);
-        
-        let t7 = F::from_str_vartime("1305197900").unwrap(); //123451
-        initial_z.push(t7);
-        
-        let t8 = F::from_str_vartime("1305199436").unwrap(); //123452
-        initial_z.push(t8);
-        
-        let t9 = F::from_str_vartime("1305200301").unwrap(); //123453
-        initial_z.push(t9);
-        
-        let t10 = F::from_str_vartime("1305200460").unwrap(); //123454
-        initial_z.push(t10);
-        
-        let t11 = F::from_str_vartime("1305200584").unwrap(); //123455
-        initial_z.push(t11);
+    /// Computes the SHA256d hash of the header natively, packed into a field element
+    /// the same way as the hash in `z[0]` (hash bytes read as a little-endian number).
+    pub fn block_hash_scalar(&self) -> F {
+        witness::le_bytes_to_f(&self.witness.header.hash())
+    }
 
-        let target = F::from_str_vartime("171262555713783851185422181139260521316022447660158187451973632").unwrap();
-        initial_z.push(target);
+    pub fn witness(&self) -> &BlockHeaderWitness<F> {
+        &self.witness
+    }
 
-        let start_time_epoch = F::from(1304975844u64);
-        initial_z.push(start_time_epoch);
+    pub fn pow_limit(&self) -> &BigInt {
+        &self.pow_limit
+    }
 
-        let counter = F::from(480u64);
-        initial_z.push(counter);
+    /// Cost of a step for each of the `CONSENSUS_RULES`, without the step inputs.
+    /// Parts of the circuit which no rule claims are reported last, as "other".
+    pub fn cost_report() -> Result<CostReport, SynthesisError> {
+        let mut cs = ProfilingConstraintSystem::<F>::new(1);
+        let z = (0..Self::default().arity())
+            .map(|i| AllocatedNum::alloc(cs.namespace(|| format!("step input {}", i)), || Ok(F::ZERO)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::default().synthesize(&mut cs, &z)?;
+
+        let mut costs = vec![Cost::default(); CONSENSUS_RULES.len() + 1];
+        for (name, cost) in cs.costs() {
+            if !name.starts_with("step input ") {
+                costs[consensus_rule(name).unwrap_or(CONSENSUS_RULES.len())] += *cost;
+            }
+        }
+        let other = costs.pop().unwrap();
+        let mut parts = CONSENSUS_RULES.iter().map(|r| r.to_string()).zip(costs).collect::<Vec<_>>();
+        if other != Cost::default() {
+            parts.push(("other".to_string(), other));
+        }
+        let total = parts.iter().fold(Cost::default(), |acc, (_, c)| acc + *c);
+        Ok(CostReport { parts, total })
+    }
 
-        let chain_work = F::ZERO;
-        initial_z.push(chain_work);
+    /// State after block 123455
+    pub fn initial_state() -> HeaderChainState<F> {
+        HeaderChainState {
+            // 0000000000000b60bc96a44724fd72daf9b92cf8ad00510b5224c6253ac40095
+            tip_hash: [
+                0x95, 0x00, 0xc4, 0x3a, 0x25, 0xc6, 0x24, 0x52, 0x0b, 0x51, 0x00, 0xad, 0xf8, 0x2c, 0xb9, 0xf9,
+                0xda, 0x72, 0xfd, 0x24, 0x47, 0xa4, 0x96, 0xbc, 0x60, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+            // blocks 123445 to 123455
+            timestamps: [
+                1305191152, 1305191688, 1305193319, 1305194571, 1305194986, 1305195947,
+                1305197900, 1305199436, 1305200301, 1305200460, 1305200584,
+            ],
+            target: witness::target_from_bits(0x1a6a93b3),
+            epoch_start_time: 1304975844,
+            counter: 480,
+            chain_work: BigInt::from(0u64),
+            // no block hashes accumulated yet
+            mmr: MerkleMountainRange::new(),
+            pow_limit: witness::max_target(),
+        }
+    }
 
-        initial_z
+    pub fn initial_z_i_scalars() -> Vec<F>
+    {
+        Self::initial_state().z()
     }
 }
 
@@ -116,7 +223,7 @@ where
     F: PrimeField + PrimeFieldBits,
 {   
     fn arity(&self) -> usize {
-        16
+        18
     }
 
     fn synthesize<CS: ConstraintSystem <F> >(
@@ -124,190 +231,118 @@ where
         cs: &mut CS,
         z: &[AllocatedNum<F>],
     ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
-        let header = self.block_head.to_vec();
+        let header = &self.witness.header;
         let z_i = (*z).to_vec();
-        
-        // println!("{}", header[0]);
-        // println!("{}", header[1]);
-        // println!("{}",header[2]);
-        // println!("{}", header[3]);
-        // let hash_s = z_i[1].get_value().unwrap();
-        // let (_s1000, hash32) = f_to_nat(&hash_s).to_u32_digits();
-        // for (_i, h) in hash32.iter().enumerate() {
-        //     println!("{}", *h);
-        // }
-        
+
+        // Header bits, most significant bit of every byte first
+        let mut preimage_vec: Vec<boolean::Boolean> = Vec::new();
+        for (i, preimage64) in header.0.iter().enumerate() {
+            let mut dummy2 = boolean::u64_into_boolean_vec_le(cs.namespace(|| format!("dummy2 {}", i)), Some(*preimage64))?;
+            dummy2.reverse();
+            preimage_vec.append(&mut dummy2);
+        }
 
         // 1. Check if prevHash from z and prev_hash_from_curr_block are equal 
         //
         // Taking the example of block no. 123456
         // 0x010000009500c43a 25c624520b5100ad f82cb9f9da72fd24 47a496bc600b0000 000000006cd86237 0395dedf1da2841c cda0fc489e3039de 5f1ccddef0e83499 1a65600ea6c8cb4d b3936a1ae3143991
         // here 0x9500c43a25c624520b5100adf82cb9f9da72fd2447a496bc600b000000000000 is the prevhash (0000000000000b60bc96a44724fd72daf9b92cf8ad00510b5224c6253ac40095)
-
-        let mut prev_hash_from_curr_block_vec: Vec<u64> = Vec::new();
-        for i in 0..4 {
-            let hash_vec = (header[i] % (1 << 32 as u64)) * (1<<32 as u64) + header[i+1] / (1 << 32 as u64);
-            prev_hash_from_curr_block_vec.push(hash_vec);
-        }
-
-        let mut prev_hash_from_curr_block: Vec<boolean::Boolean> = Vec::new();
-        for (i, hash64) in prev_hash_from_curr_block_vec.iter().enumerate() {
-            let mut dummy = boolean::u64_into_boolean_vec_le(cs.namespace(|| format!("dummy {}", i)), Some(*hash64)).unwrap();
-            dummy.reverse();
-            prev_hash_from_curr_block.append(&mut dummy);
+        // Like the hash in z_i[0], it is read as a little-endian number.
+        // The 256 bits of the field do not pack injectively, z_i[0] + p would match as well.
+        // The tip is below the proof of work limit, so the top three bits, the first ones of
+        // the last byte, are zero and the remaining 253 bits fit in a field element.
+        for (i, bit) in preimage_vec[8 * 35..8 * 35 + 3].iter().enumerate() {
+            Boolean::enforce_equal(cs.namespace(|| format!("prev. hash bit {}", 255 - i)), bit, &Boolean::constant(false))?;
         }
-
-        let prev_hash = AllocatedNum::alloc(cs.namespace(|| {"last block hash"}), || {
-            let mut sum: F = F::ZERO;
-
-            for (i, b) in prev_hash_from_curr_block.iter().enumerate() {
-                let exponent = 16 * (i/8) + 7 - i;
-                if exponent >= 128 {
-                    let power_127 = F::from_u128(1 << 127);
-                    let mut power_2 = if (*b).get_value().unwrap() { F::from_u128(1 << (exponent - 127)) }  else { F::from_u128(0u128) };
-                    power_2.mul_assign(&power_127);
-                    sum.add_assign(&power_2);
-                }
-                else {
-                    let power_2 = if (*b).get_value().unwrap() { F::from_u128(1 << exponent) } else { F::from_u128(0u128) };
-                    sum.add_assign(&power_2);
-                } 
-            }
-
-            Ok(sum)
-        }).unwrap();
-
-        // equality check
-        let r_prev_hash = BigNat::equals(cs.namespace(|| {"Is prev. hash from current block equal to the last block hash?"}), &z_i[0], &prev_hash).unwrap();
-        assert!(r_prev_hash.get_value().or(Some(true)).unwrap());
+        let prev_hash = pack::pack_num::<F, CS>(&preimage_vec[8 * 4..8 * 36], BitOrder::LeBytes);
+        cs.enforce(
+            || "prev. hash from current block equals the last block hash",
+            |_| prev_hash.num,
+            |lc| lc + CS::one(),
+            |lc| lc + z_i[0].get_variable(),
+        );
         
         // 2. Check if current hash <= target
         //
         // Target computation from threshold
         // Taking the example of block no. 123456
         // 0x010000009500c43a 25c624520b5100ad f82cb9f9da72fd24 47a496bc600b0000 000000006cd86237 0395dedf1da2841c cda0fc489e3039de 5f1ccddef0e83499 1a65600ea6c8cb4d b3936a1ae3143991
-        // here 0x1a6a93b3 is the threshold
-        let n_bits = header[9] >> 32;
-        let b0 = n_bits % 256u64;
-        let b3 = n_bits >> 24;
-        let b2 = n_bits >> 16 - b3 << 24;
-        let b1 = n_bits >> 8 - b3 << 24 - b2 << 16;
-        
-        let target = AllocatedNum::alloc(cs.namespace(|| "Block target"), || {
-            let thresh_mantissa = b1 << 16 + b2 << 8 + b3;
-            let exponent = (b0 - 3) as usize;
-
-            let targ: F = if exponent >= 13 {
-                let partial_scalar = F::from_u128((thresh_mantissa as u128) << (8 * 13) as u128);
-                let mut scale = F::from_u128(1 << (8 * (exponent - 13)) as u128);
-                scale.mul_assign(&partial_scalar);
-
-                scale
-            }
-            else {
-                F::from_u128((thresh_mantissa as u128) << 8 * exponent) 
-            };
-
-            Ok(targ)
-        }).unwrap();
+        // here 0x1a6a93b3 is the threshold, target = 0x6a93b3 << 8 * (0x1a - 3)
+        let target = AllocatedNum::alloc(cs.namespace(|| "Block target"), || nat_to_scalar(&self.witness.target))?;
+        let target_nat = BigNat::from_num(cs.namespace(|| "target limbs"), Num::from(target.clone()), 64, 4)?;
+
+        // The mantissa is signed, negative targets are invalid
+        let mantissa = pack::pack_bignat::<F, CS>(&preimage_vec[8 * 72..8 * 75], BitOrder::LeBytes, 64);
+        Boolean::enforce_equal(cs.namespace(|| "mantissa sign"), &preimage_vec[8 * 74], &Boolean::constant(false))?;
+
+        // exponent - 3 must lie in [0, 32)
+        let exponent = Num::new(
+            Some(F::from((header.bits() >> 24) as u64) - F::from(3u64)),
+            pack::pack_num::<F, CS>(&preimage_vec[8 * 75..8 * 76], BitOrder::LeBytes).num - (F::from(3u64), CS::one()),
+        );
+        let exponent_bits = exponent.decompose(cs.namespace(|| "exponent bits"), 5)?;
+        let target_from_bits = mantissa.shl_var(cs.namespace(|| "target from threshold"), &exponent_bits, 8)?;
+        target_from_bits.equal(cs.namespace(|| "target matches threshold"), &target_nat)?;
 
         // Current block hash computation
-        let mut preimage_vec: Vec<boolean::Boolean> = Vec::new();
-        for (i, preimage64) in header.iter().enumerate() {
-            let mut dummy2 = boolean::u64_into_boolean_vec_le(cs.namespace(|| format!("dummy2 {}", i)), Some(*preimage64)).unwrap();
-            dummy2.reverse();
-            preimage_vec.append(&mut dummy2);
-        }
-
-        let out_sha256 = sha256::sha256(cs.namespace(|| "SHA 256"), &preimage_vec).unwrap();
-        let out = sha256::sha256(cs.namespace(|| "SHA 256d"), &out_sha256).unwrap();
+        let out = sha256d::sha256d_header(cs.namespace(|| "SHA 256d"), &preimage_vec)?;
         
-        let curr_hash = AllocatedNum::alloc(cs.namespace(|| {"current block hash"}), || {
-            let mut sum: F = F::ZERO;
-
-            for (i, b) in out.iter().enumerate() {
-                let exponent = 16 * (i/8) + 7 - i;
-                if exponent >= 128 {
-                    let power_127 = F::from_u128(1 << 127);
-                    let mut power_2 = if (*b).get_value().unwrap() { F::from_u128(1 << (exponent - 127)) }  else { F::from_u128(0u128) };
-                    power_2.mul_assign(&power_127);
-                    sum.add_assign(&power_2);
-                }
-                else {
-                    let power_2 = if (*b).get_value().unwrap() { F::from_u128(1 << exponent) } else { F::from_u128(0u128) };
-                    sum.add_assign(&power_2);
-                } 
-            }
-
-            Ok(sum)
-        }).unwrap();
+        // The digest bytes are read as a little-endian number.
+        // The packing wraps around the field for hashes of CAPACITY bits or more, but the
+        // hash is below the target, which is a field element, once the PoW check holds.
+        let curr_hash = pack::pack_num::<F, CS>(&out, BitOrder::LeBytes).as_sapling_allocated_num(cs.namespace(|| "current block hash"))?;
+        let hash_nat = pack::pack_bignat::<F, CS>(&out, BitOrder::LeBytes, 64);
 
-        // less than check
-        let r_curr_hash_targ = median::less_than(cs.namespace(|| "Is PoW consensus achieved?"), &curr_hash, &target, 224usize).unwrap();
-        assert!(r_curr_hash_targ.get_value().or(Some(true)).unwrap());
+        // hash <= target, over the full 256-bit hash
+        let r_target_hash = target_nat.is_less_than(cs.namespace(|| "Is PoW consensus achieved?"), &hash_nat)?;
+        Boolean::enforce_equal(cs.namespace(|| "hash <= target"), &r_target_hash, &Boolean::constant(false))?;
 
         // 3. Check if timestamp of the current block is greater than the median of previous 11 timestamps
         //
-        let mut times_vec: Vec<u32> = Vec::new(); 
-        for i in 1..=11 {
-            let timestamp = z_i[i].get_value().or(Some(F::ONE)).unwrap();
-            let (_s, time32) = f_to_nat(&timestamp).to_u32_digits();
-            times_vec.push(time32[0]);
-        }
-
-        // compute median
-        let median = median::compute_median_timestamp(&mut times_vec);
-
-        // verify median
-        let r_median = median::verify_median_timestamp(cs.namespace(|| "median verify"), &mut times_vec, median).unwrap();
-        assert!(r_median.get_value().or(Some(true)).unwrap());
+        // median of the timestamps in the state, z_i[1..=11]
+        let median_fe = median::median_time_past(cs.namespace(|| "median time past"), &z_i[1..=median::MEDIAN_TIME_SPAN], 32usize)?;
 
         // check if median < current timestamp
         // Taking the example of block no. 123456
         // 0x010000009500c43a 25c624520b5100ad f82cb9f9da72fd24 47a496bc600b0000 000000006cd86237 0395dedf1da2841c cda0fc489e3039de 5f1ccddef0e83499 1a65600ea6c8cb4d b3936a1ae3143991
         // here 0xa6c8cb4d is the current timestamp (supposed to be 0x4dcbc8a6)
-        let n_time = header[8] % (1 << 32);
-        let b_t0 = n_time % 256u64; // 4d
-        let b_t3 = n_time >> 24;  // a6
-        let b_t2 = n_time >> 16 - b_t3 << 24; // c8
-        let b_t1 = n_time >> 8 - b_t3 << 24 - b_t2 << 16; // cb
-        let n_time_endian = b_t0 << 24 + b_t1 << 16 + b_t2 << 8 + b_t1;
-
-        let median_fe = AllocatedNum::alloc(cs.namespace(|| "median"), || Ok(F::from(median as u64))).unwrap();
-        let curr_timestamp = AllocatedNum::alloc(cs.namespace(|| "current timestamp"), || Ok(F::from(n_time_endian))).unwrap();
-        let r_time = median::less_than(cs.namespace(|| "valid timestamp"), &median_fe, &curr_timestamp, 32usize).unwrap();
-        assert!(r_time.get_value().or(Some(true)).unwrap());
+        let curr_timestamp = AllocatedNum::alloc(cs.namespace(|| "current timestamp"), || Ok(F::from(self.witness.timestamp as u64)))?;
+        let timestamp = pack::pack_num::<F, CS>(&preimage_vec[8 * 68..8 * 72], BitOrder::LeBytes);
+        cs.enforce(
+            || "current timestamp from header",
+            |_| timestamp.num,
+            |lc| lc + CS::one(),
+            |lc| lc + curr_timestamp.get_variable(),
+        );
+        let r_time = median::less_than(cs.namespace(|| "valid timestamp"), &median_fe, &curr_timestamp, 32usize)?;
+        Boolean::enforce_equal(cs.namespace(|| "median < current timestamp"), &r_time, &Boolean::constant(true))?;
 
         // 4. Total work addition
         //
-        let max_target = AllocatedNum::alloc(cs.namespace(|| "maximum target"), || {
-            let power_127 = F::from_u128(1 << 127);
-            let mut max_thresh = F::from_u128(0xFFFF << 81);
-            max_thresh.mul_assign(&power_127);
-
-            Ok(max_thresh)
-        }).unwrap();
+        // block_work = 2^256 / (target + 1), the expected number of hashes for a hash <= target
+        let hash_space = BigNat::constant::<CS>(&(BigInt::from(1u64) << 256), 64, 5)?;
+        let target_plus_one = target_nat.add::<CS>(&BigNat::one::<CS>(64))?.carry(cs.namespace(|| "target + 1"))?;
+        let (work_nat, _) = hash_space.div_rem(cs.namespace(|| "block work"), &target_plus_one)?;
 
-        let block_work = AllocatedNum::alloc(cs.namespace(|| "work or difficulty"), || {
-            let diff = max_target.get_value().unwrap();
-            let targ = target.get_value().unwrap();
-
-            let diff_big = f_to_nat(&diff);
-            let tar_big = f_to_nat(&targ);
-            let work = diff_big / tar_big;
-
-            let (_s2, work_u64) = work.to_u64_digits();
-
-            Ok(F::from(work_u64[0] as u64))
-        }).unwrap();
+        let block_work = AllocatedNum::alloc(cs.namespace(|| "work or difficulty"), || nat_to_scalar(&self.witness.block_work))?;
 
         // Constrain allocation:
-        // max_target = target * block_work
+        // block_work = sum of the quotient limbs
+        // The quotient is below 2^254 for any target of 4 or more, which the hash has to meet,
+        // so this does not wrap around
         cs.enforce(
-            || "max_target = target * block_work",
-            |lc| lc + target.get_variable(),
+            || "block_work = quotient",
+            |lc| {
+                let mut lc = lc;
+                let mut shift = F::ONE;
+                for limb in &work_nat.limbs {
+                    lc = lc + (shift, limb);
+                    shift *= F::from(2u64).pow_vartime([64u64]);
+                }
+                lc
+            },
+            |lc| lc + CS::one(),
             |lc| lc + block_work.get_variable(),
-            |lc| lc + max_target.get_variable(),
         );
 
         // 5. Target update
@@ -324,31 +359,8 @@ where
             |lc| lc,
         );
 
-        let start_time_epoch = z_i[13].clone();
-        let target_calc = difficulty_update::calculate_difficulty_update(cs.namespace(|| "target calculated"), &z_i[12], &z_i[13]).unwrap();
-        let bigint_tar = target_calc.value.unwrap_or_default();
-        let (_s2_calc, target_u64_calc) = bigint_tar.to_u64_digits();
-
-        let calculated_target = AllocatedNum::alloc(cs.namespace(|| "target updated"), || {
-            let exponent = 8 * (b0 - 3) as usize;
-            let ls_tar = F::from_u128(((target_u64_calc[0] as u128) + (target_u64_calc[1] as u128) << 64) >> exponent);
-            let ms64b = F::from_u128((target_u64_calc[2] as u128) << 64);
-            let mut pow_2 = F::from_u128((1 << 64)>>exponent);
-
-            pow_2.mul_assign(&ms64b);
-            pow_2.add_assign(&ls_tar);
-
-            let exp_2 = F::from_u128(1u128 << exponent);
-            pow_2.mul_assign(&exp_2);
-
-            Ok(pow_2)
-        }).unwrap();
-
-        // let _r = BigNat::equals(cs.namespace(|| "verify target update"), &target, &calculated_target).unwrap();
-        
-        // Either the counter z_i[14] is non-zero or curr_target = calc_target = z_i[12] * t_sum / (2016*10*60)
         let delta_inv = AllocatedNum::alloc(cs.namespace(|| "delta_inv"), || {
-            let delta = z_i[14].get_value().unwrap();
+            let delta = *z_i[14].get_value().grab()?;
 
             if delta.is_zero().unwrap_u8() == 1 {
                 Ok(F::ONE) // we can return any number here, it doesn't matter
@@ -362,8 +374,8 @@ where
         // If `z_i[14]` is zero, `t` will equal 0
 
         let t = AllocatedNum::alloc(cs.namespace(|| "t"), || {
-            let mut tmp = z_i[14].get_value().unwrap();
-            tmp.mul_assign(&(delta_inv.get_value().unwrap()));
+            let mut tmp = *z_i[14].get_value().grab()?;
+            tmp.mul_assign(delta_inv.get_value().grab()?);
 
             Ok(tmp)
         })?;
@@ -388,20 +400,94 @@ where
             |lc| lc,
         );
 
-        // Constrain:
-        // (curr_target - calc_target) * (t - 1) == 0
-        // This enforces that correct `delta_inv` was provided,
-        // and thus `t` is 1 if `z_i[14]` is non zero
+        // The first block of an epoch, where t = 0, is retargeted from the previous epoch,
+        // which started at z_i[13] and whose last block is z_i[11]
+        let prev_target = BigNat::from_num(cs.namespace(|| "previous target"), Num::from(z_i[12].clone()), 64, 4)?;
+        // The 256-bit decomposition of z_i[12] is only unique below the modulus.
+        // The limit is a field element, so bounding the target by it picks the canonical one.
+        let pow_limit = BigNat::constant::<CS>(&self.pow_limit, 64, 4)?;
+        let above_limit = pow_limit.is_less_than(cs.namespace(|| "previous target bound"), &prev_target)?;
+        Boolean::enforce_equal(cs.namespace(|| "previous target <= pow limit"), &above_limit, &Boolean::constant(false))?;
+        let next_target = difficulty_update::retarget(cs.namespace(|| "retarget"), &prev_target, &z_i[13], &z_i[median::MEDIAN_TIME_SPAN], &self.pow_limit)?;
+
+        // nBits encodes the retargeted value rounded down to the precision of the mantissa:
+        // the mantissa is the retargeted value shifted right by the exponent,
+        // and it is normalized, so that no other exponent fits.
+        let truncated = next_target.shr_var(cs.namespace(|| "truncated retarget"), &exponent_bits, 8)?;
+        let n_limbs = max(truncated.params.n_limbs, mantissa.params.n_limbs);
+        let (truncated, retarget_mantissa) = (truncated.with_n_limbs::<CS>(n_limbs), mantissa.with_n_limbs::<CS>(n_limbs));
+        for i in 0..n_limbs {
+            // (truncated - mantissa) * (1 - t) == 0
+            cs.enforce(
+                || format!("mantissa matches retarget {}", i),
+                |lc| lc + &truncated.limbs[i] - &retarget_mantissa.limbs[i],
+                |lc| lc + CS::one() - t.get_variable(),
+                |lc| lc,
+            );
+        }
+        let normalized = BigNat::constant::<CS>(&BigInt::from(0x7fffu64), 64, 1)?
+            .is_less_than(cs.namespace(|| "mantissa normalized"), &mantissa)?;
+        // (1 - normalized) * (1 - t) == 0
         cs.enforce(
-            || "(curr_target - calc_target) * (t - 1) == 0",
-            |lc| lc + target.get_variable() - calculated_target.get_variable(),
-            |lc| lc + t.get_variable() - CS::one(),
+            || "retarget mantissa normalized",
+            |lc| lc + CS::one() - &normalized.lc(CS::one(), F::ONE),
+            |lc| lc + CS::one() - t.get_variable(),
             |lc| lc,
         );
 
-        
-        // Either the counter z_i[14] is not equal to 2015 or start_time_epoch = z_i[13]
+        // A block with counter 0 starts a new epoch:
+        // start_time_epoch = z_i[13] + (1 - t) * (curr_timestamp - z_i[13])
+        let start_time_epoch = AllocatedNum::alloc(cs.namespace(|| "start time epoch"), || {
+            if *t.get_value().grab()? == F::ZERO {
+                Ok(*curr_timestamp.get_value().grab()?)
+            } else {
+                Ok(*z_i[13].get_value().grab()?)
+            }
+        })?;
+
+        cs.enforce(
+            || "start_time_epoch - z_i[13] = (1 - t) * (curr_timestamp - z_i[13])",
+            |lc| lc + CS::one() - t.get_variable(),
+            |lc| lc + curr_timestamp.get_variable() - z_i[13].get_variable(),
+            |lc| lc + start_time_epoch.get_variable() - z_i[13].get_variable(),
+        );
+
+        // The counter wraps after the last block of an epoch.
+        // is_last = 1 iff z_i[14] == 2015, using `last_delta_inv` as for `t`
+        let last_counter = F::from(witness::DIFFICULTY_ADJUSTMENT_INTERVAL - 1);
+        let last_delta_inv = AllocatedNum::alloc(cs.namespace(|| "last_delta_inv"), || {
+            let delta = *z_i[14].get_value().grab()? - last_counter;
+
+            if delta.is_zero().unwrap_u8() == 1 {
+                Ok(F::ZERO)
+            } else {
+                Ok(delta.invert().unwrap())
+            }
+        })?;
+
+        let is_last = AllocatedNum::alloc(cs.namespace(|| "is_last"), || {
+            let delta = *z_i[14].get_value().grab()? - last_counter;
+
+            Ok(F::ONE - delta * last_delta_inv.get_value().grab()?)
+        })?;
 
+        // Constrain allocation:
+        // (z_i[14] - 2015) * last_delta_inv = 1 - is_last
+        cs.enforce(
+            || "(z_i[14] - 2015) * last_delta_inv = 1 - is_last",
+            |lc| lc + z_i[14].get_variable() - (last_counter, CS::one()),
+            |lc| lc + last_delta_inv.get_variable(),
+            |lc| lc + CS::one() - is_last.get_variable(),
+        );
+
+        // Constrain:
+        // (z_i[14] - 2015) * is_last == 0
+        cs.enforce(
+            || "(z_i[14] - 2015) * is_last == 0",
+            |lc| lc + z_i[14].get_variable() - (last_counter, CS::one()),
+            |lc| lc + is_last.get_variable(),
+            |lc| lc,
+        );
 
         // 6. z_out
         //
@@ -453,28 +539,29 @@ where
 
         // z_out[14]
         z_out.push(AllocatedNum::alloc(cs.namespace(|| "target counter"), || {
-            let mut prev_ctr = z_i[14].get_value().unwrap();
+            let mut prev_ctr = *z_i[14].get_value().grab()?;
 
             prev_ctr.add_assign(F::ONE);
+            prev_ctr.mul_assign(F::ONE - is_last.get_value().grab()?);
             Ok(prev_ctr)
-        }).unwrap());
+        })?);
 
         cs.enforce(
-            || "z_out[14] = z_i[14] + 1", 
+            || "z_out[14] = (z_i[14] + 1) * (1 - is_last)", 
             |lc| lc + CS::one() + z_i[14].get_variable(),
-            |lc| lc + CS::one(),
+            |lc| lc + CS::one() - is_last.get_variable(),
             |lc| lc + z_out[14].get_variable(),
         );
 
         // total work
         // z_out[15]
         z_out.push(AllocatedNum::alloc(cs.namespace(|| "total work"), || {
-            let prev_total = z_i[15].get_value().unwrap();
-            let mut curr_work = block_work.get_value().unwrap();
+            let prev_total = *z_i[15].get_value().grab()?;
+            let mut curr_work = *block_work.get_value().grab()?;
 
             curr_work.add_assign(&prev_total);
             Ok(curr_work)
-        }).unwrap());
+        })?);
 
         cs.enforce(
             || "z_out[15] = z_i[15] + block_work", 
@@ -483,6 +570,198 @@ where
             |lc| lc + z_out[15].get_variable(),
         );
 
+        // 7. Block hash accumulator
+        //
+        // z_i[16] is the root of a Merkle mountain range over the hashes of all proven blocks
+        // and z_i[17] is its leaf count. The current peaks are supplied as witness.
+        let mmr_peaks = self.witness.mmr_peaks.iter().enumerate().map(|(i, peak)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("mmr peak {}", i)), || Ok(*peak))
+        }).collect::<Result<Vec<_>, _>>()?;
+        let (mmr_root, mmr_leaf_count) = mmr::append_leaf(cs.namespace(|| "append block hash"), &z_i[16], &z_i[17], &mmr_peaks, &curr_hash)?;
+
+        z_out.push(mmr_root); // z_out[16]
+        z_out.push(mmr_leaf_count); // z_out[17]
+
         Ok(z_out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::header_step::*;
+    use crate::util::scalar::Fr;
+    use bellpepper::util_cs::metric_cs::MetricCS;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+
+    // Block no. 123456
+    const BLOCK_123456: [u64; 10] = [0x010000009500c43a, 0x25c624520b5100ad, 0xf82cb9f9da72fd24, 0x47a496bc600b0000, 0x000000006cd86237, 0x0395dedf1da2841c, 0xcda0fc489e3039de, 0x5f1ccddef0e83499, 0x1a65600ea6c8cb4d, 0xb3936a1ae3143991];
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+    const BLOCK_1: [u64; 10] = [0x010000006fe28c0a, 0xb6f1b372c1a6a246, 0xae63f74f931e8365, 0xe15a089c68d61900, 0x00000000982051fd, 0x1e4ba744bbbe680e, 0x1fee14677ba1a3c3, 0x540bf7b1cdb606e8, 0x57233e0e61bc6649, 0xffff001d01e36299];
+
+    fn synthesize_step<CS: ConstraintSystem<Fr>>(cs: &mut CS, step: &BlockHeader<Fr>, z: Option<&[Fr]>) -> Vec<Option<Fr>> {
+        let z_in = (0..step.arity()).map(|i| {
+            AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || z.map(|z| z[i]).ok_or(SynthesisError::AssignmentMissing)).unwrap()
+        }).collect::<Vec<_>>();
+        let z_out = step.synthesize(cs, &z_in).unwrap();
+        z_out.iter().map(|v| v.get_value()).collect()
+    }
+
+    #[test]
+    fn test_block_hash_scalar() {
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let blocks = BlockHeader::<Fr>::new_blocks_from(&mut state, vec![BLOCK_123456]).unwrap();
+
+        // hash = 0000000000002917ed80650c6174aac8dfc46f5fe36480aaef682ff6cd83c3ca
+        let hash = Fr::from_str_vartime("66034656675394466060794882811079286939408931933946624673563594").unwrap();
+        assert_eq!(blocks[0].block_hash_scalar(), hash);
+
+        assert_eq!(state.mmr.leaf_count(), 1);
+        assert!(MerkleMountainRange::verify(state.mmr.root(), hash, &state.mmr.prove(0).unwrap()));
+    }
+
+    #[test]
+    fn test_block_witness() {
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let witness = state.append(RawHeader(BLOCK_123456)).unwrap();
+
+        assert_eq!(witness.timestamp, 0x4dcbc8a6);
+        assert_eq!(witness.target, witness::target_from_bits(0x1a6a93b3));
+        assert_eq!(witness.block_work, BigInt::from(676108614371196u64));
+        assert!(&witness.block_work * (&witness.target + 1u64) <= BigInt::from(1u64) << 256);
+
+        assert_eq!(state.counter, 481);
+        assert_eq!(state.epoch_start_time, 1304975844);
+        assert_eq!(state.timestamps[10], 0x4dcbc8a6);
+        assert_eq!(state.chain_work, witness.block_work);
+
+        // a header is rejected if it does not extend the tip
+        assert_eq!(state.append(RawHeader(BLOCK_123456)).unwrap_err(), WitnessError::PrevHashMismatch);
+    }
+
+    #[test]
+    fn test_header_step() {
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let z0 = state.z();
+        let blocks = BlockHeader::<Fr>::new_blocks_from(&mut state, vec![BLOCK_123456]).unwrap();
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let z_out = synthesize_step(&mut cs, &blocks[0], Some(&z0));
+        assert!(cs.is_satisfied());
+        assert_eq!(z_out.into_iter().map(Option::unwrap).collect::<Vec<_>>(), state.z());
+
+        // the step does not accept another state
+        let mut z_wrong = z0.clone();
+        z_wrong[0] += Fr::from(1u64);
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        synthesize_step(&mut cs, &blocks[0], Some(&z_wrong));
+        assert!(!cs.is_satisfied());
+    }
+
+    /// Synthesizes `steps` one after the other from `z0`, returns the last state and whether
+    /// every step was satisfied
+    fn synthesize_steps<C: StepCircuit<Fr>>(steps: &[C], z0: &[Fr]) -> (Vec<Fr>, bool) {
+        let mut z = z0.to_vec();
+        let mut satisfied = true;
+        for step in steps {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let z_in = z.iter().enumerate().map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()).collect::<Vec<_>>();
+            let z_out = step.synthesize(&mut cs, &z_in).unwrap();
+            satisfied &= cs.is_satisfied();
+            z = z_out.iter().map(|v| v.get_value().unwrap()).collect();
+        }
+        (z, satisfied)
+    }
+
+    #[test]
+    fn test_compact_header_steps() {
+        // two compact steps, each opening the commitment output by the previous one
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        let z0 = compact::compact_state(&state.z(), &COMPACT_HEADLINES);
+        let steps = BlockHeader::<Fr>::new_compact_blocks(&mut state, vec![GENESIS, BLOCK_1]).unwrap();
+        let (z, satisfied) = synthesize_steps(&steps, &z0);
+        assert!(satisfied);
+        assert_eq!(z.len(), 1 + COMPACT_HEADLINES.len());
+        assert!(compact::open_state(&z, &state.z(), &COMPACT_HEADLINES));
+
+        // the compact chain from the initial state
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let steps = BlockHeader::<Fr>::new_compact_blocks(&mut state, vec![BLOCK_123456]).unwrap();
+        let (z, satisfied) = synthesize_steps(&steps, &BlockHeader::<Fr>::initial_compact_z_i_scalars());
+        assert!(satisfied);
+        assert!(compact::open_state(&z, &state.z(), &COMPACT_HEADLINES));
+
+        // a step does not accept the commitment to another state
+        let (_, satisfied) = synthesize_steps(&steps, &z0);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn test_header_step_wrong_target() {
+        // A target which is not the one encoded in nBits is rejected, even if the hash is below it
+        let z0 = BlockHeader::<Fr>::initial_z_i_scalars();
+        let mut block = BlockHeader::<Fr>::new_blocks(vec![BLOCK_123456]).unwrap().remove(0);
+        block.witness.target <<= 1;
+        block.witness.block_work = (BigInt::from(1u64) << 256) / (&block.witness.target + 1u64);
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        synthesize_step(&mut cs, &block, Some(&z0));
+        assert!(cs.which_is_unsatisfied().unwrap().starts_with("target matches threshold"));
+    }
+
+    #[test]
+    fn test_header_step_soundness() {
+        // Given the state and the header, a cheating prover cannot change the next state
+        let mut state = BlockHeader::<Fr>::initial_state();
+        let z0 = state.z();
+        let blocks = BlockHeader::<Fr>::new_blocks_from(&mut state, vec![BLOCK_123456]).unwrap();
+
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let z_in = z0.iter().enumerate().map(|(i, z)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*z)).unwrap()).collect::<Vec<_>>();
+        let z_out = blocks[0].synthesize(&mut cs, &z_in).unwrap();
+        for (i, z) in z_out.iter().enumerate() {
+            cs.fix(&format!("z {}", i));
+            cs.output_num(&format!("z_out[{}]", i), z);
+        }
+        for i in 0..10 {
+            cs.fix(&format!("dummy2 {}", i));
+        }
+        // Changing the hashes needs collisions, so only their outputs are mutated
+        cs.assert_sound_where(|path| !["SHA 256", "append block hash", "mmr peak"].iter().any(|p| path.starts_with(p)));
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let report = BlockHeader::<Fr>::cost_report().unwrap();
+        // every part of the step belongs to a consensus rule
+        assert_eq!(report.parts.len(), CONSENSUS_RULES.len());
+        // the table has a header, a line per rule and the total
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), CONSENSUS_RULES.len() + 2);
+        for rule in CONSENSUS_RULES {
+            assert!(table.lines().any(|line| line.ends_with(&format!("  {}", rule))));
+        }
+
+        let mut cs = MetricCS::<Fr>::new();
+        synthesize_step(&mut cs, &BlockHeader::default(), None);
+        assert_eq!(report.total.constraints, cs.num_constraints());
+        let sha256d = &report.parts[3];
+        assert_eq!(sha256d.0, "block hash (SHA256d)");
+        assert!(sha256d.1.constraints > report.total.constraints / 2);
+    }
+
+    #[test]
+    fn test_header_step_shape() {
+        // Synthesizing without any values gives the same shape as with a real witness
+        let blocks = BlockHeader::<Fr>::new_blocks(vec![BLOCK_123456]).unwrap();
+
+        let mut shape_cs = MetricCS::<Fr>::new();
+        synthesize_step(&mut shape_cs, &BlockHeader::default(), None);
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        synthesize_step(&mut cs, &blocks[0], Some(&BlockHeader::<Fr>::initial_z_i_scalars()));
+
+        assert_eq!(shape_cs.num_constraints(), cs.num_constraints());
+        assert_eq!(shape_cs.num_inputs(), cs.num_inputs());
+    }
+}
@@ -1,11 +1,13 @@
-use bellpepper::gadgets::boolean::AllocatedBit;
 use bellpepper::gadgets::boolean::Boolean;
 use bellpepper_core::{ConstraintSystem, SynthesisError};
-use bellpepper::gadgets::num::{AllocatedNum, Num};
+use bellpepper::gadgets::num::AllocatedNum;
 use ff::PrimeField;
-use crate::BitAccess;
 use crate::OptionExt;
-use crate::mp::bignat::BigNat;
+use crate::util::compare;
+use crate::util::num::Num as BitNum;
+
+/// Number of previous timestamps whose median the current timestamp must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
 
 pub fn compute_median_timestamp (prev_timestamps: &mut Vec<u32>) -> u32
 {
@@ -13,97 +15,11 @@ pub fn compute_median_timestamp (prev_timestamps: &mut Vec<u32>) -> u32
     return prev_timestamps[prev_timestamps.len()/2];
 }
 
-pub fn verify_median_timestamp<Scalar, CS> (mut cs: CS, timestamp_array: &mut Vec<u32>, median: u32) -> Result<Boolean, SynthesisError>
-where
-Scalar: PrimeField,
-CS: ConstraintSystem<Scalar>,
-{
-    let fe_median = AllocatedNum::alloc(cs.namespace(|| "median"), || Ok(Scalar::from(median as u64))).unwrap();
-
-    let mut fe_timestamps = vec![];
-    let mut fe_temp: AllocatedNum<Scalar>;
-    for (i, stamp) in timestamp_array.iter().enumerate() {
-        fe_temp = AllocatedNum::alloc(cs.namespace(|| format!("timestamp {}", i)), || Ok(Scalar::from(*stamp as u64))).unwrap();
-        fe_timestamps.push(fe_temp);
-    }
-
-    let mut n_median_occurrences = Num::zero();
-    let mut sign_diff = AllocatedNum::alloc(cs.namespace(|| "sign_diff"), || Ok(Scalar::from(0u64))).unwrap();
-
-    for (i, fe_time) in fe_timestamps.iter().enumerate() {
-        // n_median_occurrences += 1 if median == timestamp else no change
-        let res_eq = BigNat::equals(cs.namespace(|| format!("check equal for time {}", i)), &fe_median, fe_time).unwrap();
-        n_median_occurrences = n_median_occurrences.add_bool_with_coeff(CS::one(), &res_eq, Scalar::ONE);
-
-        let res_lt = less_than(cs.namespace(|| format!("res_lt {}", i)), &fe_median, fe_time, 32).unwrap();
-
-        // delta_sign is the signum of (median - timestamp)
-        let delta_sign = AllocatedNum::alloc(cs.namespace(|| format!("signum {}", i)), || {
-            let eq = Scalar::from(res_eq.get_value().unwrap() as u64);
-            let lt = Scalar::from(res_lt.get_value().unwrap() as u64);
-            
-            // let signum: i64 = (1 - eq) * (1 - 2 * lt);
-            let mut eq_opd = Scalar::ONE;
-            eq_opd.sub_assign(&eq);
-            let mut mult_res = Scalar::ONE;
-            mult_res.sub_assign(&lt);
-            mult_res.sub_assign(&lt);
-
-            mult_res.mul_assign(&eq_opd);
-            Ok(mult_res)
-            // Ok(Scalar::from(signum))
-        }).unwrap();
-        
-        let eq_bit = AllocatedBit::alloc(cs.namespace(|| format!("equality {}", i)), res_eq.get_value())?;
-        let lt_bit_mul2 = AllocatedNum::alloc(cs.namespace(|| format!("less than {}", i)), || Ok(Scalar::from(2*(res_lt.get_value().unwrap() as u64)))).unwrap();
-        // Constrain:
-        // (1 - res_eq) * (1 - 2 * res_lt) == delta_sign
-        cs.enforce(
-            || format!("(1 - res_eq) * (1 - 2 * res_lt) == delta_sign {}", i),
-            |lc| lc + CS::one() - eq_bit.get_variable(),
-            |lc| lc + CS::one() - lt_bit_mul2.get_variable(),
-            |lc| lc + delta_sign.get_variable(),
-        );
-
-        // sign_diff += signum(median - timestamp)
-        sign_diff = sign_diff.add(cs.namespace(|| format!("sign_diff increment {}", i)), &delta_sign).unwrap();
-    }
-    // absolute value of sign_diff needs to be obtained
-    let const_12 = AllocatedNum::alloc(cs.namespace(|| "max positive sign_diff"), || Ok(Scalar::from(12u64))).unwrap();
-    let is_positive = less_than(cs.namespace(|| "is_pos"), &sign_diff, &const_12, 32usize).unwrap();
-    let neg_sign_diff = AllocatedNum::alloc(cs.namespace(|| "Negative of sign_diff"), || {
-        let sign_val = sign_diff.get_value().unwrap();
-        let mut zero_val = Scalar::ZERO;
-        zero_val.sub_assign(&sign_val);
-        Ok(zero_val)
-    }).unwrap();
-    // Constrain:
-    // (sign_diff + neg_sign_diff) * 1 == 0
-    cs.enforce(
-        || "(sign_diff + neg_sign_diff) * 1 == 0",
-        |lc| lc + sign_diff.get_variable() + neg_sign_diff.get_variable(),
-        |lc| lc + CS::one(),
-        |lc| lc,
-    );
-
-    let (abs_sign_diff, _dummy) = AllocatedNum::conditionally_reverse(cs.namespace(|| "absolute value"), &sign_diff, &neg_sign_diff, &is_positive).unwrap();
-
-    let const_1 = AllocatedNum::alloc(cs.namespace(|| "increment by 1"), || Ok(Scalar::ONE)).unwrap();
-    let lhs = abs_sign_diff.add(cs.namespace(|| "LHS of inequality"), &const_1).unwrap();
-    // lhs = abs(sign_diff) + 1
-
-    let fe_median_occ = AllocatedNum::alloc(cs.namespace(|| "fe_median"), || Ok(n_median_occurrences.get_value().unwrap())).unwrap();
-
-    // let r_eq = BigNat::equals(cs.namespace(|| "verify median"), &lhs, &fe_median_occ).unwrap();
-    // let r_lt = less_than(cs.namespace(|| "lt"), &lhs, &fe_median_occ, 5).unwrap();
-    // Boolean::or(cs.namespace(|| "less than or equal to check"), &r_eq, &r_lt)
-    leq(cs.namespace(|| "median leq"), &lhs, &fe_median_occ, 32usize)
-}
-
 /// Takes two allocated numbers (a, b) and returns
 /// allocated boolean variable with value `true`
 /// if the `a` and `b` are such that a is strictly less than b, 
 /// `false` otherwise.
+/// Both numbers are range checked to `n_bits` bits, see `util::compare`.
 pub fn less_than <Scalar, CS> (
     mut cs: CS,
     a: &AllocatedNum<Scalar>,
@@ -114,61 +30,16 @@ where
     Scalar: PrimeField,
     CS: ConstraintSystem<Scalar>,
 {
-    let zero_shifted_cmp = AllocatedNum::alloc(cs.namespace(|| "zero shift"), || {
-        let mut cmp_val = a.get_value().unwrap();
-        let b_value = b.get_value().unwrap();
-
-        // Accomodating n_bits > 128 (i.e a and b atleast 128 bits)
-        let zero_shift = if n_bits <= 126 { 
-            Scalar::from_u128(2 << n_bits) 
-        } 
-        else { 
-            let shift1 = Scalar::from_u128(2 << (n_bits - 126));
-            let mut shift2 = Scalar::from_u128(2 << 126);
-            shift2.mul_assign(&shift1);
-            shift2
-        };
-
-        cmp_val.add_assign(&zero_shift);
-        cmp_val.sub_assign(&b_value);
-
-        Ok(cmp_val)
-    }).unwrap();
-
-    let n_bit_zero_cmp = AllocatedNum::alloc(cs.namespace(|| "n_bits zero cmp"), ||{
-        let mut exponent = Scalar::ONE;
-        let sc_two = Scalar::ONE + Scalar::ONE;
-        let cmp = zero_shifted_cmp.get_value();
-
-        let mut summ = Scalar::ZERO;
-        for i in 0..=n_bits{
-            let mut bit_i = if *cmp.grab()?.get_bit(i).grab()? {
-                Scalar::ONE
-            } else {
-                Scalar::ZERO
-            };
-            bit_i.mul_assign(&exponent);
-            summ.add_assign(&bit_i);
-            exponent.mul_assign(&sc_two);
-        }
-
-        Ok(summ)
-    }).unwrap();
-
-    BigNat::equals(cs.namespace(|| "check fits in n_bits"), &zero_shifted_cmp, &n_bit_zero_cmp)
-
-    // let _ = zero_shifted_cmp.fits_in_bits(cs.namespace(|| "check if fits"), n_bits + 1);
-    // let boolean_r = cs.is_satisfied();
-    // let r = AllocatedBit::alloc(cs.namespace(|| "r"), Some(boolean_r))?;
-
-    // Ok(Boolean::from(r))
-
+    let (a, b) = range_checked(cs.namespace(|| "range check"), a, b, n_bits)?;
+    let r = compare::lt(cs.namespace(|| "lt"), &a, &b, n_bits)?;
+    r.alloc_boolean(cs.namespace(|| "result"))
 }
 
 /// Takes two allocated numbers (a, b) and returns
 /// allocated boolean variable with value `true`
 /// if the `a` and `b` are such that a is less than or equal to b, 
 /// `false` otherwise.
+/// Both numbers are range checked to `n_bits` bits, see `util::compare`.
 pub fn leq <Scalar, CS> (
     mut cs: CS,
     a: &AllocatedNum<Scalar>,
@@ -179,49 +50,120 @@ where
     Scalar: PrimeField,
     CS: ConstraintSystem<Scalar>,
 {
-    let zero_shifted_cmp = AllocatedNum::alloc(cs.namespace(|| "zero shift leq"), || {
-        let mut cmp_val = a.get_value().unwrap();
-        let mut b_value = b.get_value().unwrap();
-        b_value.add_assign(Scalar::ONE);
-
-        // Accomodating n_bits > 128 (i.e a and b atleast 128 bits)
-        let zero_shift = if n_bits <= 126 { 
-            Scalar::from_u128(2 << n_bits) 
-        } 
-        else { 
-            let shift1 = Scalar::from_u128(2 << (n_bits - 126));
-            let mut shift2 = Scalar::from_u128(2 << 126);
-            shift2.mul_assign(&shift1);
-            shift2
-        };
-
-        cmp_val.add_assign(&zero_shift);
-        cmp_val.sub_assign(&b_value);
-
-        Ok(cmp_val)
-    }).unwrap();
-
-    let n_bit_zero_cmp = AllocatedNum::alloc(cs.namespace(|| "n_bits zero cmp leq"), ||{
-        let mut exponent = Scalar::ONE;
-        let sc_two = Scalar::ONE + Scalar::ONE;
-        let cmp = zero_shifted_cmp.get_value();
-
-        let mut summ = Scalar::ZERO;
-        for i in 0..=n_bits{
-            let mut bit_i = if *cmp.grab()?.get_bit(i).grab()? {
-                Scalar::ONE
-            } else {
-                Scalar::ZERO
-            };
-            bit_i.mul_assign(&exponent);
-            summ.add_assign(&bit_i);
-            exponent.mul_assign(&sc_two);
-        }
+    let (a, b) = range_checked(cs.namespace(|| "range check"), a, b, n_bits)?;
+    let r = compare::leq(cs.namespace(|| "leq"), &a, &b, n_bits)?;
+    r.alloc_boolean(cs.namespace(|| "result"))
+}
+
+fn range_checked<Scalar, CS>(
+    mut cs: CS,
+    a: &AllocatedNum<Scalar>,
+    b: &AllocatedNum<Scalar>,
+    n_bits: usize,
+) -> Result<(BitNum<Scalar>, BitNum<Scalar>), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let a = BitNum::from(a.clone());
+    let b = BitNum::from(b.clone());
+    compare::range_check(cs.namespace(|| "a"), &a, n_bits)?;
+    compare::range_check(cs.namespace(|| "b"), &b, n_bits)?;
+    Ok((a, b))
+}
+
+/// Orders two `n_bits` wide numbers, returning `(min(a, b), max(a, b))`.
+/// Equal inputs are returned unchanged.
+fn compare_and_swap<Scalar, CS>(
+    mut cs: CS,
+    a: &AllocatedNum<Scalar>,
+    b: &AllocatedNum<Scalar>,
+    n_bits: usize,
+) -> Result<(AllocatedNum<Scalar>, AllocatedNum<Scalar>), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let a_leq_b = compare::leq(cs.namespace(|| "a <= b"), &BitNum::from(a.clone()), &BitNum::from(b.clone()), n_bits)?;
+
+    // min = b + a_leq_b * (a - b)
+    let min = AllocatedNum::alloc(cs.namespace(|| "min"), || {
+        let a = *a.get_value().grab()?;
+        let b = *b.get_value().grab()?;
+        Ok(if *a_leq_b.value.grab()? { a } else { b })
+    })?;
+    cs.enforce(
+        || "min = b + a_leq_b * (a - b)",
+        |lc| lc + &a_leq_b.bit,
+        |lc| lc + a.get_variable() - b.get_variable(),
+        |lc| lc + min.get_variable() - b.get_variable(),
+    );
+
+    // max = a + b - min
+    let max = AllocatedNum::alloc(cs.namespace(|| "max"), || {
+        let mut v = *a.get_value().grab()?;
+        v.add_assign(b.get_value().grab()?);
+        v.sub_assign(min.get_value().grab()?);
+        Ok(v)
+    })?;
+    cs.enforce(
+        || "max = a + b - min",
+        |lc| lc + a.get_variable() + b.get_variable() - min.get_variable(),
+        |lc| lc + CS::one(),
+        |lc| lc + max.get_variable(),
+    );
 
-        Ok(summ)
-    }).unwrap();
+    Ok((min, max))
+}
+
+/// Sorts `values` in ascending order with an odd-even transposition network.
+/// Every value must fit in `n_bits` bits, which the caller has to ensure.
+pub fn sort<Scalar, CS>(
+    mut cs: CS,
+    values: &[AllocatedNum<Scalar>],
+    n_bits: usize,
+) -> Result<Vec<AllocatedNum<Scalar>>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let mut sorted = values.to_vec();
+    for round in 0..sorted.len() {
+        for i in ((round % 2)..sorted.len().saturating_sub(1)).step_by(2) {
+            let (min, max) = compare_and_swap(
+                cs.namespace(|| format!("round {} compare {}", round, i)),
+                &sorted[i],
+                &sorted[i + 1],
+                n_bits,
+            )?;
+            sorted[i] = min;
+            sorted[i + 1] = max;
+        }
+    }
+    Ok(sorted)
+}
 
-    BigNat::equals(cs.namespace(|| "check fits in n_bits leq"), &zero_shifted_cmp, &n_bit_zero_cmp)
+/// Computes the median of an odd number of timestamps in the circuit.
+/// The timestamps are range checked to `n_bits` bits and sorted by a sorting network,
+/// so the result is the median of exactly the given variables, repeated values included.
+/// Returns `SynthesisError::Unsatisfiable` for an even or empty window.
+pub fn median_time_past<Scalar, CS>(
+    mut cs: CS,
+    timestamps: &[AllocatedNum<Scalar>],
+    n_bits: usize,
+) -> Result<AllocatedNum<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    if timestamps.len() % 2 != 1 {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    for (i, t) in timestamps.iter().enumerate() {
+        BitNum::from(t.clone()).fits_in_bits(cs.namespace(|| format!("timestamp {} range", i)), n_bits)?;
+    }
+    let sorted = sort(cs.namespace(|| "sort"), timestamps, n_bits)?;
+    Ok(sorted[timestamps.len() / 2].clone())
 }
 
 #[cfg(test)]
@@ -229,6 +171,8 @@ mod tests {
     use crate::btc_validation::median::*;
     use bellpepper_core::test_cs::TestConstraintSystem;
     use crate::util::{scalar::Fr, num};
+    use crate::util::fuzz::FuzzingConstraintSystem;
+    use crate::mp::bignat::BigNat;
 
     #[test]
     fn test_median_compute() {
@@ -238,15 +182,88 @@ mod tests {
         assert_eq!(median, 6);
     }
 
+    fn alloc_timestamps(cs: &mut TestConstraintSystem<Fr>, timestamps: &[u32]) -> Vec<AllocatedNum<Fr>> {
+        timestamps.iter().enumerate().map(|(i, t)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("timestamp {}", i)), || Ok(Fr::from(*t as u64))).unwrap()
+        }).collect()
+    }
+
     #[test]
-    fn test_median_verify() {
+    fn test_median_time_past() {
+        let windows: Vec<Vec<u32>> = vec![
+            vec![11,2,3,4,7,5,8,6,10,9,1],
+            vec![11,2,3,4,6,6,8,6,10,9,1],
+            vec![7,7,7,7,7,7,7,7,7,7,7],
+            vec![u32::MAX,0,u32::MAX,1,2],
+            vec![1305191152],
+            vec![3,1,2],
+        ];
+        for timestamps in windows {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let fe_timestamps = alloc_timestamps(&mut cs, &timestamps);
+            let median = median_time_past(cs.namespace(|| "mtp"), &fe_timestamps, 32).unwrap();
+
+            assert!(cs.is_satisfied());
+            let expected = compute_median_timestamp(&mut timestamps.clone());
+            assert_eq!(median.get_value().unwrap(), Fr::from(expected as u64));
+        }
+    }
+
+    #[test]
+    fn test_median_time_past_even_window() {
         let mut cs = TestConstraintSystem::<Fr>::new();
-        let mut timestamps: Vec<u32> = vec![11,2,3,4,6,6,8,6,10,9,1];
-        // let mut timestamps: Vec<u32> = vec![11,11,11,11,11,11,11,11,11,11,11,11];
-        let median: u32 = compute_median_timestamp(&mut timestamps);
+        let fe_timestamps = alloc_timestamps(&mut cs, &[1, 2, 3, 4]);
+        assert!(median_time_past(cs.namespace(|| "mtp"), &fe_timestamps, 32).is_err());
+    }
+
+    #[test]
+    fn test_median_time_past_out_of_range() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let mut fe_timestamps = alloc_timestamps(&mut cs, &[1, 2]);
+        fe_timestamps.push(AllocatedNum::alloc(cs.namespace(|| "wide"), || Ok(Fr::from(1u64 << 32))).unwrap());
+        median_time_past(cs.namespace(|| "mtp"), &fe_timestamps, 32).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_median_soundness() {
+        for (a, b) in [(3u64, 5u64), (5, 5), (5, 3), (0, u32::MAX as u64)] {
+            let mut cs = FuzzingConstraintSystem::<Fr>::new();
+            let fe_a = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Fr::from(a))).unwrap();
+            let fe_b = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(Fr::from(b))).unwrap();
+            let lt = less_than(cs.namespace(|| "lt"), &fe_a, &fe_b, 32).unwrap();
+            let le = leq(cs.namespace(|| "leq"), &fe_a, &fe_b, 32).unwrap();
+            cs.fix("a");
+            cs.fix("b");
+            cs.output_boolean("a < b", &lt);
+            cs.output_boolean("a <= b", &le);
+            cs.assert_sound();
+        }
 
-        let r = verify_median_timestamp(cs.namespace(|| "verify median"), &mut timestamps, median).unwrap().get_value().unwrap();
-        assert!(r);
+        for timestamps in [vec![11,2,3,4,6,6,8,6,10,9,1], vec![9, 1, 5]] {
+            let mut cs = FuzzingConstraintSystem::<Fr>::new();
+            let fe_timestamps = timestamps.iter().enumerate().map(|(i, t)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("timestamp {}", i)), || Ok(Fr::from(*t as u64))).unwrap()
+            }).collect::<Vec<_>>();
+            let median = median_time_past(cs.namespace(|| "mtp"), &fe_timestamps, 32).unwrap();
+            for i in 0..timestamps.len() {
+                cs.fix(&format!("timestamp {}", i));
+            }
+            cs.output_num("median", &median);
+            cs.assert_sound();
+        }
+    }
+
+    #[test]
+    fn test_median_time_past_wrong_swap() {
+        // a prover cannot claim the larger value as the minimum
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let fe_timestamps = alloc_timestamps(&mut cs, &[9, 1, 5]);
+        let median = median_time_past(cs.namespace(|| "mtp"), &fe_timestamps, 32).unwrap();
+        assert_eq!(median.get_value().unwrap(), Fr::from(5u64));
+        cs.set("mtp/sort/round 0 compare 0/min/num", Fr::from(9u64));
+        cs.set("mtp/sort/round 0 compare 0/max/num", Fr::from(1u64));
+        assert!(!cs.is_satisfied());
     }
 
     #[test]
@@ -0,0 +1,416 @@
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use generic_array::typenum::{U2, U33};
+use neptune::circuit2::poseidon_hash_allocated;
+use neptune::poseidon::PoseidonConstants;
+use neptune::Poseidon;
+use std::sync::Arc;
+
+use crate::util::bit::Bit;
+use crate::util::num::Num;
+use crate::util::poseidon::poseidon_constants;
+use crate::OptionExt;
+
+/// Number of peak slots of the Merkle mountain range.
+/// The range can hold up to `2^MMR_MAX_PEAKS - 1` leaves.
+pub const MMR_MAX_PEAKS: usize = 32;
+
+/// Poseidon constants used to hash two sibling nodes into their parent.
+pub fn node_constants<F: PrimeField>() -> Arc<PoseidonConstants<F, U2>> {
+    poseidon_constants()
+}
+
+/// Poseidon constants used to bag the leaf count and all peak slots into the root.
+pub fn root_constants<F: PrimeField>() -> Arc<PoseidonConstants<F, U33>> {
+    poseidon_constants()
+}
+
+/// Hashes two sibling nodes into their parent.
+pub fn hash_nodes<F: PrimeField>(left: F, right: F, constants: &PoseidonConstants<F, U2>) -> F {
+    Poseidon::new_with_preimage(&[left, right], constants).hash()
+}
+
+/// Computes the root committing to the leaf count and the `MMR_MAX_PEAKS` peak slots.
+pub fn bag_peaks<F: PrimeField>(
+    leaf_count: u64,
+    peaks: &[F],
+    constants: &PoseidonConstants<F, U33>,
+) -> F {
+    assert_eq!(peaks.len(), MMR_MAX_PEAKS);
+    let mut preimage = vec![F::from(leaf_count)];
+    preimage.extend_from_slice(peaks);
+    Poseidon::new_with_preimage(&preimage, constants).hash()
+}
+
+/// A Merkle mountain range over field elements, hashed with Poseidon.
+///
+/// Peak slot `h` holds the root of the perfect subtree of height `h` if bit `h`
+/// of the leaf count is set, and zero otherwise.
+#[derive(Clone, Debug)]
+pub struct MerkleMountainRange<F: PrimeField> {
+    /// All nodes grouped by height, `levels[0]` being the leaves.
+    levels: Vec<Vec<F>>,
+    leaf_count: u64,
+    node_constants: Arc<PoseidonConstants<F, U2>>,
+}
+
+/// A proof that a leaf is part of a Merkle mountain range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrProof<F: PrimeField> {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// Sibling nodes from the leaf up to (excluding) its peak.
+    pub siblings: Vec<F>,
+    /// All peak slots of the range the proof was produced for.
+    pub peaks: Vec<F>,
+}
+
+/// Returns the height of the peak containing `leaf_index` and the index of
+/// the first leaf under that peak.
+fn peak_of_leaf(leaf_count: u64, leaf_index: u64) -> Option<(usize, u64)> {
+    if leaf_index >= leaf_count {
+        return None;
+    }
+    let mut start = 0u64;
+    for height in (0..MMR_MAX_PEAKS).rev() {
+        if leaf_count >> height & 1 == 1 {
+            let size = 1u64 << height;
+            if leaf_index < start + size {
+                return Some((height, start));
+            }
+            start += size;
+        }
+    }
+    None
+}
+
+impl<F: PrimeField> Default for MerkleMountainRange<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: PrimeField> MerkleMountainRange<F> {
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new(); MMR_MAX_PEAKS],
+            leaf_count: 0,
+            node_constants: node_constants(),
+        }
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Returns the `MMR_MAX_PEAKS` peak slots, lowest height first.
+    pub fn peaks(&self) -> Vec<F> {
+        (0..MMR_MAX_PEAKS)
+            .map(|height| {
+                if self.leaf_count >> height & 1 == 1 {
+                    *self.levels[height].last().unwrap()
+                } else {
+                    F::ZERO
+                }
+            })
+            .collect()
+    }
+
+    pub fn root(&self) -> F {
+        bag_peaks(self.leaf_count, &self.peaks(), &root_constants())
+    }
+
+    /// Appends a leaf, merging equal-height peaks the same way as `append_leaf`.
+    pub fn append(&mut self, leaf: F) {
+        assert!(self.leaf_count < (1u64 << MMR_MAX_PEAKS) - 1, "MMR is full");
+        let peaks = self.peaks();
+        let mut node = leaf;
+        let mut height = 0;
+        self.levels[0].push(node);
+        while self.leaf_count >> height & 1 == 1 {
+            node = hash_nodes(peaks[height], node, &self.node_constants);
+            height += 1;
+            self.levels[height].push(node);
+        }
+        self.leaf_count += 1;
+    }
+
+    /// Produces a membership proof for the leaf at `leaf_index`.
+    pub fn prove(&self, leaf_index: u64) -> Option<MmrProof<F>> {
+        let (height, _) = peak_of_leaf(self.leaf_count, leaf_index)?;
+        let siblings = (0..height)
+            .map(|level| self.levels[level][((leaf_index >> level) ^ 1) as usize])
+            .collect();
+        Some(MmrProof {
+            leaf_index,
+            leaf_count: self.leaf_count,
+            siblings,
+            peaks: self.peaks(),
+        })
+    }
+
+    /// Checks that `leaf` is part of the range committed to by `root`.
+    pub fn verify(root: F, leaf: F, proof: &MmrProof<F>) -> bool {
+        let height = match peak_of_leaf(proof.leaf_count, proof.leaf_index) {
+            Some((height, _)) => height,
+            None => return false,
+        };
+        if proof.siblings.len() != height || proof.peaks.len() != MMR_MAX_PEAKS {
+            return false;
+        }
+        let node_constants = node_constants();
+        let mut node = leaf;
+        for (level, sibling) in proof.siblings.iter().enumerate() {
+            node = if proof.leaf_index >> level & 1 == 0 {
+                hash_nodes(node, *sibling, &node_constants)
+            } else {
+                hash_nodes(*sibling, node, &node_constants)
+            };
+        }
+        node == proof.peaks[height]
+            && bag_peaks(proof.leaf_count, &proof.peaks, &root_constants()) == root
+    }
+}
+
+/// Appends `leaf` to the Merkle mountain range committed to by `root`.
+/// `peaks` are the (unconstrained) peak slots, checked against `root`.
+/// Returns the new root and the new leaf count.
+pub fn append_leaf<Scalar, CS>(
+    mut cs: CS,
+    root: &AllocatedNum<Scalar>,
+    leaf_count: &AllocatedNum<Scalar>,
+    peaks: &[AllocatedNum<Scalar>],
+    leaf: &AllocatedNum<Scalar>,
+) -> Result<(AllocatedNum<Scalar>, AllocatedNum<Scalar>), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    if peaks.len() != MMR_MAX_PEAKS {
+        eprintln!("Expected {} peak slots, got {}", MMR_MAX_PEAKS, peaks.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let node_constants = node_constants::<Scalar>();
+    let root_constants = root_constants::<Scalar>();
+
+    let mut preimage = vec![leaf_count.clone()];
+    preimage.extend(peaks.iter().cloned());
+    let old_root = poseidon_hash_allocated(cs.namespace(|| "old root"), preimage, &root_constants)?;
+    cs.enforce(
+        || "old root matches",
+        |lc| lc,
+        |lc| lc,
+        |lc| lc + old_root.get_variable() - root.get_variable(),
+    );
+
+    // Also enforces that the leaf count fits in `MMR_MAX_PEAKS` bits
+    let count_bits = Num::from(leaf_count.clone())
+        .decompose(cs.namespace(|| "leaf count bits"), MMR_MAX_PEAKS)?
+        .into_bits();
+
+    // The carry is set while the new node is still being merged into existing peaks.
+    let mut carry = Bit::new_true::<CS>();
+    let mut node = leaf.clone();
+    let mut new_peaks = Vec::with_capacity(MMR_MAX_PEAKS);
+    for (i, (peak, bit)) in peaks.iter().zip(count_bits.iter()).enumerate() {
+        let mut cs = cs.namespace(|| format!("height {}", i));
+        let merged = poseidon_hash_allocated(
+            cs.namespace(|| "merge"),
+            vec![peak.clone(), node.clone()],
+            &node_constants,
+        )?;
+
+        // placed = (1 - bit) * node
+        let placed = AllocatedNum::alloc(cs.namespace(|| "placed"), || {
+            Ok(if *bit.value.grab()? {
+                Scalar::ZERO
+            } else {
+                *node.get_value().grab()?
+            })
+        })?;
+        cs.enforce(
+            || "placed = (1 - bit) * node",
+            |lc| lc + CS::one() - &bit.bit,
+            |lc| lc + node.get_variable(),
+            |lc| lc + placed.get_variable(),
+        );
+
+        // new_peak = carry ? placed : peak
+        let new_peak = AllocatedNum::alloc(cs.namespace(|| "new peak"), || {
+            Ok(if *carry.value.grab()? {
+                *placed.get_value().grab()?
+            } else {
+                *peak.get_value().grab()?
+            })
+        })?;
+        cs.enforce(
+            || "new_peak - peak = carry * (placed - peak)",
+            |lc| lc + &carry.bit,
+            |lc| lc + placed.get_variable() - peak.get_variable(),
+            |lc| lc + new_peak.get_variable() - peak.get_variable(),
+        );
+        new_peaks.push(new_peak);
+
+        // next_node = bit ? merged : node
+        let next_node = AllocatedNum::alloc(cs.namespace(|| "next node"), || {
+            Ok(if *bit.value.grab()? {
+                *merged.get_value().grab()?
+            } else {
+                *node.get_value().grab()?
+            })
+        })?;
+        cs.enforce(
+            || "next_node - node = bit * (merged - node)",
+            |lc| lc + &bit.bit,
+            |lc| lc + merged.get_variable() - node.get_variable(),
+            |lc| lc + next_node.get_variable() - node.get_variable(),
+        );
+        node = next_node;
+
+        // next_carry = carry * bit
+        let next_carry_value = carry.value.and_then(|c| bit.value.map(|b| c && b));
+        let next_carry = cs.alloc(
+            || "next carry",
+            || {
+                Ok(if *next_carry_value.grab()? {
+                    Scalar::ONE
+                } else {
+                    Scalar::ZERO
+                })
+            },
+        )?;
+        cs.enforce(
+            || "next_carry = carry * bit",
+            |lc| lc + &carry.bit,
+            |lc| lc + &bit.bit,
+            |lc| lc + next_carry,
+        );
+        carry = Bit::new(LinearCombination::zero() + next_carry, next_carry_value);
+    }
+    carry.constrain_value(cs.namespace(|| "range is not full"), false);
+
+    let new_leaf_count = AllocatedNum::alloc(cs.namespace(|| "new leaf count"), || {
+        let mut count = *leaf_count.get_value().grab()?;
+        count.add_assign(&Scalar::ONE);
+        Ok(count)
+    })?;
+    cs.enforce(
+        || "new_leaf_count = leaf_count + 1",
+        |lc| lc + leaf_count.get_variable() + CS::one(),
+        |lc| lc + CS::one(),
+        |lc| lc + new_leaf_count.get_variable(),
+    );
+
+    let mut preimage = vec![new_leaf_count.clone()];
+    preimage.extend(new_peaks);
+    let new_root = poseidon_hash_allocated(cs.namespace(|| "new root"), preimage, &root_constants)?;
+
+    Ok((new_root, new_leaf_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::mmr::*;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use crate::util::scalar::Fr;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+
+    fn leaf(i: u64) -> Fr {
+        Fr::from(1000 + i)
+    }
+
+    fn mmr_with_leaves(n: u64) -> MerkleMountainRange<Fr> {
+        let mut mmr = MerkleMountainRange::new();
+        for i in 0..n {
+            mmr.append(leaf(i));
+        }
+        mmr
+    }
+
+    #[test]
+    fn test_mmr_membership_proofs() {
+        let mmr = mmr_with_leaves(11);
+        let root = mmr.root();
+
+        for i in 0..11 {
+            let proof = mmr.prove(i).unwrap();
+            assert!(MerkleMountainRange::verify(root, leaf(i), &proof));
+            assert!(!MerkleMountainRange::verify(root, leaf(i + 1), &proof));
+        }
+        assert!(mmr.prove(11).is_none());
+
+        let mut tampered = mmr.prove(5).unwrap();
+        tampered.leaf_index = 4;
+        assert!(!MerkleMountainRange::verify(root, leaf(5), &tampered));
+    }
+
+    #[test]
+    fn test_mmr_old_proof_fails_against_new_root() {
+        let mut mmr = mmr_with_leaves(3);
+        let proof = mmr.prove(2).unwrap();
+        mmr.append(leaf(3));
+        assert!(!MerkleMountainRange::verify(mmr.root(), leaf(2), &proof));
+        assert!(MerkleMountainRange::verify(mmr.root(), leaf(2), &mmr.prove(2).unwrap()));
+    }
+
+    fn synthesize_append(mmr: &MerkleMountainRange<Fr>, peaks: Vec<Fr>, new_leaf: Fr) -> (TestConstraintSystem<Fr>, Fr, Fr) {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let root = AllocatedNum::alloc(cs.namespace(|| "root"), || Ok(mmr.root())).unwrap();
+        let count = AllocatedNum::alloc(cs.namespace(|| "count"), || Ok(Fr::from(mmr.leaf_count()))).unwrap();
+        let peaks: Vec<_> = peaks
+            .iter()
+            .enumerate()
+            .map(|(i, p)| AllocatedNum::alloc(cs.namespace(|| format!("peak {}", i)), || Ok(*p)).unwrap())
+            .collect();
+        let leaf = AllocatedNum::alloc(cs.namespace(|| "leaf"), || Ok(new_leaf)).unwrap();
+
+        let (new_root, new_count) = append_leaf(cs.namespace(|| "append"), &root, &count, &peaks, &leaf).unwrap();
+        let (new_root, new_count) = (new_root.get_value().unwrap(), new_count.get_value().unwrap());
+        (cs, new_root, new_count)
+    }
+
+    #[test]
+    fn test_append_leaf_matches_native() {
+        for n in 0..8 {
+            let mut mmr = mmr_with_leaves(n);
+            let (cs, new_root, new_count) = synthesize_append(&mmr, mmr.peaks(), leaf(n));
+            mmr.append(leaf(n));
+
+            assert!(cs.is_satisfied());
+            assert_eq!(new_root, mmr.root());
+            assert_eq!(new_count, Fr::from(n + 1));
+        }
+    }
+
+    #[test]
+    fn test_append_leaf_wrong_peaks() {
+        let mmr = mmr_with_leaves(5);
+        let mut peaks = mmr.peaks();
+        peaks[0] = leaf(7);
+        let (cs, _, _) = synthesize_append(&mmr, peaks, leaf(5));
+
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_append_leaf_soundness() {
+        let mmr = mmr_with_leaves(5);
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let root = AllocatedNum::alloc(cs.namespace(|| "root"), || Ok(mmr.root())).unwrap();
+        let count = AllocatedNum::alloc(cs.namespace(|| "count"), || Ok(Fr::from(mmr.leaf_count()))).unwrap();
+        let peaks: Vec<_> = mmr.peaks()
+            .iter()
+            .enumerate()
+            .map(|(i, p)| AllocatedNum::alloc(cs.namespace(|| format!("peak {}", i)), || Ok(*p)).unwrap())
+            .collect();
+        let leaf = AllocatedNum::alloc(cs.namespace(|| "leaf"), || Ok(leaf(5))).unwrap();
+        let (new_root, new_count) = append_leaf(cs.namespace(|| "append"), &root, &count, &peaks, &leaf).unwrap();
+        for input in ["root", "count", "leaf"] {
+            cs.fix(input);
+        }
+        cs.output_num("new root", &new_root);
+        cs.output_num("new count", &new_count);
+        // Changing the Poseidon hashes needs collisions, so only their outputs are mutated
+        cs.assert_sound_where(|path| !["append/old root/", "append/new root/", "/merge/"].iter().any(|p| path.contains(p)));
+    }
+}
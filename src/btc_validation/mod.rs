@@ -1,5 +1,17 @@
 pub mod median;
 pub mod difficulty_update;
 // pub mod prev_block_hash;
-// pub mod hash_target;
-pub mod header_step;
\ No newline at end of file
+pub mod hash_target;
+pub mod header_step;
+#[cfg(feature = "prover")]
+pub mod checkpoint_step;
+pub mod mmr;
+#[cfg(all(test, feature = "prover"))]
+pub mod mutations;
+pub mod compact;
+#[cfg(feature = "prover")]
+pub mod segment;
+pub mod sha256d;
+#[cfg(all(test, feature = "prover"))]
+pub mod synthetic;
+pub mod witness;
\ No newline at end of file
@@ -0,0 +1,233 @@
+//! Mutations of header chains which the step circuit must reject
+//!
+//! Every mutation starts from a valid synthetic chain and changes one thing: a field of the next
+//! header, which is mined again so that only the mutated rule fails, a value of its witness, or
+//! a value of the state claimed after it. Every step is synthesized with `StepCircuit::synthesize`,
+//! the circuit which is proven.
+
+use bellpepper_core::num::AllocatedNum;
+use bellpepper_core::{Circuit, ConstraintSystem, SynthesisError};
+use ff::Field;
+use nova_snark::traits::circuit::StepCircuit;
+use num_bigint::{BigInt, Sign};
+
+use crate::btc_validation::header_step::BlockHeader;
+use crate::btc_validation::median::MEDIAN_TIME_SPAN;
+use crate::btc_validation::synthetic::{HeaderTemplate, SyntheticChain};
+use crate::btc_validation::witness::{
+    bits_from_target, BlockHeaderWitness, RawHeader, DIFFICULTY_ADJUSTMENT_INTERVAL,
+};
+use crate::util::convert::f_to_nat;
+use crate::util::scalar::Fr;
+
+/// Seconds between the blocks of the synthetic chains
+const SPACING: u32 = 600;
+
+/// A step synthesized on the state `z_in`. If `z_out` is given, the state computed by the
+/// step is constrained to it, as the IVC binds the output of a step to the input of the next.
+pub struct StepClaim {
+    pub step: BlockHeader<Fr>,
+    pub z_in: Vec<Fr>,
+    pub z_out: Option<Vec<Fr>>,
+}
+
+impl Circuit<Fr> for StepClaim {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let z_in = self
+            .z_in
+            .iter()
+            .enumerate()
+            .map(|(i, z)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*z)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let z_out = self.step.synthesize(cs, &z_in)?;
+        for (i, (z, claimed)) in z_out.iter().zip(self.z_out.iter().flatten()).enumerate() {
+            cs.enforce(
+                || format!("claimed z_out[{}]", i),
+                |lc| lc + z.get_variable(),
+                |lc| lc + CS::one(),
+                |lc| lc + (*claimed, CS::one()),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Chain whose next block is in the middle of an epoch, after `MEDIAN_TIME_SPAN` blocks
+fn ordinary_chain() -> SyntheticChain<Fr> {
+    let mut chain = SyntheticChain::regtest();
+    chain.mine(MEDIAN_TIME_SPAN, SPACING);
+    chain
+}
+
+/// Chain whose next block starts an epoch, retargeted from an epoch shorter than expected
+fn retarget_chain() -> SyntheticChain<Fr> {
+    let mut chain = SyntheticChain::regtest_in_epoch(DIFFICULTY_ADJUSTMENT_INTERVAL - 1, SPACING);
+    chain.mine(1, SPACING);
+    chain
+}
+
+/// Step proving `header` on the state of `chain`, whose witness is changed by `mutate`
+fn claim_with(
+    chain: &SyntheticChain<Fr>,
+    header: RawHeader,
+    mutate: impl FnOnce(&mut BlockHeaderWitness<Fr>),
+) -> StepClaim {
+    let mut witness = chain.state.witness_unchecked(header);
+    mutate(&mut witness);
+    StepClaim {
+        step: BlockHeader::new(witness, chain.state.pow_limit.clone()),
+        z_in: chain.state.z(),
+        z_out: None,
+    }
+}
+
+/// Step proving the header mined from the next template of `chain`, changed by `mutate`
+pub fn mutated_header(
+    chain: &SyntheticChain<Fr>,
+    mutate: impl FnOnce(&mut HeaderTemplate),
+) -> StepClaim {
+    let mut template = chain.next_template(SPACING);
+    mutate(&mut template);
+    claim_with(chain, template.mine(), |_| ())
+}
+
+/// Step proving a valid next header of `chain` with a witness changed by `mutate`
+pub fn mutated_witness(
+    chain: &SyntheticChain<Fr>,
+    mutate: impl FnOnce(&mut BlockHeaderWitness<Fr>),
+) -> StepClaim {
+    let header = chain.next_template(SPACING).mine();
+    claim_with(chain, header, mutate)
+}
+
+/// Step proving a valid next header of `chain`, whose next state is claimed with the element
+/// at `index` changed by `delta`
+pub fn mutated_state(chain: &SyntheticChain<Fr>, index: usize, delta: Fr) -> StepClaim {
+    let mut next = chain.clone();
+    next.mine(1, SPACING);
+    let mut z_out = next.state.z();
+    z_out[index] += delta;
+    StepClaim {
+        z_out: Some(z_out),
+        ..mutated_witness(chain, |_| ())
+    }
+}
+
+pub fn wrong_prev_hash() -> StepClaim {
+    let chain = ordinary_chain();
+    mutated_header(&chain, |t| t.prev_hash[0] ^= 1)
+}
+
+/// The previous hash field holds the tip plus the field modulus, which packs to the tip
+pub fn prev_hash_plus_modulus() -> StepClaim {
+    let chain = ordinary_chain();
+    let tip = BigInt::from_bytes_le(Sign::Plus, &chain.state.tip_hash);
+    let modulus = f_to_nat(&-Fr::ONE) + 1u64;
+    let (_, bytes) = (tip + modulus).to_bytes_le();
+    mutated_header(&chain, |t| {
+        t.prev_hash = [0u8; 32];
+        t.prev_hash[..bytes.len()].copy_from_slice(&bytes);
+    })
+}
+
+/// The nonce is the first one whose hash is above the target
+pub fn insufficient_work() -> StepClaim {
+    let chain = ordinary_chain();
+    let header = chain.next_template(SPACING).mine_insufficient();
+    claim_with(&chain, header, |_| ())
+}
+
+/// nBits encode a valid target, slightly below the one of the state
+pub fn unexpected_bits() -> StepClaim {
+    let chain = ordinary_chain();
+    mutated_header(&chain, |t| t.bits -= 1)
+}
+
+pub fn timestamp_equal_to_median_time_past() -> StepClaim {
+    let chain = ordinary_chain();
+    let median_time_past = chain.state.timestamps[MEDIAN_TIME_SPAN / 2];
+    mutated_header(&chain, |t| t.timestamp = median_time_past)
+}
+
+/// The mantissa of the retargeted nBits is off by `delta`
+pub fn retarget_off_by(delta: i32) -> StepClaim {
+    let chain = retarget_chain();
+    mutated_header(&chain, |t| t.bits = t.bits.wrapping_add_signed(delta))
+}
+
+/// The first block of the epoch keeps the target of the previous epoch
+pub fn retarget_skipped() -> StepClaim {
+    let chain = retarget_chain();
+    let bits = bits_from_target(&chain.state.target);
+    mutated_header(&chain, |t| t.bits = bits)
+}
+
+/// The retargeted nBits have one more byte of exponent and the mantissa shifted right by a byte.
+/// The mantissa is still the retarget truncated at the exponent, but it is not normalized.
+pub fn denormalized_retarget() -> StepClaim {
+    let chain = retarget_chain();
+    mutated_header(&chain, |t| {
+        let (exponent, mantissa) = (t.bits >> 24, t.bits & 0x00ffffff);
+        t.bits = ((exponent + 1) << 24) | (mantissa >> 8)
+    })
+}
+
+/// The state claims a counter one past the height of the block in its epoch
+pub fn tampered_counter() -> StepClaim {
+    mutated_state(&ordinary_chain(), 14, Fr::from(1))
+}
+
+/// The state claims one more hash of chainwork than the block adds
+pub fn tampered_chain_work() -> StepClaim {
+    mutated_state(&ordinary_chain(), 15, Fr::from(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::mutations::*;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_retarget_step_soundness() {
+        // Given the state and a header starting an epoch, the retargeted next state is unique
+        let chain = retarget_chain();
+        let claim = mutated_witness(&chain, |_| ());
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let z_in = claim.z_in.iter().enumerate().map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()).collect::<Vec<_>>();
+        let z_out = claim.step.synthesize(&mut cs, &z_in).unwrap();
+        for (i, z) in z_out.iter().enumerate() {
+            cs.fix(&format!("z {}", i));
+            cs.output_num(&format!("z_out[{}]", i), z);
+        }
+        for i in 0..10 {
+            cs.fix(&format!("dummy2 {}", i));
+        }
+        // Changing the hashes needs collisions, so only their outputs are mutated
+        cs.assert_sound_where(|path| !["SHA 256", "append block hash", "mmr peak"].iter().any(|p| path.starts_with(p)));
+    }
+
+    // The unmutated claims are satisfied, so that each mutation is rejected for its own sake
+    circuit_tests! {
+        unmutated_step: (mutated_state(&ordinary_chain(), 0, Fr::from(0)), true),
+        unmutated_retarget: (retarget_off_by(0), true),
+        unmutated_witness: (mutated_witness(&retarget_chain(), |_| ()), true),
+    }
+
+    circuit_tests! {
+        mutation_wrong_prev_hash: wrong_prev_hash() => "prev. hash from current block equals the last block hash",
+        mutation_prev_hash_plus_modulus: prev_hash_plus_modulus() => "prev. hash bit",
+        mutation_insufficient_work: insufficient_work() => "hash <= target",
+        mutation_unexpected_bits: unexpected_bits() => "0 = (target - z_i[12]) * z_i[14]",
+        mutation_timestamp_equal_to_median_time_past: timestamp_equal_to_median_time_past() => "median < current timestamp",
+        mutation_retarget_off_by_minus_one: retarget_off_by(-1) => "mantissa matches retarget",
+        mutation_retarget_off_by_one: retarget_off_by(1) => "mantissa matches retarget",
+        mutation_retarget_skipped: retarget_skipped() => "mantissa matches retarget",
+        mutation_denormalized_retarget: denormalized_retarget() => "retarget mantissa normalized",
+        mutation_tampered_counter: tampered_counter() => "claimed z_out[14]",
+        mutation_tampered_chain_work: tampered_chain_work() => "claimed z_out[15]",
+        mutation_block_work: mutated_witness(&ordinary_chain(), |w| w.block_work += 1) => "block_work = quotient",
+        mutation_witness_timestamp: mutated_witness(&ordinary_chain(), |w| w.timestamp += 1) => "current timestamp from header",
+        mutation_witness_target: mutated_witness(&ordinary_chain(), |w| w.target -= 1) => "target matches threshold",
+    }
+}
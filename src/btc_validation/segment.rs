@@ -0,0 +1,345 @@
+use std::fmt::{self, Display, Formatter};
+use std::thread;
+
+use ff::{Field, PrimeFieldBits};
+use nova_snark::errors::NovaError;
+use nova_snark::traits::snark::RelaxedR1CSSNARKTrait;
+use nova_snark::{PublicParams, RecursiveSNARK};
+use num_bigint::BigInt;
+
+use crate::btc_validation::checkpoint_step::{CheckpointParams, CheckpointProof};
+use crate::btc_validation::header_step::BlockHeader;
+use crate::btc_validation::witness::{max_target, HeaderChainState, WitnessError};
+use crate::prover::Params;
+use crate::verifier::{z0_secondary, CurveCycle, Scalar, C1, C2};
+
+#[derive(Debug)]
+pub enum SegmentError {
+    /// The bundle or a segment has no steps
+    Empty,
+    /// The first segment does not start from the expected state
+    InitialStateMismatch,
+    /// The output state of `segment` is not the input state of the next one
+    Disconnected { segment: usize },
+    /// `segment` starts from a state with another proof of work limit than the parameters
+    PowLimitMismatch { segment: usize },
+    /// Proving or verifying `segment` failed
+    Nova { segment: usize, error: NovaError },
+    /// The checkpoint the bundle starts from does not verify
+    Checkpoint(NovaError),
+}
+
+impl Display for SegmentError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SegmentError::Empty => write!(f, "no steps to prove or verify"),
+            SegmentError::InitialStateMismatch => write!(f, "first segment does not start from the initial state"),
+            SegmentError::Disconnected { segment } => {
+                write!(f, "output of segment {} is not the input of segment {}", segment, segment + 1)
+            }
+            SegmentError::PowLimitMismatch { segment } => {
+                write!(f, "segment {} and the parameters have different proof of work limits", segment)
+            }
+            SegmentError::Nova { segment, error } => write!(f, "segment {}: {}", segment, error),
+            SegmentError::Checkpoint(error) => write!(f, "checkpoint: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SegmentError {}
+
+/// Consecutive headers proven on their own, starting from the native state at the first height
+#[derive(Clone, Debug)]
+pub struct Segment<F>
+where
+    F: PrimeFieldBits,
+{
+    pub start: HeaderChainState<F>,
+    pub steps: Vec<BlockHeader<F>>,
+}
+
+impl<F> Segment<F>
+where
+    F: PrimeFieldBits,
+{
+    /// Splits `input` into segments of `segment_len` headers, continuing from `state`.
+    /// Only the native state is advanced here, so the split is cheap compared to proving.
+    pub fn split(
+        state: &mut HeaderChainState<F>,
+        input: Vec<[u64; 10]>,
+        segment_len: usize,
+    ) -> Result<Vec<Self>, WitnessError> {
+        assert!(segment_len > 0, "segments must not be empty");
+        input
+            .chunks(segment_len)
+            .map(|chunk| {
+                let start = state.clone();
+                let steps = BlockHeader::new_blocks_from(state, chunk.to_vec())?;
+                Ok(Self { start, steps })
+            })
+            .collect()
+    }
+
+    pub fn z_in(&self) -> Vec<F> {
+        self.start.z()
+    }
+}
+
+/// Public parameters of the uniform header chain on the cycle `C`, shared by the proofs of all
+/// segments
+pub struct SegmentParams<C: CurveCycle> {
+    pp: Params<C>,
+    pow_limit: BigInt,
+}
+
+impl<C: CurveCycle> SegmentParams<C> {
+    /// Parameters of the mainnet header chain
+    pub fn setup() -> Result<Self, NovaError> {
+        Self::setup_with(max_target())
+    }
+
+    /// Parameters of a header chain whose proof of work limit is `pow_limit`
+    pub fn setup_with(pow_limit: BigInt) -> Result<Self, NovaError> {
+        let pp = PublicParams::setup(
+            &C1::<C>::blank(pow_limit.clone()),
+            &C2::<C>::default(),
+            &*C::S1::ck_floor(),
+            &*C::S2::ck_floor(),
+        )?;
+        Ok(Self { pp, pow_limit })
+    }
+
+    pub fn params(&self) -> &Params<C> {
+        &self.pp
+    }
+}
+
+/// IVC proof of one segment together with the state it starts from
+pub struct SegmentProof<C: CurveCycle> {
+    pub z_in: Vec<Scalar<C>>,
+    pub num_steps: usize,
+    pub snark: RecursiveSNARK<C::E1, C::E2, C1<C>, C2<C>>,
+}
+
+impl<C: CurveCycle> SegmentProof<C> {
+    pub fn prove(params: &SegmentParams<C>, segment: &Segment<Scalar<C>>) -> Result<Self, NovaError> {
+        let circuit_secondary = C2::<C>::default();
+        let z_in = segment.z_in();
+        let mut snark = RecursiveSNARK::new(&params.pp, &segment.steps[0], &circuit_secondary, &z_in, &z0_secondary::<C>())?;
+        for step in &segment.steps {
+            snark.prove_step(&params.pp, step, &circuit_secondary)?;
+        }
+        Ok(Self {
+            z_in,
+            num_steps: segment.steps.len(),
+            snark,
+        })
+    }
+
+    /// Verifies the proof, returns the state after the segment
+    pub fn verify(&self, params: &SegmentParams<C>) -> Result<Vec<Scalar<C>>, NovaError> {
+        let (z_out, _) = self.snark.verify(&params.pp, self.num_steps, &self.z_in, &z0_secondary::<C>())?;
+        Ok(z_out)
+    }
+}
+
+/// Proofs of consecutive segments, which together prove the whole height range
+pub struct SegmentBundle<C: CurveCycle> {
+    pub segments: Vec<SegmentProof<C>>,
+}
+
+impl<C: CurveCycle> SegmentBundle<C> {
+    /// Proves `segments` on up to `threads` threads at a time, keeping their order
+    pub fn prove(
+        params: &SegmentParams<C>,
+        segments: &[Segment<Scalar<C>>],
+        threads: usize,
+    ) -> Result<Self, SegmentError> {
+        if segments.iter().any(|s| s.steps.is_empty()) {
+            return Err(SegmentError::Empty);
+        }
+        if let Some(segment) = segments.iter().position(|s| s.start.pow_limit != params.pow_limit) {
+            return Err(SegmentError::PowLimitMismatch { segment });
+        }
+        let mut proofs = Vec::with_capacity(segments.len());
+        for batch in segments.chunks(threads.max(1)) {
+            let batch_proofs = thread::scope(|s| {
+                let handles = batch
+                    .iter()
+                    .map(|segment| s.spawn(move || SegmentProof::prove(params, segment)))
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|h| h.join().expect("segment prover panicked"))
+                    .collect::<Vec<_>>()
+            });
+            for proof in batch_proofs {
+                let segment = proofs.len();
+                proofs.push(proof.map_err(|error| SegmentError::Nova { segment, error })?);
+            }
+        }
+        Ok(Self { segments: proofs })
+    }
+
+    /// Verifies every segment proof and that the segments are chained from `z0`,
+    /// returns the state after the last segment
+    pub fn verify(&self, params: &SegmentParams<C>, z0: &[Scalar<C>]) -> Result<Vec<Scalar<C>>, SegmentError> {
+        let links = self
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(segment, proof)| {
+                let z_out = proof.verify(params).map_err(|error| SegmentError::Nova { segment, error })?;
+                Ok((proof.z_in.clone(), z_out))
+            })
+            .collect::<Result<Vec<_>, SegmentError>>()?;
+        stitch(z0, &links)
+    }
+
+    /// Verifies `checkpoint` and the bundle from the checkpointed state, so that the checkpoint
+    /// is a prefix step of the chain. Returns the state after the last segment.
+    pub fn verify_from_checkpoint(
+        &self,
+        params: &SegmentParams<C>,
+        checkpoint_params: &CheckpointParams<C>,
+        checkpoint: &CheckpointProof<C>,
+    ) -> Result<Vec<Scalar<C>>, SegmentError> {
+        let z0 = checkpoint.verify(checkpoint_params).map_err(SegmentError::Checkpoint)?;
+        self.verify(params, &z0)
+    }
+}
+
+/// Checks that the (input, output) states of consecutive segments form a chain from `z0`,
+/// returns the last output
+pub fn stitch<F: Field>(z0: &[F], links: &[(Vec<F>, Vec<F>)]) -> Result<Vec<F>, SegmentError> {
+    let (first, last) = match (links.first(), links.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(SegmentError::Empty),
+    };
+    if first.0 != z0 {
+        return Err(SegmentError::InitialStateMismatch);
+    }
+    if let Some(segment) = links.windows(2).position(|w| w[0].1 != w[1].0) {
+        return Err(SegmentError::Disconnected { segment });
+    }
+    Ok(last.1.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::segment::*;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::num::AllocatedNum;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use bellpepper_core::ConstraintSystem;
+    use nova_snark::traits::circuit::StepCircuit;
+
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+    const BLOCK_1: [u64; 10] = [0x010000006fe28c0a, 0xb6f1b372c1a6a246, 0xae63f74f931e8365, 0xe15a089c68d61900, 0x00000000982051fd, 0x1e4ba744bbbe680e, 0x1fee14677ba1a3c3, 0x540bf7b1cdb606e8, 0x57233e0e61bc6649, 0xffff001d01e36299];
+    const BLOCK_2: [u64; 10] = [0x010000004860eb18, 0xbf1b1620e37e9490, 0xfc8a427514416fd7, 0x5159ab86688e9a83, 0x00000000d5fdcc54, 0x1e25de1c7a5added, 0xf24858b8bb665c9f, 0x36ef744ee42c3160, 0x22c90f9bb0bc6649, 0xffff001d08d2bd61];
+
+    /// Synthesizes the steps of `segment` from `z`, returns the state after them
+    fn synthesize_segment(segment: &Segment<Fr>, z: &[Fr]) -> Vec<Fr> {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let mut z = z.iter().enumerate().map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()).collect::<Vec<_>>();
+        for (i, step) in segment.steps.iter().enumerate() {
+            z = step.synthesize(&mut cs.namespace(|| format!("step {}", i)), &z).unwrap();
+        }
+        assert!(cs.is_satisfied());
+        z.iter().map(|v| v.get_value().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        let mut sequential = HeaderChainState::<Fr>::before_genesis();
+        BlockHeader::new_blocks_from(&mut sequential, vec![GENESIS, BLOCK_1, BLOCK_2]).unwrap();
+
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        let segments = Segment::split(&mut state, vec![GENESIS, BLOCK_1, BLOCK_2], 2).unwrap();
+        assert_eq!(segments.iter().map(|s| s.steps.len()).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(segments[0].z_in(), HeaderChainState::<Fr>::before_genesis().z());
+        assert_eq!(state.z(), sequential.z());
+
+        // each segment starts where the circuit of the previous one ends
+        let links = segments.iter().map(|s| (s.z_in(), synthesize_segment(s, &s.z_in()))).collect::<Vec<_>>();
+        assert_eq!(links[0].1, segments[1].z_in());
+        assert_eq!(stitch(&segments[0].z_in(), &links).unwrap(), sequential.z());
+
+        // a header which does not extend the previous segment is rejected
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        assert_eq!(Segment::split(&mut state, vec![GENESIS, BLOCK_2], 1).unwrap_err(), WitnessError::PrevHashMismatch);
+    }
+
+    #[test]
+    fn test_segment_start_soundness() {
+        // The first step of a later segment starts from a state with hashes in the MMR, and
+        // its next state is unique as well
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        let segments = Segment::split(&mut state, vec![GENESIS, BLOCK_1, BLOCK_2], 2).unwrap();
+        let segment = &segments[1];
+
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let z_in = segment.z_in().iter().enumerate().map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", i)), || Ok(*v)).unwrap()).collect::<Vec<_>>();
+        let z_out = segment.steps[0].synthesize(&mut cs, &z_in).unwrap();
+        for (i, z) in z_out.iter().enumerate() {
+            cs.fix(&format!("z {}", i));
+            cs.output_num(&format!("z_out[{}]", i), z);
+        }
+        for i in 0..10 {
+            cs.fix(&format!("dummy2 {}", i));
+        }
+        // Changing the hashes needs collisions, so only their outputs are mutated
+        cs.assert_sound_where(|path| !["SHA 256", "append block hash", "mmr peak"].iter().any(|p| path.starts_with(p)));
+    }
+
+    #[test]
+    fn test_stitch() {
+        let z = |v: u64| vec![Fr::from(v), Fr::from(v + 1)];
+        let links = vec![(z(0), z(1)), (z(1), z(2)), (z(2), z(3))];
+        assert_eq!(stitch(&z(0), &links).unwrap(), z(3));
+
+        assert!(matches!(stitch(&z(1), &links), Err(SegmentError::InitialStateMismatch)));
+        assert!(matches!(stitch::<Fr>(&z(0), &[]), Err(SegmentError::Empty)));
+
+        let mut gap = links.clone();
+        gap[1].1 = z(5);
+        assert!(matches!(stitch(&z(0), &gap), Err(SegmentError::Disconnected { segment: 1 })));
+    }
+
+    #[test]
+    fn test_prove_bundle() {
+        use crate::btc_validation::synthetic::{regtest_pow_limit, SyntheticChain};
+        use crate::verifier::PallasVesta;
+
+        let mut chain = SyntheticChain::<Scalar<PallasVesta>>::regtest();
+        chain.mine(3, 600);
+        let params = SegmentParams::<PallasVesta>::setup_with(regtest_pow_limit()).unwrap();
+        let mut state = chain.start.clone();
+        let z0 = state.z();
+        let segments = Segment::split(&mut state, chain.headers.clone(), 1).unwrap();
+        let mut bundle = SegmentBundle::prove(&params, &segments, 2).unwrap();
+        assert_eq!(bundle.verify(&params, &z0).unwrap(), chain.state.z());
+
+        // the segments must be proven with the parameters of their proof of work limit
+        let mainnet = Segment::split(&mut HeaderChainState::before_genesis(), vec![GENESIS], 1).unwrap();
+        assert!(matches!(SegmentBundle::prove(&params, &mainnet, 1), Err(SegmentError::PowLimitMismatch { segment: 0 })));
+
+        // reordered segments
+        bundle.segments.swap(0, 1);
+        assert!(matches!(bundle.verify(&params, &z0), Err(SegmentError::InitialStateMismatch)));
+        bundle.segments.swap(0, 1);
+        bundle.segments.swap(1, 2);
+        assert!(matches!(bundle.verify(&params, &z0), Err(SegmentError::Disconnected { segment: 0 })));
+        bundle.segments.swap(1, 2);
+
+        // a missing segment
+        let removed = bundle.segments.remove(1);
+        assert!(matches!(bundle.verify(&params, &z0), Err(SegmentError::Disconnected { segment: 0 })));
+        bundle.segments.insert(1, removed);
+
+        // a segment claiming another input state
+        bundle.segments[1].z_in[15] += Scalar::<PallasVesta>::ONE;
+        assert!(matches!(bundle.verify(&params, &z0), Err(SegmentError::Nova { segment: 1, .. })));
+    }
+}
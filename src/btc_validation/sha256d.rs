@@ -0,0 +1,431 @@
+//! SHA256d of an 80 byte block header.
+//!
+//! `bellpepper::gadgets::sha256` already skips the XORs and choices of constant bits, but each
+//! addition allocates bits for the sum of as many full words as it has operands, constants
+//! included. Here the constant operands of every addition, such as the round constants and the
+//! padding words of the second chunk and of the second hash, are folded into one constant, and
+//! only the bits needed by the largest possible sum are allocated. The three compressions also
+//! share one `MultiEq`, which packs the linear equalities of the additions into few constraints.
+
+use bellpepper::gadgets::multieq::MultiEq;
+use bellpepper_core::boolean::{AllocatedBit, Boolean};
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+
+/// Length of a block header in bits
+pub const HEADER_BITS: usize = 640;
+
+#[allow(clippy::unreadable_literal)]
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[allow(clippy::unreadable_literal)]
+const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Computes SHA256d of the `HEADER_BITS` bits of a header, most significant bit of every byte
+/// first. Returns the digest bits in the same order, as `sha256(sha256(header))` would.
+pub fn sha256d_header<F, CS>(cs: CS, header: &[Boolean]) -> Result<Vec<Boolean>, SynthesisError>
+where
+    F: PrimeField,
+    CS: ConstraintSystem<F>,
+{
+    assert_eq!(header.len(), HEADER_BITS);
+    let mut cs = MultiEq::new(cs);
+    let iv = IV.map(Word::constant);
+    let words = header
+        .chunks(32)
+        .map(Word::from_bits_be)
+        .collect::<Vec<_>>();
+
+    // The second chunk holds the last 16 header bytes, the rest is padding
+    let state = compress(cs.namespace(|| "first chunk"), words[..16].to_vec(), &iv)?;
+    let chunk = padded(&words[16..], HEADER_BITS as u64);
+    let digest = compress(cs.namespace(|| "second chunk"), chunk, &state)?;
+
+    let out = compress(cs.namespace(|| "second hash"), padded(&digest, 256), &iv)?;
+    Ok(out.into_iter().flat_map(Word::into_bits_be).collect())
+}
+
+/// A 32 bit word, least significant bit first
+#[derive(Clone)]
+struct Word {
+    bits: Vec<Boolean>,
+}
+
+impl Word {
+    fn constant(value: u32) -> Self {
+        Self {
+            bits: (0..32)
+                .map(|i| Boolean::constant((value >> i) & 1 == 1))
+                .collect(),
+        }
+    }
+
+    fn from_bits_be(bits: &[Boolean]) -> Self {
+        assert_eq!(bits.len(), 32);
+        Self {
+            bits: bits.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn into_bits_be(self) -> Vec<Boolean> {
+        self.bits.into_iter().rev().collect()
+    }
+
+    fn value(&self) -> Option<u32> {
+        self.bits
+            .iter()
+            .enumerate()
+            .try_fold(0u32, |acc, (i, bit)| {
+                Some(acc | (bit.get_value()? as u32) << i)
+            })
+    }
+
+    /// The value of the word, if all its bits are constant
+    fn constant_value(&self) -> Option<u32> {
+        if self.bits.iter().all(Boolean::is_constant) {
+            self.value()
+        } else {
+            None
+        }
+    }
+
+    /// The largest value of the word, given its constant bits
+    fn max_value(&self) -> u64 {
+        self.bits
+            .iter()
+            .enumerate()
+            .filter(|(_, bit)| !matches!(bit, Boolean::Constant(false)))
+            .map(|(i, _)| 1u64 << i)
+            .sum()
+    }
+
+    fn rotr(&self, by: usize) -> Self {
+        Self {
+            bits: self
+                .bits
+                .iter()
+                .cycle()
+                .skip(by % 32)
+                .take(32)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn shr(&self, by: usize) -> Self {
+        Self {
+            bits: self.bits[by..]
+                .iter()
+                .cloned()
+                .chain(std::iter::repeat(Boolean::constant(false)))
+                .take(32)
+                .collect(),
+        }
+    }
+
+    /// Applies `op` to the bits of `words` in parallel
+    fn bitwise<F, CS, Op>(mut cs: CS, words: [&Self; 3], op: Op) -> Result<Self, SynthesisError>
+    where
+        F: PrimeField,
+        CS: ConstraintSystem<F>,
+        Op: Fn(&mut CS, usize, [&Boolean; 3]) -> Result<Boolean, SynthesisError>,
+    {
+        let bits = (0..32)
+            .map(|i| op(&mut cs, i, words.map(|w| &w.bits[i])))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { bits })
+    }
+
+    fn xor3<F: PrimeField, CS: ConstraintSystem<F>>(
+        cs: CS,
+        words: [&Self; 3],
+    ) -> Result<Self, SynthesisError> {
+        Self::bitwise(cs, words, |cs, i, [a, b, c]| {
+            let ab = Boolean::xor(cs.namespace(|| format!("first xor {}", i)), a, b)?;
+            Boolean::xor(cs.namespace(|| format!("second xor {}", i)), &ab, c)
+        })
+    }
+
+    fn ch<F: PrimeField, CS: ConstraintSystem<F>>(
+        cs: CS,
+        words: [&Self; 3],
+    ) -> Result<Self, SynthesisError> {
+        Self::bitwise(cs, words, |cs, i, [e, f, g]| {
+            Boolean::sha256_ch(cs.namespace(|| format!("ch {}", i)), e, f, g)
+        })
+    }
+
+    fn maj<F: PrimeField, CS: ConstraintSystem<F>>(
+        cs: CS,
+        words: [&Self; 3],
+    ) -> Result<Self, SynthesisError> {
+        Self::bitwise(cs, words, |cs, i, [a, b, c]| {
+            Boolean::sha256_maj(cs.namespace(|| format!("maj {}", i)), a, b, c)
+        })
+    }
+}
+
+/// Sum of `operands` modulo 2^32. The constant operands are folded into one constant, and the
+/// sum is decomposed into as many bits as its largest value needs.
+fn add<F, CS, M>(mut cs: M, operands: &[Word]) -> Result<Word, SynthesisError>
+where
+    F: PrimeField,
+    CS: ConstraintSystem<F>,
+    M: ConstraintSystem<F, Root = MultiEq<F, CS>>,
+{
+    let (constants, variables): (Vec<_>, Vec<_>) =
+        operands.iter().partition(|w| w.constant_value().is_some());
+    let constant = constants
+        .iter()
+        .filter_map(|w| w.constant_value())
+        .fold(0u32, u32::wrapping_add);
+    match (variables.len(), constant) {
+        (0, _) => return Ok(Word::constant(constant)),
+        (1, 0) => return Ok(variables[0].clone()),
+        _ => {}
+    }
+
+    let max_value = variables.iter().map(|w| w.max_value()).sum::<u64>() + constant as u64;
+    let value = variables
+        .iter()
+        .map(|w| w.value().map(u64::from))
+        .sum::<Option<u64>>()
+        .map(|v| v + constant as u64);
+
+    let mut lc = LinearCombination::zero() + (F::from(constant as u64), CS::one());
+    for word in &variables {
+        let mut coeff = F::ONE;
+        for bit in &word.bits {
+            lc = lc + &bit.lc(CS::one(), coeff);
+            coeff = coeff.double();
+        }
+    }
+
+    let num_bits = (u64::BITS - max_value.leading_zeros()) as usize;
+    let mut bits = Vec::with_capacity(num_bits.max(32));
+    let mut result_lc = LinearCombination::zero();
+    let mut coeff = F::ONE;
+    for i in 0..num_bits {
+        let bit = AllocatedBit::alloc(
+            cs.namespace(|| format!("result bit {}", i)),
+            value.map(|v| (v >> i) & 1 == 1),
+        )?;
+        result_lc = result_lc + (coeff, bit.get_variable());
+        bits.push(Boolean::from(bit));
+        coeff = coeff.double();
+    }
+    cs.get_root().enforce_equal(num_bits, &lc, &result_lc);
+
+    // Carry bits are dropped, missing high bits are zero
+    bits.resize(32, Boolean::constant(false));
+    Ok(Word { bits })
+}
+
+/// `words` followed by the padding of a message of `len` bits which ends with them
+fn padded(words: &[Word], len: u64) -> Vec<Word> {
+    let mut chunk = words.to_vec();
+    chunk.push(Word::constant(0x80000000));
+    chunk.resize(14, Word::constant(0));
+    chunk.push(Word::constant((len >> 32) as u32));
+    chunk.push(Word::constant(len as u32));
+    chunk
+}
+
+/// A sum whose bits are only computed when needed, so that more terms can be added first
+enum Lazy {
+    Sum(Vec<Word>),
+    Word(Word),
+}
+
+impl Lazy {
+    fn compute<F, CS, M>(self, cs: M, others: &[Word]) -> Result<Word, SynthesisError>
+    where
+        F: PrimeField,
+        CS: ConstraintSystem<F>,
+        M: ConstraintSystem<F, Root = MultiEq<F, CS>>,
+    {
+        let mut terms = match self {
+            Lazy::Word(word) if others.is_empty() => return Ok(word),
+            Lazy::Word(word) => vec![word],
+            Lazy::Sum(terms) => terms,
+        };
+        terms.extend_from_slice(others);
+        add(cs, &terms)
+    }
+}
+
+/// The SHA-256 compression function on the 16 words of `chunk`
+fn compress<F, CS, M>(
+    mut cs: M,
+    chunk: Vec<Word>,
+    state: &[Word; 8],
+) -> Result<[Word; 8], SynthesisError>
+where
+    F: PrimeField,
+    CS: ConstraintSystem<F>,
+    M: ConstraintSystem<F, Root = MultiEq<F, CS>>,
+{
+    assert_eq!(chunk.len(), 16);
+    let mut w = chunk;
+    for i in 16..64 {
+        let cs = &mut cs.namespace(|| format!("w extension {}", i));
+        let s0 = Word::xor3(
+            cs.namespace(|| "s0"),
+            [&w[i - 15].rotr(7), &w[i - 15].rotr(18), &w[i - 15].shr(3)],
+        )?;
+        let s1 = Word::xor3(
+            cs.namespace(|| "s1"),
+            [&w[i - 2].rotr(17), &w[i - 2].rotr(19), &w[i - 2].shr(10)],
+        )?;
+        let word = add(
+            cs.namespace(|| "w"),
+            &[w[i - 16].clone(), s0, w[i - 7].clone(), s1],
+        )?;
+        w.push(word);
+    }
+
+    let [a, b, c, d, e, f, g, h] = state.clone();
+    let (mut a, mut b, mut c, mut d) = (Lazy::Word(a), b, c, d);
+    let (mut e, mut f, mut g, mut h) = (Lazy::Word(e), f, g, h);
+    for (i, w) in w.into_iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("compression round {}", i));
+
+        let new_e = e.compute(cs.namespace(|| "e"), &[])?;
+        let s1 = Word::xor3(
+            cs.namespace(|| "s1"),
+            [&new_e.rotr(6), &new_e.rotr(11), &new_e.rotr(25)],
+        )?;
+        let ch = Word::ch(cs.namespace(|| "ch"), [&new_e, &f, &g])?;
+        let temp1 = [h, s1, ch, Word::constant(ROUND_CONSTANTS[i]), w];
+
+        let new_a = a.compute(cs.namespace(|| "a"), &[])?;
+        let s0 = Word::xor3(
+            cs.namespace(|| "s0"),
+            [&new_a.rotr(2), &new_a.rotr(13), &new_a.rotr(22)],
+        )?;
+        let maj = Word::maj(cs.namespace(|| "maj"), [&new_a, &b, &c])?;
+
+        h = g;
+        g = f;
+        f = new_e;
+        e = Lazy::Sum(temp1.iter().cloned().chain([d]).collect());
+        d = c;
+        c = b;
+        b = new_a;
+        a = Lazy::Sum(temp1.into_iter().chain([s0, maj]).collect());
+    }
+
+    Ok([
+        a.compute(cs.namespace(|| "new h0"), &[state[0].clone()])?,
+        add(cs.namespace(|| "new h1"), &[state[1].clone(), b])?,
+        add(cs.namespace(|| "new h2"), &[state[2].clone(), c])?,
+        add(cs.namespace(|| "new h3"), &[state[3].clone(), d])?,
+        e.compute(cs.namespace(|| "new h4"), &[state[4].clone()])?,
+        add(cs.namespace(|| "new h5"), &[state[5].clone(), f])?,
+        add(cs.namespace(|| "new h6"), &[state[6].clone(), g])?,
+        add(cs.namespace(|| "new h7"), &[state[7].clone(), h])?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::sha256d::*;
+    use crate::btc_validation::witness::RawHeader;
+    use crate::util::fuzz::FuzzingConstraintSystem;
+    use crate::util::scalar::Fr;
+    use bellpepper::gadgets::sha256::sha256;
+    use bellpepper_core::boolean::u64_into_boolean_vec_le;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use ff::Field;
+
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+    const BLOCK_123456: [u64; 10] = [0x010000009500c43a, 0x25c624520b5100ad, 0xf82cb9f9da72fd24, 0x47a496bc600b0000, 0x000000006cd86237, 0x0395dedf1da2841c, 0xcda0fc489e3039de, 0x5f1ccddef0e83499, 0x1a65600ea6c8cb4d, 0xb3936a1ae3143991];
+
+    /// Allocates the header bits in the order of `BlockHeader::synthesize`
+    fn header_bits<CS: ConstraintSystem<Fr>>(cs: &mut CS, header: &[u64; 10]) -> Vec<Boolean> {
+        header.iter().enumerate().flat_map(|(i, word)| {
+            let mut bits = u64_into_boolean_vec_le(cs.namespace(|| format!("header {}", i)), Some(*word)).unwrap();
+            bits.reverse();
+            bits
+        }).collect()
+    }
+
+    fn bytes(bits: &[Boolean]) -> Vec<u8> {
+        bits.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, bit| acc << 1 | bit.get_value().unwrap() as u8)).collect()
+    }
+
+    #[test]
+    fn test_sha256d_header() {
+        for header in [GENESIS, BLOCK_123456] {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let bits = header_bits(&mut cs, &header);
+            let before = cs.num_constraints();
+            let out = sha256d_header(cs.namespace(|| "sha256d"), &bits).unwrap();
+            let specialized = cs.num_constraints() - before;
+            assert!(cs.is_satisfied());
+            assert_eq!(bytes(&out), RawHeader(header).hash());
+
+            // same digest as the generic gadget called twice, for fewer constraints
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let bits = header_bits(&mut cs, &header);
+            let before = cs.num_constraints();
+            let first = sha256(cs.namespace(|| "first"), &bits).unwrap();
+            let generic_out = sha256(cs.namespace(|| "second"), &first).unwrap();
+            let generic = cs.num_constraints() - before;
+            assert_eq!(bytes(&generic_out), bytes(&out));
+            assert!(specialized < generic, "{} >= {}", specialized, generic);
+        }
+    }
+
+    #[test]
+    fn test_sha256d_header_forged() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let bits = header_bits(&mut cs, &BLOCK_123456);
+        sha256d_header(cs.namespace(|| "sha256d"), &bits).unwrap();
+        assert!(cs.is_satisfied());
+
+        // flipping a bit of a sum breaks the packed equality of its chunk
+        let path = "sha256d/second hash/compression round 63/a/result bit 5/boolean";
+        let bit = cs.get(path);
+        cs.set(path, Fr::ONE - bit);
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_add_soundness() {
+        // with a constant operand, fewer carry bits are allocated, and the sum stays unique
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let (a, b) = (0xfedcba98u32, 0x89abcdefu32);
+        let word = Word::from_bits_be(&(0..32).map(|i| {
+            Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("a {}", i)), Some((a >> (31 - i)) & 1 == 1)).unwrap())
+        }).collect::<Vec<_>>());
+        for i in 0..32 {
+            cs.fix(&format!("a {}", i));
+        }
+        let sum = {
+            let mut multi = MultiEq::new(&mut cs);
+            let sum = add(multi.namespace(|| "sum"), &[word.clone(), Word::constant(b), Word::constant(0)]).unwrap();
+            let sum2 = add(multi.namespace(|| "sum2"), &[word, sum.clone()]).unwrap();
+            (sum, sum2)
+        };
+        assert_eq!(sum.0.value(), Some(a.wrapping_add(b)));
+        assert_eq!(sum.1.value(), Some(a.wrapping_add(a.wrapping_add(b))));
+        for (name, word) in [("sum", &sum.0), ("sum2", &sum.1)] {
+            for (i, bit) in word.bits.iter().enumerate() {
+                cs.output_boolean(&format!("{} bit {}", name, i), bit);
+            }
+        }
+        cs.assert_sound();
+    }
+}
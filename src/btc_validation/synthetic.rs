@@ -0,0 +1,239 @@
+//! Synthetic header chains for end-to-end tests, mined on the CPU at an easy proof of work limit
+//!
+//! Chains start from a `HeaderChainState` whose `pow_limit` is `regtest_pow_limit()`, so that a
+//! block takes a few hundred hashes, and follow the mainnet rules otherwise: the target is
+//! retargeted every `DIFFICULTY_ADJUSTMENT_INTERVAL` blocks, by a factor of at most four, and
+//! capped at the limit. Every header is checked by `HeaderChainState::append` as it is added.
+
+use ff::{PrimeField, PrimeFieldBits};
+use num_bigint::{BigInt, Sign};
+
+use crate::btc_validation::header_step::BlockHeader;
+use crate::btc_validation::median::MEDIAN_TIME_SPAN;
+use crate::btc_validation::witness::{
+    bits_from_target, target_from_bits, HeaderChainState, RawHeader, WitnessError, TARGET_TIMESPAN,
+};
+
+/// nBits of the proof of work limit of synthetic chains. About one hash in 256 meets it, and it
+/// is far enough below the moduli of the supported fields for targets to be field elements.
+pub const REGTEST_BITS: u32 = 0x2000ffff;
+
+pub fn regtest_pow_limit() -> BigInt {
+    target_from_bits(REGTEST_BITS)
+}
+
+/// Fields of a header, before its nonce is chosen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeaderTemplate {
+    pub version: u32,
+    /// Hash of the previous header, in the byte order it is produced
+    pub prev_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub timestamp: u32,
+    pub bits: u32,
+}
+
+impl HeaderTemplate {
+    pub fn header(&self, nonce: u32) -> RawHeader {
+        let mut bytes = Vec::with_capacity(80);
+        bytes.extend(self.version.to_le_bytes());
+        bytes.extend(self.prev_hash);
+        bytes.extend(self.merkle_root);
+        bytes.extend(self.timestamp.to_le_bytes());
+        bytes.extend(self.bits.to_le_bytes());
+        bytes.extend(nonce.to_le_bytes());
+        let mut words = [0u64; 10];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks(8)) {
+            *word = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        RawHeader(words)
+    }
+
+    /// Header with the first nonce whose hash meets the target of `bits`, or misses it
+    fn mine_with(&self, meets_target: bool) -> RawHeader {
+        let target = target_from_bits(self.bits);
+        (0..=u32::MAX)
+            .map(|nonce| self.header(nonce))
+            .find(|h| (BigInt::from_bytes_le(Sign::Plus, &h.hash()) <= target) == meets_target)
+            .expect("no nonce left")
+    }
+
+    /// Header whose hash meets the target of `bits`
+    pub fn mine(&self) -> RawHeader {
+        self.mine_with(true)
+    }
+
+    /// Header whose hash is above the target of `bits`
+    pub fn mine_insufficient(&self) -> RawHeader {
+        self.mine_with(false)
+    }
+}
+
+/// Headers extending the state `start`, which are all valid
+#[derive(Clone, Debug)]
+pub struct SyntheticChain<F: PrimeField> {
+    pub start: HeaderChainState<F>,
+    /// State after the last header
+    pub state: HeaderChainState<F>,
+    pub headers: Vec<[u64; 10]>,
+}
+
+impl<F: PrimeField> SyntheticChain<F> {
+    pub fn from_state(state: HeaderChainState<F>) -> Self {
+        Self {
+            start: state.clone(),
+            state,
+            headers: vec![],
+        }
+    }
+
+    /// Empty chain before its genesis block, at the regtest limit
+    pub fn regtest() -> Self {
+        Self::from_state(HeaderChainState::before_genesis_with(regtest_pow_limit()))
+    }
+
+    /// Empty chain at the regtest limit whose next block has height `counter` in its epoch.
+    /// The previous blocks of the epoch are `spacing` seconds apart, the first one at
+    /// `4 * TARGET_TIMESPAN`.
+    pub fn regtest_in_epoch(counter: u64, spacing: u32) -> Self {
+        let mut state = HeaderChainState::before_genesis_with(regtest_pow_limit());
+        let epoch_start = 4 * TARGET_TIMESPAN as u32;
+        let last_height = counter as i64 - 1;
+        for (i, t) in state.timestamps.iter_mut().enumerate() {
+            let height = last_height - (MEDIAN_TIME_SPAN - 1 - i) as i64;
+            *t = epoch_start.saturating_add_signed(height as i32 * spacing as i32);
+        }
+        state.epoch_start_time = epoch_start;
+        state.counter = counter;
+        Self::from_state(state)
+    }
+
+    /// Template of a valid next header, `spacing` seconds after the last one
+    pub fn next_template(&self, spacing: u32) -> HeaderTemplate {
+        let mut merkle_root = [0u8; 32];
+        merkle_root[..8].copy_from_slice(&(self.headers.len() as u64).to_le_bytes());
+        HeaderTemplate {
+            version: 4,
+            prev_hash: self.state.tip_hash,
+            merkle_root,
+            timestamp: self.state.timestamps[MEDIAN_TIME_SPAN - 1] + spacing,
+            bits: bits_from_target(&self.state.next_target()),
+        }
+    }
+
+    /// Appends `header` if it extends the chain
+    pub fn push(&mut self, header: RawHeader) -> Result<(), WitnessError> {
+        self.state.append(header)?;
+        self.headers.push(header.0);
+        Ok(())
+    }
+
+    /// Mines `n` headers, `spacing` seconds apart
+    pub fn mine(&mut self, n: usize, spacing: u32) -> &mut Self {
+        for _ in 0..n {
+            let header = self.next_template(spacing).mine();
+            self.push(header).expect("mined header is valid");
+        }
+        self
+    }
+}
+
+impl<F: PrimeField + PrimeFieldBits> SyntheticChain<F> {
+    /// Steps proving the headers from `start`
+    pub fn steps(&self) -> Vec<BlockHeader<F>> {
+        BlockHeader::new_blocks_from(&mut self.start.clone(), self.headers.clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::synthetic::*;
+    use crate::btc_validation::witness::DIFFICULTY_ADJUSTMENT_INTERVAL;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::num::AllocatedNum;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use bellpepper_core::ConstraintSystem;
+    use nova_snark::traits::circuit::StepCircuit;
+
+    /// Synthesizes the steps of `chain`, checks that each is satisfied and returns the last state
+    fn synthesize_chain(chain: &SyntheticChain<Fr>) -> Vec<Fr> {
+        let mut z = chain.start.z();
+        for (i, step) in chain.steps().iter().enumerate() {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let z_in = z.iter().enumerate().map(|(j, v)| AllocatedNum::alloc(cs.namespace(|| format!("z {}", j)), || Ok(*v)).unwrap()).collect::<Vec<_>>();
+            let z_out = step.synthesize(&mut cs, &z_in).unwrap();
+            assert!(cs.is_satisfied(), "step {}: {:?}", i, cs.which_is_unsatisfied());
+            z = z_out.iter().map(|v| v.get_value().unwrap()).collect();
+        }
+        z
+    }
+
+    #[test]
+    fn test_mine() {
+        let chain = SyntheticChain::<Fr>::regtest();
+        let template = HeaderTemplate { version: 0x20000000, ..chain.next_template(600) };
+        let header = template.mine();
+        assert!(BigInt::from_bytes_le(Sign::Plus, &header.hash()) <= regtest_pow_limit());
+        assert_eq!(header.bytes()[..4], 0x20000000u32.to_le_bytes());
+        assert_eq!(header.prev_hash(), chain.state.tip_hash);
+        assert_eq!(header.timestamp(), 4 * TARGET_TIMESPAN as u32 + 600);
+        assert_eq!(header.bits(), REGTEST_BITS);
+
+        let header = template.mine_insufficient();
+        assert!(BigInt::from_bytes_le(Sign::Plus, &header.hash()) > regtest_pow_limit());
+    }
+
+    #[test]
+    fn test_regtest_chain() {
+        let mut chain = SyntheticChain::<Fr>::regtest();
+        chain.mine(3, 600);
+        assert_eq!(chain.headers.len(), 3);
+        assert_eq!(chain.state.counter, 3);
+        // every block is at the limit, just below 2^248, so it adds 256 hashes of work
+        assert_eq!(chain.state.chain_work, BigInt::from(3 * 256));
+        assert_eq!(synthesize_chain(&chain), chain.state.z());
+    }
+
+    #[test]
+    fn test_retarget_boundaries() {
+        let limit = regtest_pow_limit();
+        let cases = [
+            // an epoch 600 seconds shorter than expected
+            (600, &limit * (TARGET_TIMESPAN - 600) / TARGET_TIMESPAN),
+            // the timespan is clamped to a quarter of the expected one
+            (60, &limit / 4),
+            // the timespan is clamped to four times the expected one, then the target is capped
+            (6000, limit.clone()),
+        ];
+        for (spacing, expected) in cases {
+            // the last block of the epoch, then the first block of the next one
+            let mut chain = SyntheticChain::<Fr>::regtest_in_epoch(DIFFICULTY_ADJUSTMENT_INTERVAL - 1, spacing);
+            chain.mine(2, spacing);
+            assert_eq!(chain.state.target, target_from_bits(bits_from_target(&expected)));
+            assert_eq!(chain.state.counter, 1);
+            assert_eq!(synthesize_chain(&chain), chain.state.z());
+        }
+    }
+
+    #[test]
+    fn test_invalid_blocks() {
+        let mut chain = SyntheticChain::<Fr>::regtest();
+        chain.mine(MEDIAN_TIME_SPAN, 600);
+        let template = chain.next_template(600);
+        let median_time_past = chain.state.timestamps[MEDIAN_TIME_SPAN / 2];
+
+        let cases = [
+            (HeaderTemplate { prev_hash: [1; 32], ..template }.mine(), WitnessError::PrevHashMismatch),
+            (HeaderTemplate { bits: 0x1f00ffff, ..template }.mine(), WitnessError::UnexpectedBits { bits: 0x1f00ffff, expected: REGTEST_BITS }),
+            (HeaderTemplate { timestamp: median_time_past, ..template }.mine(), WitnessError::TimestampTooEarly { timestamp: median_time_past, median_time_past }),
+            (template.mine_insufficient(), WitnessError::InsufficientWork),
+        ];
+        for (header, error) in cases {
+            assert_eq!(chain.push(header), Err(error));
+        }
+        // the rejected headers leave the chain unchanged
+        assert_eq!(chain.headers.len(), MEDIAN_TIME_SPAN);
+        assert_eq!(chain.state.tip_hash, RawHeader(chain.headers[MEDIAN_TIME_SPAN - 1]).hash());
+        assert!(chain.push(template.mine()).is_ok());
+    }
+}
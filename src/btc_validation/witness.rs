@@ -0,0 +1,326 @@
+use std::fmt::{self, Display, Formatter};
+
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero;
+use sha2::{Digest, Sha256};
+
+use crate::btc_validation::median::{compute_median_timestamp, MEDIAN_TIME_SPAN};
+use crate::btc_validation::mmr::{MerkleMountainRange, MMR_MAX_PEAKS};
+use crate::util::convert::nat_to_f;
+
+/// Number of blocks between two target updates
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 2016;
+
+/// Expected duration of a difficulty epoch in seconds
+pub const TARGET_TIMESPAN: u64 = 2016 * 10 * 60;
+
+/// Number of elements of the step circuit state, see `HeaderChainState::z`
+pub const STATE_LEN: usize = 18;
+
+/// Largest target, i.e. the target at difficulty 1
+pub fn max_target() -> BigInt {
+    BigInt::from(0xFFFFu64) << 208
+}
+
+/// Decodes the compact `nBits` encoding of a target.
+pub fn target_from_bits(bits: u32) -> BigInt {
+    let exponent = bits >> 24;
+    let mantissa = BigInt::from(bits & 0x007f_ffff);
+    if exponent <= 3 {
+        mantissa >> (8 * (3 - exponent))
+    } else {
+        mantissa << (8 * (exponent - 3))
+    }
+}
+
+/// Encodes a target in the compact `nBits` encoding, dropping all but the
+/// three most significant bytes.
+pub fn bits_from_target(target: &BigInt) -> u32 {
+    let mut size = (target.bits() as u32).div_ceil(8);
+    let mut compact = if size <= 3 {
+        (target << (8 * (3 - size))).to_u32_digits().1.first().copied().unwrap_or(0)
+    } else {
+        (target >> (8 * (size - 3))).to_u32_digits().1.first().copied().unwrap_or(0)
+    };
+    // the mantissa is signed, so keep its top bit clear
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | size << 24
+}
+
+/// Field elements are packed from the little-endian reading of the bytes
+pub(crate) fn le_bytes_to_f<F: PrimeField>(bytes: &[u8]) -> F {
+    let byte_base = F::from(256u64);
+    bytes.iter().rev().fold(F::ZERO, |acc, byte| acc * byte_base + F::from(*byte as u64))
+}
+
+fn nat_to_scalar<F: PrimeField>(n: &BigInt) -> F {
+    nat_to_f(n).expect("value does not fit in the field")
+}
+
+/// An 80-byte header, given as ten big-endian 64-bit words
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RawHeader(pub [u64; 10]);
+
+impl RawHeader {
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    fn le_u32(&self, offset: usize) -> u32 {
+        let bytes = self.bytes();
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    pub fn prev_hash(&self) -> [u8; 32] {
+        self.bytes()[4..36].try_into().unwrap()
+    }
+
+    pub fn timestamp(&self) -> u32 {
+        self.le_u32(68)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.le_u32(72)
+    }
+
+    /// SHA256d of the header, in the byte order it is produced
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(Sha256::digest(self.bytes())).into()
+    }
+}
+
+/// Reasons for which a header cannot extend the chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessError {
+    PrevHashMismatch,
+    /// The timestamp is not later than the median of the previous timestamps
+    TimestampTooEarly { timestamp: u32, median_time_past: u32 },
+    /// `nBits` does not match the target required at this height
+    UnexpectedBits { bits: u32, expected: u32 },
+    /// The hash is above the target
+    InsufficientWork,
+}
+
+impl Display for WitnessError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WitnessError::PrevHashMismatch => write!(f, "previous hash does not match the chain tip"),
+            WitnessError::TimestampTooEarly { timestamp, median_time_past } => write!(
+                f,
+                "timestamp {} is not after the median time past {}",
+                timestamp, median_time_past
+            ),
+            WitnessError::UnexpectedBits { bits, expected } => {
+                write!(f, "nBits {:#010x} differ from the expected {:#010x}", bits, expected)
+            }
+            WitnessError::InsufficientWork => write!(f, "block hash is above the target"),
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+/// Native values consumed by the step circuit for one header
+#[derive(Clone, Debug)]
+pub struct BlockHeaderWitness<F: PrimeField> {
+    pub header: RawHeader,
+    pub timestamp: u32,
+    pub target: BigInt,
+    /// `2^256 / (target + 1)`, the work added by the block as Bitcoin counts it
+    pub block_work: BigInt,
+    /// Peaks of the block hash MMR before the block is appended
+    pub mmr_peaks: Vec<F>,
+}
+
+impl<F: PrimeField> Default for BlockHeaderWitness<F> {
+    fn default() -> Self {
+        Self {
+            header: RawHeader::default(),
+            timestamp: 0,
+            target: BigInt::zero(),
+            block_work: BigInt::zero(),
+            mmr_peaks: vec![F::ZERO; MMR_MAX_PEAKS],
+        }
+    }
+}
+
+/// Native mirror of the step circuit state, used to generate the witness of each step
+#[derive(Clone, Debug)]
+pub struct HeaderChainState<F: PrimeField> {
+    /// SHA256d of the last header, in the byte order it is produced
+    pub tip_hash: [u8; 32],
+    /// Timestamps of the last `MEDIAN_TIME_SPAN` blocks, oldest first
+    pub timestamps: [u32; MEDIAN_TIME_SPAN],
+    pub target: BigInt,
+    /// Timestamp of the first block of the current difficulty epoch
+    pub epoch_start_time: u32,
+    /// Height of the next block modulo `DIFFICULTY_ADJUSTMENT_INTERVAL`
+    pub counter: u64,
+    pub chain_work: BigInt,
+    pub mmr: MerkleMountainRange<F>,
+    /// Easiest target of the chain, `max_target()` on mainnet. It caps retargets and is a
+    /// constant of the step circuit, see `BlockHeader::new`, not part of its state.
+    pub pow_limit: BigInt,
+}
+
+impl<F: PrimeField> HeaderChainState<F> {
+    /// The state as step circuit inputs, see `BlockHeader::synthesize` for the layout
+    pub fn z(&self) -> Vec<F> {
+        let mut z = vec![le_bytes_to_f(&self.tip_hash)];
+        z.extend(self.timestamps.iter().map(|t| F::from(*t as u64)));
+        z.push(nat_to_scalar(&self.target));
+        z.push(F::from(self.epoch_start_time as u64));
+        z.push(F::from(self.counter));
+        z.push(nat_to_scalar(&self.chain_work));
+        z.push(self.mmr.root());
+        z.push(F::from(self.mmr.leaf_count()));
+        z
+    }
+
+    /// State before the genesis block, which starts the first epoch. The previous epoch is a
+    /// placeholder whose timespan keeps the retargeted value at `max_target()`.
+    pub fn before_genesis() -> Self {
+        Self::before_genesis_with(max_target())
+    }
+
+    /// State before the genesis block of a chain whose proof of work limit is `pow_limit`,
+    /// as in `before_genesis`
+    pub fn before_genesis_with(pow_limit: BigInt) -> Self {
+        let mut timestamps = [0u32; MEDIAN_TIME_SPAN];
+        timestamps[MEDIAN_TIME_SPAN - 1] = (4 * TARGET_TIMESPAN) as u32;
+        Self {
+            timestamps,
+            target: pow_limit.clone(),
+            pow_limit,
+            ..Default::default()
+        }
+    }
+
+    /// Target required for the next block
+    pub fn next_target(&self) -> BigInt {
+        if self.counter != 0 {
+            return self.target.clone();
+        }
+        let last_timestamp = self.timestamps[MEDIAN_TIME_SPAN - 1] as u64;
+        let timespan = last_timestamp
+            .saturating_sub(self.epoch_start_time as u64)
+            .clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4);
+        let target = (&self.target * timespan / TARGET_TIMESPAN).min(self.pow_limit.clone());
+        target_from_bits(bits_from_target(&target))
+    }
+
+    /// Witness for proving `header` on this state, without checking that it extends the chain.
+    /// The step circuit is only satisfied by the witnesses of valid headers.
+    pub fn witness_unchecked(&self, header: RawHeader) -> BlockHeaderWitness<F> {
+        let target = target_from_bits(header.bits());
+        BlockHeaderWitness {
+            header,
+            timestamp: header.timestamp(),
+            block_work: (BigInt::from(1u64) << 256) / (&target + 1u64),
+            target,
+            mmr_peaks: self.mmr.peaks(),
+        }
+    }
+
+    /// Checks that `header` extends the chain, returns the witness for proving it
+    /// and advances the state past it.
+    pub fn append(&mut self, header: RawHeader) -> Result<BlockHeaderWitness<F>, WitnessError> {
+        if header.prev_hash() != self.tip_hash {
+            return Err(WitnessError::PrevHashMismatch);
+        }
+
+        let expected = bits_from_target(&self.next_target());
+        if header.bits() != expected {
+            return Err(WitnessError::UnexpectedBits { bits: header.bits(), expected });
+        }
+        let target = target_from_bits(header.bits());
+
+        let hash = header.hash();
+        if BigInt::from_bytes_le(Sign::Plus, &hash) > target {
+            return Err(WitnessError::InsufficientWork);
+        }
+
+        let timestamp = header.timestamp();
+        let median_time_past = compute_median_timestamp(&mut self.timestamps.to_vec());
+        if timestamp <= median_time_past {
+            return Err(WitnessError::TimestampTooEarly { timestamp, median_time_past });
+        }
+
+        let witness = self.witness_unchecked(header);
+
+        self.tip_hash = hash;
+        self.timestamps.rotate_left(1);
+        self.timestamps[MEDIAN_TIME_SPAN - 1] = timestamp;
+        if self.counter == 0 {
+            self.epoch_start_time = timestamp;
+        }
+        self.target = target;
+        self.counter = (self.counter + 1) % DIFFICULTY_ADJUSTMENT_INTERVAL;
+        self.chain_work += &witness.block_work;
+        self.mmr.append(le_bytes_to_f(&hash));
+
+        Ok(witness)
+    }
+}
+
+impl<F: PrimeField> Default for HeaderChainState<F> {
+    fn default() -> Self {
+        Self {
+            tip_hash: [0u8; 32],
+            timestamps: [0u32; MEDIAN_TIME_SPAN],
+            target: max_target(),
+            epoch_start_time: 0,
+            counter: 0,
+            chain_work: BigInt::zero(),
+            mmr: MerkleMountainRange::new(),
+            pow_limit: max_target(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::witness::*;
+    use num_traits::One;
+    use crate::util::scalar::Fr;
+
+    #[test]
+    fn test_compact_bits() {
+        // block 123456
+        let target = target_from_bits(0x1a6a93b3);
+        assert_eq!(target, BigInt::from(0x6a93b3u64) << 184);
+        assert_eq!(bits_from_target(&target), 0x1a6a93b3);
+
+        assert_eq!(target_from_bits(0x1d00ffff), max_target());
+        assert_eq!(bits_from_target(&max_target()), 0x1d00ffff);
+
+        // mantissas with the top bit set get an extra byte
+        assert_eq!(bits_from_target(&BigInt::from(0x80u64)), 0x02008000);
+        assert_eq!(bits_from_target(&BigInt::one()), 0x01010000);
+        assert_eq!(target_from_bits(0x01010000), BigInt::one());
+    }
+
+    #[test]
+    fn test_next_target() {
+        let mut state = HeaderChainState::<Fr> {
+            target: target_from_bits(0x17058ebe),
+            counter: 5,
+            ..Default::default()
+        };
+        assert_eq!(state.next_target(), state.target);
+
+        // blocks 796320 to 798335, retargeted at block 798336 to nBits 0x17053894
+        state.counter = 0;
+        state.epoch_start_time = 0;
+        state.timestamps[MEDIAN_TIME_SPAN - 1] = 13 * 24 * 3600 + 3 * 3600 + 39 * 60 + 6;
+        assert_eq!(bits_from_target(&state.next_target()), 0x17053894);
+
+        // the timespan is clamped to a quarter of the expected one
+        state.timestamps[MEDIAN_TIME_SPAN - 1] = 1;
+        assert_eq!(state.next_target(), target_from_bits(bits_from_target(&(&state.target / 4))));
+    }
+}
@@ -0,0 +1,147 @@
+//! ECDSA signatures over secp256k1
+
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Zero;
+
+use super::secp256k1::{n, AllocatedPoint, Point};
+use crate::mp::field::{mod_inverse, FieldElement, FieldParams};
+use crate::util::pack::{self, BitOrder};
+use crate::OptionExt;
+
+/// Signs the message hash `z` with the secret key `sk` and the nonce `k`, giving `(r, s)`.
+/// The signature is normalized to a low `s`, as Bitcoin requires.
+pub fn sign(sk: &BigInt, z: &[u8; 32], k: &BigInt) -> (BigInt, BigInt) {
+    let n = n();
+    assert!(sk > &BigInt::zero() && sk < &n, "invalid secret key");
+    let r = match Point::generator().mul(k).coordinates() {
+        Some((x, _)) => x.mod_floor(&n),
+        None => panic!("invalid nonce"),
+    };
+    assert!(!r.is_zero(), "invalid nonce");
+    let k_inv = mod_inverse(k, &n).unwrap();
+    let s = (k_inv * (BigInt::from_bytes_be(Sign::Plus, z) + &r * sk)).mod_floor(&n);
+    assert!(!s.is_zero(), "invalid nonce");
+    let s = if s > &n >> 1 { &n - s } else { s };
+    (r, s)
+}
+
+/// Verifies the signature `(r, s)` of the message hash `z` by the public key `public_key`.
+pub fn verify(public_key: &Point, z: &[u8; 32], (r, s): (&BigInt, &BigInt)) -> bool {
+    let n = n();
+    if r.is_zero() || s.is_zero() || r >= &n || s >= &n || !public_key.is_on_curve() {
+        return false;
+    }
+    let s_inv = mod_inverse(s, &n).unwrap();
+    let u1 = (BigInt::from_bytes_be(Sign::Plus, z) * &s_inv).mod_floor(&n);
+    let u2 = (r * &s_inv).mod_floor(&n);
+    match Point::generator().mul(&u1).add(&public_key.mul(&u2)) {
+        Point::Affine(x, _) => &x.mod_floor(&n) == r,
+        Point::Infinity => false,
+    }
+}
+
+/// Constrains `signature` to be a valid signature `(r, s)` of the message hash with the 256
+/// bits `z` (most significant bit first) by `public_key`.
+///
+/// The signature is a witness. Any `s` verifies, high or low.
+/// Keys equal to the generator or its negation cannot be used.
+pub fn verify_signature<Scalar, CS>(
+    mut cs: CS,
+    public_key: &AllocatedPoint<Scalar>,
+    z: &[Boolean],
+    signature: Option<(&BigInt, &BigInt)>,
+) -> Result<(), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    assert_eq!(z.len(), 256, "the message hash must be 32 bytes");
+    let params = FieldParams::secp256k1_scalar();
+    let r = FieldElement::alloc(
+        cs.namespace(|| "r"),
+        || Ok(signature.grab()?.0.clone()),
+        &params,
+    )?;
+    let s = FieldElement::alloc(
+        cs.namespace(|| "s"),
+        || Ok(signature.grab()?.1.clone()),
+        &params,
+    )?;
+    // r = 0 would otherwise be accepted when R.x = n
+    r.inverse(cs.namespace(|| "r is not zero"))?;
+    let z = FieldElement {
+        nat: pack::pack_bignat::<Scalar, CS>(z, BitOrder::MsbFirst, params.limb_width),
+        params: params.clone(),
+    };
+
+    // R = (z / s) G + (r / s) Q
+    let s_inv = s.inverse(cs.namespace(|| "1 / s"))?;
+    let u1 = z.mul(cs.namespace(|| "z / s"), &s_inv)?;
+    let u2 = r.mul(cs.namespace(|| "r / s"), &s_inv)?;
+    let u1_bits = u1.to_bits(cs.namespace(|| "u1 bits"), BitOrder::LsbFirst)?;
+    let u2_bits = u2.to_bits(cs.namespace(|| "u2 bits"), BitOrder::LsbFirst)?;
+    let generator = AllocatedPoint::constant::<CS>(&Point::generator())?;
+    let big_r = AllocatedPoint::double_scalar_mul(
+        cs.namespace(|| "u1 G + u2 Q"),
+        &u1_bits,
+        &generator,
+        &u2_bits,
+        public_key,
+    )?;
+
+    // R.x < p < 2n, so its canonical representative is also an unreduced element mod n
+    let x = big_r.x.reduce(cs.namespace(|| "reduce R.x"))?;
+    let x = FieldElement { nat: x.nat, params };
+    x.equal(cs.namespace(|| "R.x = r (mod n)"), &r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::schnorr::tagged_hash;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::CheckingConstraintSystem;
+
+    #[test]
+    fn test_native_ecdsa() {
+        let sk = BigInt::from(0xc0ffee);
+        let public_key = Point::generator().mul(&sk);
+        let z = tagged_hash("test", b"ecdsa");
+        let (r, s) = sign(&sk, &z, &BigInt::from(0x5eed));
+        assert!(verify(&public_key, &z, (&r, &s)));
+        // both s and -s verify
+        assert!(verify(&public_key, &z, (&r, &(n() - &s))));
+        assert!(!verify(
+            &public_key,
+            &tagged_hash("test", b"other"),
+            (&r, &s)
+        ));
+        assert!(!verify(&public_key.neg(), &z, (&r, &s)));
+        assert!(!verify(&public_key, &z, (&BigInt::zero(), &s)));
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let sk = BigInt::from(0xc0ffee);
+        let public_key = Point::generator().mul(&sk);
+        let z = tagged_hash("test", b"ecdsa");
+        let (r, s) = sign(&sk, &z, &BigInt::from(0x5eed));
+
+        let mut cs = CheckingConstraintSystem::<Fr>::new();
+        let key = AllocatedPoint::alloc(cs.namespace(|| "key"), Some(&public_key)).unwrap();
+        let z_bits: Vec<Boolean> = z
+            .iter()
+            .flat_map(|b| {
+                (0..8)
+                    .rev()
+                    .map(move |i| Boolean::constant((b >> i) & 1 == 1))
+            })
+            .collect();
+        verify_signature(cs.namespace(|| "verify"), &key, &z_bits, Some((&r, &s))).unwrap();
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+    }
+}
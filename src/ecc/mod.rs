@@ -0,0 +1,3 @@
+pub mod ecdsa;
+pub mod schnorr;
+pub mod secp256k1;
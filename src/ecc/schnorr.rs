@@ -0,0 +1,245 @@
+//! BIP-340 Schnorr signatures over secp256k1
+
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use sha2::{Digest, Sha256};
+
+use super::secp256k1::{n, p, AllocatedPoint, Point};
+use crate::mp::field::{FieldElement, FieldParams};
+use crate::util::pack::BitOrder;
+use crate::OptionExt;
+
+/// `SHA256(SHA256(tag) || SHA256(tag) || msg)`
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(msg);
+    hasher.finalize().into()
+}
+
+fn int(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+fn bytes(n: &BigInt) -> [u8; 32] {
+    let (_, be) = n.to_bytes_be();
+    let mut out = [0u8; 32];
+    out[32 - be.len()..].copy_from_slice(&be);
+    out
+}
+
+/// The x-only public key of the secret key `sk`
+pub fn public_key(sk: &BigInt) -> [u8; 32] {
+    bytes(Point::generator().mul(sk).coordinates().unwrap().0)
+}
+
+/// Signs `msg` with the secret key `sk` and the auxiliary randomness `aux`.
+pub fn sign(sk: &BigInt, msg: &[u8; 32], aux: &[u8; 32]) -> [u8; 64] {
+    let n = n();
+    assert!(sk > &BigInt::from(0) && sk < &n, "invalid secret key");
+    let public = Point::generator().mul(sk);
+    let (px, py) = public.coordinates().unwrap();
+    let d = if py.is_even() { sk.clone() } else { &n - sk };
+    let t = bytes(&d)
+        .iter()
+        .zip(tagged_hash("BIP0340/aux", aux))
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>();
+    let rand = tagged_hash("BIP0340/nonce", &[&t[..], &bytes(px), msg].concat());
+    let k = int(&rand).mod_floor(&n);
+    assert!(k != BigInt::from(0), "nonce is zero");
+    let r = Point::generator().mul(&k);
+    let (rx, ry) = r.coordinates().unwrap();
+    let k = if ry.is_even() { k } else { &n - k };
+    let e = challenge(&bytes(rx), &bytes(px), msg);
+    let mut sig = [0u8; 64];
+    sig[..32].copy_from_slice(&bytes(rx));
+    sig[32..].copy_from_slice(&bytes(&(k + e * d).mod_floor(&n)));
+    sig
+}
+
+fn challenge(r: &[u8; 32], px: &[u8; 32], msg: &[u8; 32]) -> BigInt {
+    int(&tagged_hash(
+        "BIP0340/challenge",
+        &[&r[..], px, msg].concat(),
+    ))
+    .mod_floor(&n())
+}
+
+/// Verifies the signature `sig` of `msg` by the x-only public key `pk`.
+pub fn verify(pk: &[u8; 32], msg: &[u8; 32], sig: &[u8; 64]) -> bool {
+    let public = match Point::lift_x(&int(pk)) {
+        Some(point) => point,
+        None => return false,
+    };
+    let (r, s) = (int(&sig[..32]), int(&sig[32..]));
+    if r >= p() || s >= n() {
+        return false;
+    }
+    let e = challenge(sig[..32].try_into().unwrap(), pk, msg);
+    match Point::generator().mul(&s).add(&public.mul(&e).neg()) {
+        Point::Affine(x, y) => y.is_even() && x == r,
+        Point::Infinity => false,
+    }
+}
+
+fn bytes_to_bits(bytes: &[u8]) -> Vec<Boolean> {
+    bytes
+        .iter()
+        .flat_map(|b| {
+            (0..8)
+                .rev()
+                .map(move |i| Boolean::constant((b >> i) & 1 == 1))
+        })
+        .collect()
+}
+
+/// Constrains `signature` to be a valid signature of the 256 bits of `message`
+/// (the bits of its bytes, each most significant bit first) by `public_key`,
+/// which must be the point with an even y-coordinate of an x-only key.
+///
+/// The signature is a witness. Keys equal to the generator or its negation cannot be used.
+pub fn verify_signature<Scalar, CS>(
+    mut cs: CS,
+    public_key: &AllocatedPoint<Scalar>,
+    message: &[Boolean],
+    signature: Option<&[u8; 64]>,
+) -> Result<(), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    assert_eq!(message.len(), 256, "the message must be 32 bytes");
+    let r = FieldElement::alloc(
+        cs.namespace(|| "r"),
+        || Ok(int(&signature.grab()?[..32])),
+        &FieldParams::secp256k1_base(),
+    )?;
+    let s = FieldElement::alloc(
+        cs.namespace(|| "s"),
+        || Ok(int(&signature.grab()?[32..])),
+        &FieldParams::secp256k1_scalar(),
+    )?;
+    let public_key_odd = public_key.is_y_odd(cs.namespace(|| "public key parity"))?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "public key has even y"),
+        &public_key_odd,
+        &Boolean::constant(false),
+    )?;
+
+    // e = SHA256(tag || tag || r || P.x || m), reduced modulo n by the scalar multiplication
+    let tag = Sha256::digest(b"BIP0340/challenge");
+    let mut preimage = bytes_to_bits(&[&tag[..], &tag[..]].concat());
+    preimage.extend(r.to_bits(cs.namespace(|| "r bits"), BitOrder::MsbFirst)?);
+    preimage.extend(
+        public_key
+            .x
+            .to_bits(cs.namespace(|| "P.x bits"), BitOrder::MsbFirst)?,
+    );
+    preimage.extend(message.iter().cloned());
+    let mut e_bits = sha256(cs.namespace(|| "challenge"), &preimage)?;
+    e_bits.reverse();
+
+    // R = s G - e P
+    let s_bits = s.to_bits(cs.namespace(|| "s bits"), BitOrder::LsbFirst)?;
+    let generator = AllocatedPoint::constant::<CS>(&Point::generator())?;
+    let neg_public_key = public_key.neg(cs.namespace(|| "-P"))?;
+    let big_r = AllocatedPoint::double_scalar_mul(
+        cs.namespace(|| "s G - e P"),
+        &s_bits,
+        &generator,
+        &e_bits,
+        &neg_public_key,
+    )?;
+    let big_r_odd = big_r.is_y_odd(cs.namespace(|| "R parity"))?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "R has even y"),
+        &big_r_odd,
+        &Boolean::constant(false),
+    )?;
+    big_r.x.equal(cs.namespace(|| "R.x = r"), &r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::CheckingConstraintSystem;
+    use num_traits::Num;
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        let v = BigInt::from_str_radix(s, 16).unwrap().to_bytes_be().1;
+        let mut out = [0u8; N];
+        out[N - v.len()..].copy_from_slice(&v);
+        out
+    }
+
+    #[test]
+    fn test_bip340_vectors() {
+        // test vectors 0 and 1 of BIP-340
+        let sk = BigInt::from(3);
+        let sig = sign(&sk, &[0; 32], &[0; 32]);
+        assert_eq!(
+            sig,
+            hex::<64>("e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0")
+        );
+        assert!(verify(&public_key(&sk), &[0; 32], &sig));
+
+        let sk = BigInt::from_str_radix(
+            "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+            16,
+        )
+        .unwrap();
+        let pk = hex::<32>("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659");
+        let msg = hex::<32>("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89");
+        let aux = hex::<32>("0000000000000000000000000000000000000000000000000000000000000001");
+        assert_eq!(public_key(&sk), pk);
+        let sig = sign(&sk, &msg, &aux);
+        assert_eq!(
+            sig,
+            hex::<64>("6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a")
+        );
+        assert!(verify(&pk, &msg, &sig));
+
+        let mut forged = sig;
+        forged[63] ^= 1;
+        assert!(!verify(&pk, &msg, &forged));
+        assert!(!verify(&pk, &[0; 32], &sig));
+    }
+
+    fn verify_in_circuit(
+        pk: &[u8; 32],
+        msg: &[u8; 32],
+        sig: &[u8; 64],
+    ) -> CheckingConstraintSystem<Fr> {
+        let mut cs = CheckingConstraintSystem::<Fr>::new();
+        let public_key = AllocatedPoint::constant::<CheckingConstraintSystem<Fr>>(
+            &Point::lift_x(&int(pk)).unwrap(),
+        )
+        .unwrap();
+        let message = bytes_to_bits(msg);
+        let result = verify_signature(cs.namespace(|| "verify"), &public_key, &message, Some(sig));
+        assert!(result.is_ok() || !cs.is_satisfied());
+        cs
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let sk = BigInt::from(0xc0ffee);
+        let pk = public_key(&sk);
+        let msg = tagged_hash("test", b"checkpoint");
+        let sig = sign(&sk, &msg, &[7; 32]);
+        let cs = verify_in_circuit(&pk, &msg, &sig);
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+
+        // the signature of another message
+        let cs = verify_in_circuit(&pk, &tagged_hash("test", b"other"), &sig);
+        assert!(!cs.is_satisfied());
+    }
+}
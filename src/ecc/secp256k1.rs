@@ -0,0 +1,485 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Num as BigNum, One, Zero};
+
+use crate::mp::field::{mod_inverse, FieldElement, FieldParams};
+use crate::util::pack::BitOrder;
+use crate::OptionExt;
+
+/// The order of the base field
+pub fn p() -> BigInt {
+    FieldParams::secp256k1_base().modulus
+}
+
+/// The order of the group
+pub fn n() -> BigInt {
+    FieldParams::secp256k1_scalar().modulus
+}
+
+/// A point of secp256k1, `y^2 = x^3 + 7`, computed natively
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Point {
+    Infinity,
+    Affine(BigInt, BigInt),
+}
+
+impl Point {
+    pub fn generator() -> Self {
+        let x = BigInt::from_str_radix(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            16,
+        )
+        .unwrap();
+        let y = BigInt::from_str_radix(
+            "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+            16,
+        )
+        .unwrap();
+        Point::Affine(x, y)
+    }
+
+    /// The point with x-coordinate `x` and an even y-coordinate, if there is one
+    pub fn lift_x(x: &BigInt) -> Option<Self> {
+        let p = p();
+        if x >= &p || x.sign() == num_bigint::Sign::Minus {
+            return None;
+        }
+        let c: BigInt = (x.modpow(&BigInt::from(3), &p) + 7) % &p;
+        // p = 3 mod 4
+        let y = c.modpow(&((&p + 1) / 4), &p);
+        if (&y * &y) % &p != c {
+            return None;
+        }
+        Some(Point::Affine(
+            x.clone(),
+            if y.is_even() { y } else { &p - y },
+        ))
+    }
+
+    pub fn is_on_curve(&self) -> bool {
+        match self {
+            Point::Infinity => true,
+            Point::Affine(x, y) => {
+                let p = p();
+                (y * y - x * x * x - BigInt::from(7))
+                    .mod_floor(&p)
+                    .is_zero()
+            }
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        match self {
+            Point::Infinity => Point::Infinity,
+            Point::Affine(x, y) => Point::Affine(x.clone(), (-y).mod_floor(&p())),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let p = p();
+        match (self, other) {
+            (Point::Infinity, q) | (q, Point::Infinity) => q.clone(),
+            (Point::Affine(x1, y1), Point::Affine(x2, y2)) => {
+                let lambda = if x1 == x2 {
+                    if (y1 + y2).mod_floor(&p).is_zero() {
+                        return Point::Infinity;
+                    }
+                    3 * x1 * x1 * mod_inverse(&(2 * y1), &p).unwrap()
+                } else {
+                    (y2 - y1) * mod_inverse(&(x2 - x1), &p).unwrap()
+                };
+                let x3 = (&lambda * &lambda - x1 - x2).mod_floor(&p);
+                let y3 = (lambda * (x1 - &x3) - y1).mod_floor(&p);
+                Point::Affine(x3, y3)
+            }
+        }
+    }
+
+    pub fn double(&self) -> Self {
+        self.add(self)
+    }
+
+    pub fn mul(&self, k: &BigInt) -> Self {
+        let mut acc = Point::Infinity;
+        for i in (0..k.bits()).rev() {
+            acc = acc.double();
+            if k.bit(i) {
+                acc = acc.add(self);
+            }
+        }
+        acc
+    }
+
+    /// The coordinates of an affine point
+    pub fn coordinates(&self) -> Option<(&BigInt, &BigInt)> {
+        match self {
+            Point::Infinity => None,
+            Point::Affine(x, y) => Some((x, y)),
+        }
+    }
+}
+
+/// The offset from which `AllocatedPoint::double_scalar_mul` starts accumulating, so that
+/// it never meets the point at infinity: the nothing-up-my-sleeve point `H` of BIP-341.
+fn offset() -> Point {
+    let x = BigInt::from_str_radix(
+        "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0",
+        16,
+    )
+    .unwrap();
+    Point::lift_x(&x).unwrap()
+}
+
+/// An affine point of secp256k1 with coordinates in the non-native base field.
+/// The point at infinity has no representation.
+#[derive(Clone)]
+pub struct AllocatedPoint<Scalar: PrimeField> {
+    pub x: FieldElement<Scalar>,
+    pub y: FieldElement<Scalar>,
+}
+
+impl<Scalar: PrimeField> AllocatedPoint<Scalar> {
+    /// Allocates an affine point, constrained to be on the curve.
+    pub fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Point>,
+    ) -> Result<Self, SynthesisError> {
+        let params = FieldParams::secp256k1_base();
+        let coordinates = || {
+            value
+                .grab()?
+                .coordinates()
+                .ok_or(SynthesisError::Unsatisfiable)
+        };
+        let x = FieldElement::alloc(
+            cs.namespace(|| "x"),
+            || Ok(coordinates()?.0.clone()),
+            &params,
+        )?;
+        let y = FieldElement::alloc(
+            cs.namespace(|| "y"),
+            || Ok(coordinates()?.1.clone()),
+            &params,
+        )?;
+        let point = AllocatedPoint { x, y };
+        point.enforce_on_curve(cs.namespace(|| "on curve"))?;
+        Ok(point)
+    }
+
+    /// The constant affine point `point`
+    pub fn constant<CS: ConstraintSystem<Scalar>>(point: &Point) -> Result<Self, SynthesisError> {
+        let params = FieldParams::secp256k1_base();
+        let (x, y) = point.coordinates().ok_or(SynthesisError::Unsatisfiable)?;
+        Ok(AllocatedPoint {
+            x: FieldElement::constant::<CS>(x, &params)?,
+            y: FieldElement::constant::<CS>(y, &params)?,
+        })
+    }
+
+    pub fn value(&self) -> Option<Point> {
+        Some(Point::Affine(self.x.value()?, self.y.value()?))
+    }
+
+    /// Constrains `y^2 = x^3 + 7`.
+    pub fn enforce_on_curve<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let seven = FieldElement::constant::<CS>(&BigInt::from(7), &self.x.params)?;
+        let y2 = self.y.mul(cs.namespace(|| "y^2"), &self.y)?;
+        let x2 = self.x.mul(cs.namespace(|| "x^2"), &self.x)?;
+        let x3 = x2.mul(cs.namespace(|| "x^3"), &self.x)?;
+        let rhs = x3.add(cs.namespace(|| "x^3 + 7"), &seven)?;
+        y2.equal(cs.namespace(|| "y^2 = x^3 + 7"), &rhs)
+    }
+
+    pub fn neg<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        Ok(AllocatedPoint {
+            x: self.x.clone(),
+            y: self.y.neg(cs.namespace(|| "-y"))?,
+        })
+    }
+
+    /// Computes `self + other`, for points with distinct x-coordinates.
+    /// Equal x-coordinates are unsatisfiable.
+    pub fn add_incomplete<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let dy = other.y.sub(cs.namespace(|| "y2 - y1"), &self.y)?;
+        let dx = other.x.sub(cs.namespace(|| "x2 - x1"), &self.x)?;
+        let lambda = dy.div(cs.namespace(|| "lambda"), &dx)?;
+        self.third_point(cs.namespace(|| "sum"), &lambda, &other.x)
+    }
+
+    /// Computes `2 * self`.
+    pub fn double<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let params = &self.x.params;
+        let x2 = self.x.mul(cs.namespace(|| "x^2"), &self.x)?;
+        let three_x2 = x2
+            .add(cs.namespace(|| "2x^2"), &x2)?
+            .add(cs.namespace(|| "3x^2"), &x2)?;
+        let two_y = self.y.add(cs.namespace(|| "2y"), &self.y)?;
+        // secp256k1 has no point of order 2, so y is never zero and the slope is unique
+        let lambda = FieldElement::alloc_unreduced(
+            cs.namespace(|| "lambda"),
+            || {
+                let inverse = mod_inverse(two_y.value().grab()?, &params.modulus)
+                    .ok_or(SynthesisError::DivisionByZero)?;
+                Ok((three_x2.value().grab()? * inverse).mod_floor(&params.modulus))
+            },
+            params,
+        )?;
+        FieldElement::enforce_product(
+            cs.namespace(|| "lambda 2y = 3x^2"),
+            &lambda,
+            &two_y,
+            &three_x2,
+        )?;
+        self.third_point(cs.namespace(|| "double"), &lambda, &self.x)
+    }
+
+    // The reflection of the third point on the line of slope `lambda` through `self` and
+    // a point of x-coordinate `other_x`
+    fn third_point<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        lambda: &FieldElement<Scalar>,
+        other_x: &FieldElement<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        let params = &self.x.params;
+        let p = &params.modulus;
+        // x3 = lambda^2 - x1 - x2
+        let x = FieldElement::alloc_unreduced(
+            cs.namespace(|| "x3"),
+            || {
+                let l = lambda.value().grab()?.clone();
+                Ok((&l * &l - self.x.value().grab()? - other_x.value().grab()?).mod_floor(p))
+            },
+            params,
+        )?;
+        let xs = x
+            .add(cs.namespace(|| "x3 + x1"), &self.x)?
+            .add(cs.namespace(|| "x3 + x1 + x2"), other_x)?;
+        FieldElement::enforce_product(
+            cs.namespace(|| "lambda^2 = x1 + x2 + x3"),
+            lambda,
+            lambda,
+            &xs,
+        )?;
+        // y3 = lambda (x1 - x3) - y1
+        let dx = self.x.sub(cs.namespace(|| "x1 - x3"), &x)?;
+        let y = FieldElement::alloc_unreduced(
+            cs.namespace(|| "y3"),
+            || {
+                Ok(
+                    (lambda.value().grab()? * dx.value().grab()? - self.y.value().grab()?)
+                        .mod_floor(p),
+                )
+            },
+            params,
+        )?;
+        let ys = y.add(cs.namespace(|| "y3 + y1"), &self.y)?;
+        FieldElement::enforce_product(
+            cs.namespace(|| "lambda (x1 - x3) = y3 + y1"),
+            lambda,
+            &dx,
+            &ys,
+        )?;
+        Ok(AllocatedPoint { x, y })
+    }
+
+    /// Returns `a` if `condition` is true, otherwise `b`.
+    pub fn conditional_select<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        condition: &Boolean,
+    ) -> Result<Self, SynthesisError> {
+        Ok(AllocatedPoint {
+            x: FieldElement::conditional_select(cs.namespace(|| "x"), &a.x, &b.x, condition)?,
+            y: FieldElement::conditional_select(cs.namespace(|| "y"), &a.y, &b.y, condition)?,
+        })
+    }
+
+    /// Computes `a * p + b * q` with a joint double-and-add over the bits of `a` and `b`,
+    /// given least significant bit first.
+    ///
+    /// The accumulator starts from a fixed offset point whose multiple is subtracted at the end,
+    /// so the incomplete addition only fails when the result is the point at infinity, or with
+    /// negligible probability. Neither case is satisfiable, so soundness is not affected.
+    pub fn double_scalar_mul<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &[Boolean],
+        p: &Self,
+        b: &[Boolean],
+        q: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let n_bits = std::cmp::max(a.len(), b.len());
+        let bit =
+            |bits: &[Boolean], i: usize| bits.get(i).cloned().unwrap_or(Boolean::constant(false));
+        let p_plus_q = p.add_incomplete(cs.namespace(|| "p + q"), q)?;
+        let offset = offset();
+        let mut acc = AllocatedPoint::constant::<CS>(&offset)?;
+        for i in (0..n_bits).rev() {
+            let mut cs = cs.namespace(|| format!("bit {}", i));
+            let (a_i, b_i) = (bit(a, i), bit(b, i));
+            acc = acc.double(cs.namespace(|| "double"))?;
+            // a_i ? (b_i ? p + q : p) : q
+            let p_or_sum = AllocatedPoint::conditional_select(
+                cs.namespace(|| "p or p + q"),
+                &p_plus_q,
+                p,
+                &b_i,
+            )?;
+            let term =
+                AllocatedPoint::conditional_select(cs.namespace(|| "term"), &p_or_sum, q, &a_i)?;
+            let sum = acc.add_incomplete(cs.namespace(|| "add"), &term)?;
+            let neither = Boolean::and(cs.namespace(|| "neither"), &a_i.not(), &b_i.not())?;
+            acc = AllocatedPoint::conditional_select(
+                cs.namespace(|| "select"),
+                &acc,
+                &sum,
+                &neither,
+            )?;
+        }
+        let correction = offset.mul(&(BigInt::one() << n_bits)).neg();
+        acc.add_incomplete(
+            cs.namespace(|| "remove offset"),
+            &AllocatedPoint::constant::<CS>(&correction)?,
+        )
+    }
+
+    /// Whether the y-coordinate is odd
+    pub fn is_y_odd<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Boolean, SynthesisError> {
+        Ok(self
+            .y
+            .to_bits(cs.namespace(|| "y bits"), BitOrder::LsbFirst)?[0]
+            .clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+
+    fn alloc_bits(
+        cs: &mut TestConstraintSystem<Fr>,
+        name: &str,
+        k: u64,
+        n_bits: usize,
+    ) -> Vec<Boolean> {
+        (0..n_bits)
+            .map(|i| {
+                Boolean::from(
+                    bellpepper::gadgets::boolean::AllocatedBit::alloc(
+                        cs.namespace(|| format!("{} {}", name, i)),
+                        Some((k >> i) & 1 == 1),
+                    )
+                    .unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_native_group() {
+        let g = Point::generator();
+        assert!(g.is_on_curve());
+        assert_eq!(g.mul(&n()), Point::Infinity);
+        assert_eq!(g.mul(&(n() - 1)), g.neg());
+        assert_eq!(g.double().add(&g), g.mul(&BigInt::from(3)));
+        // the x-only public key of the secret key 3 in the BIP-340 test vectors
+        let x = BigInt::from_str_radix(
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            16,
+        )
+        .unwrap();
+        assert_eq!(g.mul(&BigInt::from(3)).coordinates().unwrap().0, &x);
+        assert!(offset().is_on_curve());
+    }
+
+    #[test]
+    fn test_point_ops() {
+        let g = Point::generator();
+        let h = g.mul(&BigInt::from(5));
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let g_var = AllocatedPoint::alloc(cs.namespace(|| "g"), Some(&g)).unwrap();
+        let h_var = AllocatedPoint::alloc(cs.namespace(|| "h"), Some(&h)).unwrap();
+        let sum = g_var
+            .add_incomplete(cs.namespace(|| "g + h"), &h_var)
+            .unwrap();
+        let double = h_var.double(cs.namespace(|| "2h")).unwrap();
+        let neg = g_var.neg(cs.namespace(|| "-g")).unwrap();
+        assert_eq!(sum.value(), Some(g.mul(&BigInt::from(6))));
+        assert_eq!(double.value(), Some(g.mul(&BigInt::from(10))));
+        assert_eq!(neg.value(), Some(g.neg()));
+        sum.enforce_on_curve(cs.namespace(|| "sum on curve"))
+            .unwrap();
+        double
+            .enforce_on_curve(cs.namespace(|| "double on curve"))
+            .unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_point_not_on_curve() {
+        let g = Point::generator();
+        let (x, y) = g.coordinates().unwrap();
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        AllocatedPoint::alloc(cs.namespace(|| "p"), Some(&Point::Affine(x.clone(), y + 1)))
+            .unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_add_equal_x() {
+        let g = Point::generator();
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let g_var = AllocatedPoint::alloc(cs.namespace(|| "g"), Some(&g)).unwrap();
+        let neg = g_var.neg(cs.namespace(|| "-g")).unwrap();
+        assert!(g_var
+            .add_incomplete(cs.namespace(|| "g - g"), &neg)
+            .is_err());
+    }
+
+    #[test]
+    fn test_double_scalar_mul() {
+        let g = Point::generator();
+        let q = g.mul(&BigInt::from(1000));
+        let (a, b) = (0b1011_0110u64, 0b0101_1101u64);
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a_bits = alloc_bits(&mut cs, "a", a, 8);
+        let b_bits = alloc_bits(&mut cs, "b", b, 8);
+        let g_var = AllocatedPoint::alloc(cs.namespace(|| "g"), Some(&g)).unwrap();
+        let q_var = AllocatedPoint::alloc(cs.namespace(|| "q"), Some(&q)).unwrap();
+        let r = AllocatedPoint::double_scalar_mul(
+            cs.namespace(|| "a g + b q"),
+            &a_bits,
+            &g_var,
+            &b_bits,
+            &q_var,
+        )
+        .unwrap();
+        let expected = g.mul(&BigInt::from(a + 1000 * b));
+        assert_eq!(r.value(), Some(expected.clone()));
+        let expected_var =
+            AllocatedPoint::alloc(cs.namespace(|| "expected"), Some(&expected)).unwrap();
+        r.x.equal(cs.namespace(|| "x"), &expected_var.x).unwrap();
+        r.y.equal(cs.namespace(|| "y"), &expected_var.y).unwrap();
+        assert!(cs.is_satisfied());
+
+        // a different multiple of q cannot be claimed
+        cs.set("b 0/boolean", Fr::from(0u64));
+        assert!(!cs.is_satisfied());
+    }
+}
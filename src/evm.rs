@@ -0,0 +1,231 @@
+//! Calldata and a bridge contract for header chain proofs on EVM chains.
+//!
+//! The contract emitted by `bridge_contract` takes the tip hash and chainwork from the last
+//! state of a proof of the `BlockHeader` chain, once a verifier contract for compressed Nova
+//! proofs on BN254/Grumpkin (see `verifier::Bn256Grumpkin`) accepts the proof. That verifier is
+//! given to the contract at deployment through the `INovaVerifier` interface.
+//!
+//! This module does not generate the verifier contract: nova-snark has no Solidity backend for
+//! its compressed SNARKs, and a hand-written one for the Spartan and HyperKZG checks is a
+//! project of its own. Neither contract is compiled or executed by the tests, which only check
+//! the calldata encoding and the claim decoded from a state. Deploying the bridge therefore
+//! needs a verifier implementing `INovaVerifier` from elsewhere.
+//!
+//! For the same reason the encoding of the `proof` bytes in the calldata is still undefined. It
+//! is whatever that verifier expects, and this crate has no encoding of a `HeaderChainProof`
+//! for it.
+
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+pub use crate::btc_validation::witness::STATE_LEN;
+use crate::util::convert::f_to_nat;
+
+/// Index of the tip hash in the state
+pub const TIP_HASH: usize = 0;
+
+/// Index of the chainwork in the state
+pub const CHAIN_WORK: usize = 15;
+
+/// Index of the number of blocks appended to the block hash MMR in the state
+pub const BLOCK_COUNT: usize = 17;
+
+pub const SUBMIT_TIP_SIGNATURE: &str = "submitTip(uint256[18],uint256[18],uint256,bytes)";
+
+/// First four bytes of the Keccak-256 hash of `SUBMIT_TIP_SIGNATURE`
+pub const SUBMIT_TIP_SELECTOR: [u8; 4] = [0x2b, 0x95, 0x96, 0xea];
+
+/// Size of an ABI word in bytes
+const WORD: usize = 32;
+
+/// `x` as an ABI word, a big-endian `uint256`
+pub fn abi_word<F: PrimeField>(x: &F) -> [u8; WORD] {
+    nat_word(&f_to_nat(x))
+}
+
+fn nat_word(n: &BigInt) -> [u8; WORD] {
+    let (_, bytes) = n.to_bytes_be();
+    assert!(bytes.len() <= WORD, "{} does not fit in a word", n);
+    let mut word = [0u8; WORD];
+    word[WORD - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+/// The chain tip claimed by the last state of a proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TipClaim {
+    /// Hash of the tip, as displayed by Bitcoin clients. The state packs the digest bytes as a
+    /// little-endian number, so this is that number as a big-endian word.
+    pub tip_hash: [u8; WORD],
+    /// Work of the chain, the expected number of hashes as Bitcoin Core counts it
+    pub chain_work: BigInt,
+    /// Number of blocks proven since the state the block hash MMR was empty in
+    pub block_count: u64,
+}
+
+impl TipClaim {
+    pub fn from_state<F: PrimeField>(z: &[F]) -> Self {
+        assert_eq!(z.len(), STATE_LEN);
+        let block_count = f_to_nat(&z[BLOCK_COUNT]);
+        Self {
+            tip_hash: abi_word(&z[TIP_HASH]),
+            chain_work: f_to_nat(&z[CHAIN_WORK]),
+            block_count: u64::try_from(block_count).expect("block count does not fit in 64 bits"),
+        }
+    }
+}
+
+/// Calldata of `submitTip(z0, z_out, num_steps, proof)` for a proof of `num_steps` headers
+/// from the state `z0` to `z_out`. `proof` is passed to the verifier as it is.
+pub fn submit_tip_calldata<F: PrimeField>(
+    z0: &[F],
+    z_out: &[F],
+    num_steps: usize,
+    proof: &[u8],
+) -> Vec<u8> {
+    assert_eq!(z0.len(), STATE_LEN);
+    assert_eq!(z_out.len(), STATE_LEN);
+
+    let mut calldata = SUBMIT_TIP_SELECTOR.to_vec();
+    for x in z0.iter().chain(z_out) {
+        calldata.extend(abi_word(x));
+    }
+    calldata.extend(nat_word(&BigInt::from(num_steps)));
+
+    // The head of the dynamic `bytes` argument is the offset of its length word
+    let head_len = (2 * STATE_LEN + 2) * WORD;
+    calldata.extend(nat_word(&BigInt::from(head_len)));
+    calldata.extend(nat_word(&BigInt::from(proof.len())));
+    calldata.extend(proof);
+    calldata.resize(calldata.len() + (WORD - proof.len() % WORD) % WORD, 0);
+    calldata
+}
+
+/// Solidity source of a contract which keeps the tip with the most chainwork among the proofs
+/// from the state `z0`, and of the `INovaVerifier` interface it calls
+pub fn bridge_contract<F: PrimeField>(z0: &[F]) -> String {
+    assert_eq!(z0.len(), STATE_LEN);
+    let initial_state = z0
+        .iter()
+        .enumerate()
+        .map(|(i, x)| format!("        z[{}] = 0x{};", i, hex(&abi_word(x))))
+        .collect::<Vec<_>>()
+        .join("\n");
+    BRIDGE_CONTRACT
+        .replace("{STATE_LEN}", &STATE_LEN.to_string())
+        .replace("{TIP_HASH}", &TIP_HASH.to_string())
+        .replace("{CHAIN_WORK}", &CHAIN_WORK.to_string())
+        .replace("{INITIAL_STATE}", &initial_state)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+const BRIDGE_CONTRACT: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// Verifier of compressed Nova proofs of the block header step circuit
+interface INovaVerifier {
+    function verify(
+        uint256[{STATE_LEN}] calldata z0,
+        uint256[{STATE_LEN}] calldata zOut,
+        uint256 numSteps,
+        bytes calldata proof
+    ) external view returns (bool);
+}
+
+/// Bitcoin chain tip with the most chainwork among the proofs from a fixed initial state
+contract BtcHeaderChainBridge {
+    uint256 internal constant TIP_HASH = {TIP_HASH};
+    uint256 internal constant CHAIN_WORK = {CHAIN_WORK};
+
+    INovaVerifier public immutable verifier;
+    bytes32 public immutable initialStateHash;
+
+    /// Hash of the tip, as displayed by Bitcoin clients
+    bytes32 public tipHash;
+    uint256 public chainWork;
+
+    event TipUpdated(bytes32 tipHash, uint256 chainWork);
+
+    constructor(INovaVerifier verifier_) {
+        verifier = verifier_;
+        initialStateHash = keccak256(abi.encode(initialState()));
+    }
+
+    function initialState() public pure returns (uint256[{STATE_LEN}] memory z) {
+{INITIAL_STATE}
+    }
+
+    function submitTip(
+        uint256[{STATE_LEN}] calldata z0,
+        uint256[{STATE_LEN}] calldata zOut,
+        uint256 numSteps,
+        bytes calldata proof
+    ) external {
+        require(keccak256(abi.encode(z0)) == initialStateHash, "unknown initial state");
+        require(zOut[CHAIN_WORK] > chainWork, "not more chainwork");
+        require(verifier.verify(z0, zOut, numSteps, proof), "invalid proof");
+        tipHash = bytes32(zOut[TIP_HASH]);
+        chainWork = zOut[CHAIN_WORK];
+        emit TipUpdated(tipHash, chainWork);
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::header_step::BlockHeader;
+    use crate::btc_validation::witness::HeaderChainState;
+    use crate::evm::*;
+    use crate::util::scalar::Fr;
+
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+
+    #[test]
+    fn test_tip_claim() {
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        BlockHeader::new_blocks_from(&mut state, vec![GENESIS]).unwrap();
+        let claim = TipClaim::from_state(&state.z());
+        assert_eq!(hex(&claim.tip_hash), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+        // the chainwork of the genesis block reported by Bitcoin Core
+        assert_eq!(claim.chain_work, BigInt::from(0x100010001u64));
+        assert_eq!(claim.block_count, 1);
+    }
+
+    #[test]
+    fn test_submit_tip_calldata() {
+        let z0 = (0..STATE_LEN as u64).map(Fr::from).collect::<Vec<_>>();
+        let z_out = (0..STATE_LEN as u64).map(|i| -Fr::from(i + 1)).collect::<Vec<_>>();
+        let proof = [0xab; 33];
+        let calldata = submit_tip_calldata(&z0, &z_out, 7, &proof);
+
+        assert_eq!(calldata[..4], SUBMIT_TIP_SELECTOR);
+        let words = calldata[4..].chunks(WORD).collect::<Vec<_>>();
+        assert_eq!(words.len(), 2 * STATE_LEN + 3 + 2);
+        assert_eq!(words[1], abi_word(&Fr::from(1)));
+        assert_eq!(words[STATE_LEN], abi_word(&-Fr::from(1)));
+        assert_eq!(words[2 * STATE_LEN], nat_word(&BigInt::from(7)));
+        // the offset points to the length word, which is followed by the padded proof
+        assert_eq!(words[2 * STATE_LEN + 1], nat_word(&BigInt::from((2 * STATE_LEN + 2) * WORD)));
+        assert_eq!(words[2 * STATE_LEN + 2], nat_word(&BigInt::from(33)));
+        assert_eq!(words[2 * STATE_LEN + 3], [0xab; 32]);
+        assert_eq!(words[2 * STATE_LEN + 4][..2], [0xab, 0]);
+        assert!(words[2 * STATE_LEN + 4][1..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_bridge_contract() {
+        let z0 = HeaderChainState::<Fr>::before_genesis().z();
+        let contract = bridge_contract(&z0);
+        assert!(!contract.contains("{STATE_LEN}") && !contract.contains("{INITIAL_STATE}"));
+        assert!(contract.contains("uint256[18] calldata zOut"));
+        assert!(contract.contains(&format!("z[11] = 0x{};", hex(&abi_word(&Fr::from(4 * 14 * 24 * 60 * 60))))));
+        assert_eq!(contract.matches("        z[").count(), STATE_LEN);
+
+        // the contract function is the one of `SUBMIT_TIP_SIGNATURE`
+        assert_eq!(SUBMIT_TIP_SIGNATURE, format!("submitTip(uint256[{0}],uint256[{0}],uint256,bytes)", STATE_LEN));
+        assert!(contract.contains("function submitTip(\n        uint256[18] calldata z0,\n        uint256[18] calldata zOut,\n        uint256 numSteps,\n        bytes calldata proof\n    )"));
+    }
+}
@@ -7,7 +7,13 @@ extern crate quickcheck_macros;
 #[macro_use]
 pub mod util;
 pub mod mp;
+pub mod ecc;
 pub mod btc_validation;
+#[cfg(feature = "verifier")]
+pub mod verifier;
+#[cfg(feature = "prover")]
+pub mod prover;
+pub mod evm;
 
 use bellpepper_core::SynthesisError;
 use ff::PrimeField;
@@ -0,0 +1,81 @@
+//! Command line prover and verifier of header chains from the genesis block
+//!
+//! ```text
+//! validate-btc-header prove <pallas|bn254> <headers> <bundle> <verifier key>
+//! validate-btc-header verify <pallas|bn254> <bundle> <verifier key>
+//! ```
+//!
+//! `headers` holds one hex-encoded 80-byte header per line, starting with the genesis block.
+
+use std::error::Error;
+use std::fs;
+
+use validate_btc_header::btc_validation::witness::HeaderChainState;
+use validate_btc_header::prover::HeaderChainProver;
+use validate_btc_header::verifier::{
+    verifier_key_from_bytes, verifier_key_to_bytes, Bn256Grumpkin, CurveCycle, DecodedState,
+    HeaderChainProof, PallasVesta, Scalar,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn parse_header(line: &str) -> Result<[u64; 10]> {
+    let line = line.trim();
+    if line.len() != 160 || !line.is_ascii() {
+        return Err(format!("expected 160 hex digits, got {:?}", line).into());
+    }
+    let mut words = [0u64; 10];
+    for (word, digits) in words.iter_mut().zip(line.as_bytes().chunks(16)) {
+        *word = u64::from_str_radix(std::str::from_utf8(digits)?, 16)?;
+    }
+    Ok(words)
+}
+
+fn prove<C: CurveCycle>(headers: &str, bundle: &str, vk: &str) -> Result<()> {
+    let headers = fs::read_to_string(headers)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_header)
+        .collect::<Result<Vec<_>>>()?;
+    let prover = HeaderChainProver::<C>::setup()?;
+    let proof = prover.prove(&HeaderChainState::before_genesis(), headers)?;
+    fs::write(bundle, proof.to_bytes()?)?;
+    fs::write(vk, verifier_key_to_bytes::<C>(prover.verifier_key())?)?;
+    println!("Proved {} headers", proof.num_steps);
+    Ok(())
+}
+
+fn verify<C: CurveCycle>(bundle: &str, vk: &str) -> Result<()> {
+    let proof = HeaderChainProof::<C>::from_bytes(&fs::read(bundle)?)?;
+    let vk = verifier_key_from_bytes::<C>(&fs::read(vk)?)?;
+    if proof.z0 != HeaderChainState::<Scalar<C>>::before_genesis().z() {
+        return Err("the proof does not start before the genesis block".into());
+    }
+    let state = DecodedState::decode(&proof.verify(&vk)?)?;
+    let tip_hash = state
+        .tip_hash
+        .iter()
+        .rev()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    println!("Tip {} after {} blocks", tip_hash, state.block_count);
+    println!("Chainwork {}", state.chain_work);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args[1..] {
+        ["prove", "pallas", headers, bundle, vk] => prove::<PallasVesta>(headers, bundle, vk),
+        ["prove", "bn254", headers, bundle, vk] => prove::<Bn256Grumpkin>(headers, bundle, vk),
+        ["verify", "pallas", bundle, vk] => verify::<PallasVesta>(bundle, vk),
+        ["verify", "bn254", bundle, vk] => verify::<Bn256Grumpkin>(bundle, vk),
+        _ => Err(format!(
+            "usage: {0} prove <pallas|bn254> <headers> <bundle> <verifier key>\n       \
+             {0} verify <pallas|bn254> <bundle> <verifier key>",
+            args[0]
+        )
+        .into()),
+    }
+}
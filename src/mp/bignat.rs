@@ -5,7 +5,7 @@ use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
 use ff::PrimeField;
 use num_bigint::BigInt;
 use num_integer::Integer;
-use num_traits::cast::ToPrimitive;
+use num_traits::Zero;
 
 use std::borrow::Borrow;
 use std::cmp::{max, min, Ordering};
@@ -16,6 +16,7 @@ use std::rc::Rc;
 
 use super::poly::Polynomial;
 use crate::util::bit::{Bit, Bitvector};
+use crate::util::compare;
 use crate::util::convert::{f_to_nat, nat_to_f};
 use crate::util::gadget::Gadget;
 use crate::util::lazy::LazyCell;
@@ -42,6 +43,88 @@ fn int_with_n_ones(n: usize) -> BigInt {
     m
 }
 
+/// The number of bits of the carries when carrying limbs of at most `max_word` against each other:
+/// the least `c` such that a carry below `2^c` plus `2 * max_word` still carries below `2^c`,
+/// i.e. `2 * max_word <= 2^c * (2^limb_width - 1)`.
+/// This is computed exactly, as rounding a logarithm gets it one bit short when `max_word`
+/// is just above a power of two.
+fn carry_bits(max_word: &BigInt, limb_width: usize) -> usize {
+    let twice_max_word = max_word * 2u32;
+    let max_limb = int_with_n_ones(limb_width);
+    let mut carry_bits = 0;
+    while (&max_limb << carry_bits) < twice_max_word {
+        carry_bits += 1;
+    }
+    carry_bits
+}
+
+/// Reverses the order of the bits within every byte of `bits`, converting between
+/// least significant bit first and the most significant bit first order of byte strings.
+fn swap_bit_order_in_bytes<Scalar: PrimeField>(bits: Bitvector<Scalar>) -> Bitvector<Scalar> {
+    assert_eq!(bits.bits.len() % 8, 0, "bit vector is not made of whole bytes");
+    let bits = bits.into_bits();
+    Bitvector::from_bits(
+        bits.chunks(8)
+            .flat_map(|byte| byte.iter().rev().cloned())
+            .collect(),
+    )
+}
+
+/// Shifts `bits` (least significant first) by `unit * e` positions, where `e` is the
+/// number with the little-endian bits `shift`, which must be boolean.
+/// Left shifts widen the vector so that no bit is lost.
+fn barrel_shift<Scalar, CS>(
+    mut cs: CS,
+    mut bits: Bitvector<Scalar>,
+    shift: &Bitvector<Scalar>,
+    unit: usize,
+    left: bool,
+) -> Result<Bitvector<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    for (i, s) in shift.clone().into_bits().into_iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("stage {}", i));
+        let amount = unit << i;
+        let src = bits.into_bits();
+        let len = if left { src.len() + amount } else { src.len() };
+        let mut out = Vec::with_capacity(len);
+        for j in 0..len {
+            let kept = src.get(j);
+            let moved = if left {
+                j.checked_sub(amount).map(|k| &src[k])
+            } else {
+                src.get(j + amount)
+            };
+            if kept.is_none() && moved.is_none() {
+                out.push(Bit::new_false::<CS>());
+                continue;
+            }
+            let zero = Bit::new_false::<CS>();
+            let kept = kept.unwrap_or(&zero);
+            let moved = moved.unwrap_or(&zero);
+            let value = s
+                .value
+                .and_then(|s| if s { moved.value } else { kept.value });
+            let v = cs.alloc(
+                || format!("bit {}", j),
+                || Ok(if *value.grab()? { Scalar::ONE } else { Scalar::ZERO }),
+            )?;
+            // v = kept + s * (moved - kept)
+            cs.enforce(
+                || format!("select {}", j),
+                |lc| lc + &s.bit,
+                |lc| lc + &moved.bit - &kept.bit,
+                |lc| lc + v - &kept.bit,
+            );
+            out.push(Bit::new(LinearCombination::zero() + v, value));
+        }
+        bits = Bitvector::from_bits(out);
+    }
+    Ok(bits)
+}
+
 /// Compute the limbs encoding a natural number.
 /// The limbs are assumed to be based the `limb_width` power of 2.
 pub fn nat_to_limbs<'a, Scalar: PrimeField>(
@@ -318,30 +401,21 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
     }
 
     /// Constrain `self` to be equal to `other`, assuming that they're both properly carried.
+    /// The shorter operand is padded with zero limbs, so every limb of the longer one is compared.
     pub fn equal<CS: ConstraintSystem<Scalar>>(
         &self,
         mut cs: CS,
         other: &Self,
     ) -> Result<(), SynthesisError> {
-        if self.limbs.len() < other.limbs.len() {
-            return other.equal(cs, self);
-        }
         self.enforce_limb_width_agreement(other, "equal")?;
-        let n = other.limbs.len();
-        for i in 0..n {
-            cs.enforce(
-                || format!("equal {}", i),
-                |lc| lc,
-                |lc| lc,
-                |lc| lc + &self.limbs[i] - &other.limbs[i],
-            );
-        }
-        for i in n..(self.limbs.len()) {
+        let n_limbs = max(self.params.n_limbs, other.params.n_limbs);
+        let (a, b) = (self.with_n_limbs::<CS>(n_limbs), other.with_n_limbs::<CS>(n_limbs));
+        for i in 0..n_limbs {
             cs.enforce(
                 || format!("equal {}", i),
                 |lc| lc,
                 |lc| lc,
-                |lc| lc + &self.limbs[i],
+                |lc| lc + &a.limbs[i] - &b.limbs[i],
             );
         }
         Ok(())
@@ -497,6 +571,92 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         Ok(rolling)
     }
 
+    /// Returns a bit which is set iff `self < other`.
+    /// The limbs are compared from the least significant one upwards: `self` is less on
+    /// the limbs up to `i` iff it is less on limb `i`, or equal on limb `i` and less below.
+    /// Both numbers must be well formed, see `assert_well_formed`.
+    fn less_than_bit<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Bit<Scalar>, SynthesisError> {
+        let limb_width = self.enforce_limb_width_agreement(other, "less_than")?;
+        let n_limbs = max(self.limbs.len(), other.limbs.len());
+        let a_limbs = self.with_n_limbs::<CS>(n_limbs).as_limbs::<CS>();
+        let b_limbs = other.with_n_limbs::<CS>(n_limbs).as_limbs::<CS>();
+        let mut lt = Bit::new_false::<CS>();
+        for (i, (a, b)) in a_limbs.iter().zip(b_limbs.iter()).enumerate() {
+            let mut cs = cs.namespace(|| format!("limb {}", i));
+            let limb_lt = compare::lt(cs.namespace(|| "lt"), a, b, limb_width)?;
+
+            // limb_eq = (a == b), via the inverse of a - b
+            let diff = a.value.and_then(|a| b.value.map(|b| a - b));
+            let limb_eq_value = diff.map(|d| bool::from(d.is_zero()));
+            let limb_eq = cs.alloc(
+                || "eq",
+                || Ok(if *limb_eq_value.grab()? { Scalar::ONE } else { Scalar::ZERO }),
+            )?;
+            let inverse = cs.alloc(
+                || "inverse",
+                || Ok(Option::from(diff.grab()?.invert()).unwrap_or(Scalar::ZERO)),
+            )?;
+            cs.enforce(
+                || "diff * inverse = 1 - eq",
+                |lc| lc + &a.num - &b.num,
+                |lc| lc + inverse,
+                |lc| lc + CS::one() - limb_eq,
+            );
+            cs.enforce(
+                || "diff * eq = 0",
+                |lc| lc + &a.num - &b.num,
+                |lc| lc + limb_eq,
+                |lc| lc,
+            );
+
+            // lt = limb_lt + limb_eq * lt
+            let eq_and_lt_value = limb_eq_value.and_then(|e| lt.value.map(|l| e && l));
+            let eq_and_lt = cs.alloc(
+                || "eq and lt",
+                || Ok(if *eq_and_lt_value.grab()? { Scalar::ONE } else { Scalar::ZERO }),
+            )?;
+            cs.enforce(
+                || "eq * lt",
+                |lc| lc + limb_eq,
+                |lc| lc + &lt.bit,
+                |lc| lc + eq_and_lt,
+            );
+            lt = Bit::new(
+                limb_lt.bit.clone() + eq_and_lt,
+                limb_lt.value.and_then(|l| eq_and_lt_value.map(|e| l || e)),
+            );
+        }
+        Ok(lt)
+    }
+
+    /// Returns a boolean which is `true` iff `self < other`.
+    /// Both numbers must be well formed, see `assert_well_formed`.
+    /// Costs `limb_width + 5` constraints per limb, plus one.
+    pub fn is_less_than<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        self.less_than_bit(cs.namespace(|| "lt"), other)?
+            .alloc_boolean(cs.namespace(|| "result"))
+    }
+
+    /// Constrains `self < other`.
+    /// Both numbers must be well formed, see `assert_well_formed`.
+    pub fn enforce_less_than<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.less_than_bit(cs.namespace(|| "lt"), other)?
+            .constrain_value(cs.namespace(|| "result"), true);
+        Ok(())
+    }
+
     pub fn assert_well_formed<CS: ConstraintSystem<Scalar>>(
         &self,
         mut cs: CS,
@@ -564,6 +724,78 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         nat.group_limbs(limb_width)
     }
 
+    /// The number whose little-endian byte string is given by `bits`, where the bits of
+    /// every byte are in most significant first order, as in the output of SHA-256.
+    pub fn from_le_bytes_bits(bits: &Bitvector<Scalar>, limb_width: usize) -> Self {
+        BigNat::recompose(&swap_bit_order_in_bytes(bits.clone()), limb_width)
+    }
+
+    /// The little-endian byte string of `self` as bits, each byte in most significant bit
+    /// first order. Costs a decomposition of `self`, which also enforces that it is well formed.
+    pub fn to_le_bytes_bits<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Bitvector<Scalar>, SynthesisError> {
+        let mut bits = self.decompose(cs.namespace(|| "decomp"))?;
+        while bits.bits.len() % 8 != 0 {
+            bits.push(Bit::new_false::<CS>());
+        }
+        Ok(swap_bit_order_in_bytes(bits))
+    }
+
+    /// `self * 2^shift`.
+    /// Costs a decomposition of `self`, which also enforces that it is well formed.
+    pub fn shl_const<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        shift: usize,
+    ) -> Result<Self, SynthesisError> {
+        let bits = self.decompose(cs.namespace(|| "decomp"))?;
+        Ok(BigNat::recompose(&bits.shl(shift), self.params.limb_width))
+    }
+
+    /// `self / 2^shift`, rounded down.
+    /// Costs a decomposition of `self`, which also enforces that it is well formed.
+    pub fn shr_const<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        shift: usize,
+    ) -> Result<Self, SynthesisError> {
+        let bits = self.decompose(cs.namespace(|| "decomp"))?;
+        if shift >= bits.bits.len() {
+            return BigNat::constant::<CS>(&BigInt::zero(), self.params.limb_width, 1);
+        }
+        Ok(BigNat::recompose(&bits.shr(shift), self.params.limb_width))
+    }
+
+    /// `self * 2^(unit * e)`, where `e` is the number with the little-endian bits `shift`,
+    /// which must be boolean. The result has room for the largest possible `e`.
+    /// Costs a decomposition of `self` and one constraint per result bit for every bit of `shift`.
+    pub fn shl_var<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        shift: &Bitvector<Scalar>,
+        unit: usize,
+    ) -> Result<Self, SynthesisError> {
+        let bits = self.decompose(cs.namespace(|| "decomp"))?;
+        let bits = barrel_shift(cs.namespace(|| "shift"), bits, shift, unit, true)?;
+        Ok(BigNat::recompose(&bits, self.params.limb_width))
+    }
+
+    /// `self / 2^(unit * e)`, rounded down, where `e` is the number with the little-endian
+    /// bits `shift`, which must be boolean.
+    /// Costs a decomposition of `self` and one constraint per bit of `self` for every bit of `shift`.
+    pub fn shr_var<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        shift: &Bitvector<Scalar>,
+        unit: usize,
+    ) -> Result<Self, SynthesisError> {
+        let bits = self.decompose(cs.namespace(|| "decomp"))?;
+        let bits = barrel_shift(cs.namespace(|| "shift"), bits, shift, unit, false)?;
+        Ok(BigNat::recompose(&bits, self.params.limb_width))
+    }
+
     pub fn enforce_full_bits<CS: ConstraintSystem<Scalar>>(
         &mut self,
         mut cs: CS,
@@ -721,9 +953,15 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         let target_base = BigInt::from(1u8) << self.params.limb_width as u32;
         let mut accumulated_extra = BigInt::from(0usize);
         let max_word = std::cmp::max(&self.params.max_word, &other.params.max_word);
-        let carry_bits =
-            (((max_word.to_f64().unwrap() * 2.0).log2() - self.params.limb_width as f64).ceil()
-                + 0.1) as usize;
+        let carry_bits = carry_bits(max_word, self.params.limb_width);
+        // Every sum below is less than `2^(carry_bits + limb_width)`, so it must not wrap around
+        if carry_bits + self.params.limb_width > Scalar::CAPACITY as usize {
+            eprintln!(
+                "Limbs of {} bits outgrow the field at equal_when_carried",
+                max_word.bits()
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
         let mut carry_in = Num::new(Some(Scalar::ZERO), LinearCombination::zero());
 
         for i in 0..n {
@@ -798,10 +1036,11 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
     ) -> Result<(), SynthesisError> {
         self.enforce_limb_width_agreement(other, "equal_when_carried_regroup")?;
         let max_word = std::cmp::max(&self.params.max_word, &other.params.max_word);
-        let carry_bits =
-            (((max_word.to_f64().unwrap() * 2.0).log2() - self.params.limb_width as f64).ceil()
-                + 0.1) as usize;
-        let limbs_per_group = (Scalar::CAPACITY as usize - carry_bits) / self.params.limb_width;
+        let carry_bits = carry_bits(max_word, self.params.limb_width);
+        let limbs_per_group = max(
+            1,
+            (Scalar::CAPACITY as usize - carry_bits) / self.params.limb_width,
+        );
         let self_grouped = self.group_limbs(limbs_per_group);
         let other_grouped = other.group_limbs(limbs_per_group);
         self_grouped.equal_when_carried(cs.namespace(|| "grouped"), &other_grouped)
@@ -847,7 +1086,10 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
             *v += f_to_nat(&constant);
         }
         new.params.max_word += f_to_nat(&constant);
-        assert!(new.params.max_word <= (BigInt::from(1) << Scalar::CAPACITY));
+        assert!(
+            new.params.max_word.bits() as usize <= Self::max_word_bits_limit(),
+            "limbs outgrow the field"
+        );
         new
     }
 
@@ -865,7 +1107,10 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
             *v *= f_to_nat(&constant);
         }
         new.params.max_word *= f_to_nat(&constant);
-        assert!(new.params.max_word <= (BigInt::from(1) << Scalar::CAPACITY));
+        assert!(
+            new.params.max_word.bits() as usize <= Self::max_word_bits_limit(),
+            "limbs outgrow the field, see scale_carried"
+        );
         new
     }
 
@@ -889,6 +1134,8 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         Ok(diff)
     }
 
+    /// Compute `self * other`. Operands whose limbs are too large for the product to be carried
+    /// within the field are carried first.
     pub fn mult<CS: ConstraintSystem<Scalar>>(
         &self,
         mut cs: CS,
@@ -904,7 +1151,7 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
                 Ok(s)
             },
             other.params.limb_width,
-            other.params.n_limbs + self.params.n_limbs,
+            (self.n_bits() + other.n_bits()).saturating_sub(1) / other.params.limb_width + 1,
         )?;
         if self.params.min_bits > 0 && other.params.min_bits > 0 {
             prod.params.min_bits = self.params.min_bits + other.params.min_bits - 1;
@@ -924,6 +1171,13 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         self.enforce_limb_width_agreement(other, "add")?;
         let n_limbs = max(self.params.n_limbs, other.params.n_limbs);
         let max_word = BigInt::from(&self.params.max_word + &other.params.max_word);
+        if max_word.bits() as usize > Self::max_word_bits_limit() {
+            eprintln!(
+                "Limbs of {} bits outgrow the field at add, see add_carried",
+                max_word.bits()
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
         let limbs: Vec<LinearCombination<Scalar>> = (0..n_limbs)
             .map(|i| match (self.limbs.get(i), other.limbs.get(i)) {
                 (Some(a), Some(b)) => a.clone() + b,
@@ -965,20 +1219,161 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         })
     }
 
+    /// The largest `max_word`, in bits, that limbs may reach. Carrying two numbers with such
+    /// limbs against each other stays below the field modulus.
+    pub fn max_word_bits_limit() -> usize {
+        Scalar::CAPACITY as usize - 2
+    }
+
+    /// The number of bits by which the limbs of `self` can still grow before they must be carried
+    pub fn headroom(&self) -> usize {
+        Self::max_word_bits_limit().saturating_sub(self.params.max_word.bits() as usize)
+    }
+
+    /// Whether the limbs of `self` are known to fit in `limb_width` bits
+    pub fn is_carried(&self) -> bool {
+        self.params.max_word.bits() as usize <= self.params.limb_width
+    }
+
+    /// Compute a well-formed `BigNat` equal to `self`, with as many limbs as the largest value
+    /// of `self` needs.
+    pub fn carry<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let limb_width = self.params.limb_width;
+        let n_limbs = max(
+            self.params.n_limbs,
+            self.n_bits().saturating_sub(1) / limb_width + 1,
+        );
+        let mut carried = BigNat::alloc_from_nat(
+            cs.namespace(|| "carried"),
+            || Ok(self.value.grab()?.clone()),
+            limb_width,
+            n_limbs,
+        )?;
+        carried.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        carried.params.min_bits = self.params.min_bits;
+        self.with_n_limbs::<CS>(n_limbs)
+            .equal_when_carried_regroup(cs.namespace(|| "carry"), &carried)?;
+        Ok(carried)
+    }
+
+    /// Computes `self + other`, carrying the operands first if the limbs of the sum would
+    /// outgrow `max_word_bits_limit`.
+    pub fn add_carried<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let (a, b) = Self::make_room(cs.namespace(|| "make room"), self, other, |a, b| {
+            &a.params.max_word + &b.params.max_word
+        })?;
+        a.add::<CS>(&b)
+    }
+
+    /// Computes `self * constant`, carrying `self` first if its scaled limbs would
+    /// outgrow `max_word_bits_limit`.
+    pub fn scale_carried<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        constant: Scalar,
+    ) -> Result<Self, SynthesisError> {
+        let fits = |n: &Self| {
+            (&n.params.max_word * f_to_nat(&constant)).bits() as usize
+                <= Self::max_word_bits_limit()
+        };
+        if fits(self) {
+            return Ok(self.scale::<CS>(constant));
+        }
+        let carried = self.carry(cs.namespace(|| "carry"))?;
+        if !fits(&carried) {
+            eprintln!("Limbs outgrow the field at scale_carried, even carried");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        Ok(carried.scale::<CS>(constant))
+    }
+
+    /// The largest limb of the polynomial product of `a` and `b`
+    fn product_max_word(a: &Self, b: &Self) -> BigInt {
+        let mut x = BigInt::from(min(a.limbs.len(), b.limbs.len()));
+        x *= &a.params.max_word;
+        x *= &b.params.max_word;
+        x
+    }
+
+    // Carries `a` and `b`, the one with the largest limbs first, until the largest limb `bound`
+    // of the result of an operation on them fits in `max_word_bits_limit`.
+    fn make_room<CS, B>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        bound: B,
+    ) -> Result<(Self, Self), SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        B: Fn(&Self, &Self) -> BigInt,
+    {
+        let (mut a, mut b) = (a.clone(), b.clone());
+        while bound(&a, &b).bits() as usize > Self::max_word_bits_limit() {
+            let carry_a = match (a.is_carried(), b.is_carried()) {
+                (true, true) => {
+                    eprintln!("Limbs outgrow the field, even carried");
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+                (false, true) => true,
+                (true, false) => false,
+                (false, false) => a.params.max_word >= b.params.max_word,
+            };
+            if carry_a {
+                a = a.carry(cs.namespace(|| "carry left"))?;
+            } else {
+                b = b.carry(cs.namespace(|| "carry right"))?;
+            }
+        }
+        Ok((a, b))
+    }
+
+    /// Returns `a` if `condition` is true, otherwise `b`.
+    pub fn conditional_select<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        condition: &Boolean,
+    ) -> Result<Self, SynthesisError> {
+        let select = Bit::from_sapling::<CS>(condition.clone());
+        Gadget::mux(cs.namespace(|| "select"), &select, b, a)
+    }
+
+    /// The lesser of `self` and `other`.
+    /// Both numbers must be well formed, see `assert_well_formed`.
     pub fn min<CS: ConstraintSystem<Scalar>>(
         &self,
         mut cs: CS,
         other: &Self,
     ) -> Result<Self, SynthesisError> {
-        let select = Bit::alloc(
-            cs.namespace(|| "select"),
-            self.value
-                .as_ref()
-                .and_then(|s| other.value.as_ref().map(|o| o < s)),
-        )?;
-        let (lesser, greater) = Gadget::switch(cs.namespace(|| "switch"), &select, self, other)?;
-        let _diff = greater.sub(cs.namespace(|| "difference"), other)?;
-        Ok(lesser)
+        let lt = self.less_than_bit(cs.namespace(|| "lt"), other)?;
+        Gadget::mux(cs.namespace(|| "select"), &lt, other, self)
+    }
+
+    /// The greater of `self` and `other`.
+    /// Both numbers must be well formed, see `assert_well_formed`.
+    pub fn max<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let lt = self.less_than_bit(cs.namespace(|| "lt"), other)?;
+        Gadget::mux(cs.namespace(|| "select"), &lt, self, other)
+    }
+
+    /// `self` clamped to `[low, high]`, i.e. `min(max(self, low), high)`.
+    /// All numbers must be well formed, see `assert_well_formed`, and `low <= high`.
+    pub fn clamp<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        low: &Self,
+        high: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.max(cs.namespace(|| "low"), low)?
+            .min(cs.namespace(|| "high"), high)
     }
 
     fn verify_mult<CS: ConstraintSystem<Scalar>>(
@@ -989,19 +1384,26 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
     ) -> Result<(), SynthesisError> {
         self.enforce_limb_width_agreement(other, "verify_mult, other")?;
         self.enforce_limb_width_agreement(prod, "verify_mult, prod")?;
+        let (a, b) = Self::make_room(
+            cs.namespace(|| "make room"),
+            self,
+            other,
+            Self::product_max_word,
+        )?;
         // Verify that factor is in bounds
-        let max_word = {
-            let mut x = BigInt::from(min(self.params.n_limbs, other.params.n_limbs));
-            x *= &self.params.max_word;
-            x *= &other.params.max_word;
-            x
-        };
-        let poly_prod = Polynomial::from(self.clone()).alloc_product(
+        let max_word = Self::product_max_word(&a, &b);
+        let poly_prod = Polynomial::from(a.clone()).alloc_product(
             cs.namespace(|| "poly product"),
-            &Polynomial::from(other.clone()),
+            &Polynomial::from(b.clone()),
         )?;
-        BigNat::from_poly(poly_prod, other.params.limb_width, max_word)
-            .equal_when_carried_regroup(cs.namespace(|| "equal"), prod)?;
+        let poly_prod = BigNat::from_poly(poly_prod, b.params.limb_width, max_word);
+        let n_limbs = max(poly_prod.params.n_limbs, prod.params.n_limbs);
+        poly_prod
+            .with_n_limbs::<CS>(n_limbs)
+            .equal_when_carried_regroup(
+                cs.namespace(|| "equal"),
+                &prod.with_n_limbs::<CS>(n_limbs),
+            )?;
         Ok(())
     }
 
@@ -1015,24 +1417,30 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         self.enforce_limb_width_agreement(other, "assert_product_mod, other")?;
         self.enforce_limb_width_agreement(modulus, "assert_product_mod, modulus")?;
         self.enforce_limb_width_agreement(remainder, "assert_product_mod, remainder")?;
-        let limb_width = self.params.limb_width;
-        let quotient_limbs = self.limbs.len() + other.limbs.len();
+        let (a, b) = Self::make_room(
+            cs.namespace(|| "make room"),
+            self,
+            other,
+            Self::product_max_word,
+        )?;
+        let limb_width = a.params.limb_width;
+        let quotient_limbs = a.limbs.len() + b.limbs.len();
         let quotient = BigNat::alloc_from_nat(
             cs.namespace(|| "quotient"),
             || {
                 Ok({
-                    let mut x: BigInt = self.value.grab()?.clone();
-                    x *= *other.value().grab()?;
+                    let mut x: BigInt = a.value.grab()?.clone();
+                    x *= *b.value().grab()?;
                     x /= *modulus.value().grab()?;
                     x
                 })
             },
-            self.params.limb_width,
+            a.params.limb_width,
             quotient_limbs,
         )?;
         quotient.assert_well_formed(cs.namespace(|| "quotient rangecheck"))?;
-        let a_poly = Polynomial::from(self.clone());
-        let b_poly = Polynomial::from(other.clone());
+        let a_poly = Polynomial::from(a.clone());
+        let b_poly = Polynomial::from(b.clone());
         let mod_poly = Polynomial::from(modulus.clone());
         let q_poly = Polynomial::from(BigNat::from(quotient.clone()));
         let r_poly = Polynomial::from(BigNat::from(remainder.clone()));
@@ -1043,12 +1451,7 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         // q * m + r
         let right = Polynomial::from(right_product).sum(&r_poly);
 
-        let left_max_word = {
-            let mut x = BigInt::from(min(self.limbs.len(), other.limbs.len()));
-            x *= &self.params.max_word;
-            x *= &other.params.max_word;
-            x
-        };
+        let left_max_word = Self::product_max_word(&a, &b);
         let right_max_word = {
             let mut x = BigInt::from(std::cmp::min(quotient.limbs.len(), modulus.limbs.len()));
             x *= &quotient.params.max_word;
@@ -1071,21 +1474,26 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         modulus: &Self,
     ) -> Result<(BigNat<Scalar>, BigNat<Scalar>), SynthesisError> {
         self.enforce_limb_width_agreement(other, "mult_mod")?;
-        let limb_width = self.params.limb_width;
-        let quotient_bits =
-            (self.n_bits() + other.n_bits()).saturating_sub(modulus.params.min_bits);
+        let (a, b) = Self::make_room(
+            cs.namespace(|| "make room"),
+            self,
+            other,
+            Self::product_max_word,
+        )?;
+        let limb_width = a.params.limb_width;
+        let quotient_bits = (a.n_bits() + b.n_bits()).saturating_sub(modulus.params.min_bits);
         let quotient_limbs = quotient_bits.saturating_sub(1) / limb_width + 1;
         let quotient = BigNat::alloc_from_nat(
             cs.namespace(|| "quotient"),
             || {
                 Ok({
-                    let mut x = self.value.grab()?.clone();
-                    x *= other.value.grab()?;
+                    let mut x = a.value.grab()?.clone();
+                    x *= b.value.grab()?;
                     x /= modulus.value.grab()?;
                     x
                 })
             },
-            self.params.limb_width,
+            a.params.limb_width,
             quotient_limbs,
         )?;
         quotient.assert_well_formed(cs.namespace(|| "quotient rangecheck"))?;
@@ -1093,18 +1501,18 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
             cs.namespace(|| "remainder"),
             || {
                 Ok({
-                    let mut x = self.value.grab()?.clone();
-                    x *= other.value.grab()?;
+                    let mut x = a.value.grab()?.clone();
+                    x *= b.value.grab()?;
                     x %= modulus.value.grab()?;
                     x
                 })
             },
-            self.params.limb_width,
+            a.params.limb_width,
             modulus.limbs.len(),
         )?;
         remainder.assert_well_formed(cs.namespace(|| "remainder rangecheck"))?;
-        let a_poly = Polynomial::from(self.clone());
-        let b_poly = Polynomial::from(other.clone());
+        let a_poly = Polynomial::from(a.clone());
+        let b_poly = Polynomial::from(b.clone());
         let mod_poly = Polynomial::from(modulus.clone());
         let q_poly = Polynomial::from(BigNat::from(quotient.clone()));
         let r_poly = Polynomial::from(BigNat::from(remainder.clone()));
@@ -1115,12 +1523,7 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         // q * m + r
         let right = Polynomial::from(right_product).sum(&r_poly);
 
-        let left_max_word = {
-            let mut x = BigInt::from(min(self.limbs.len(), other.limbs.len()));
-            x *= &self.params.max_word;
-            x *= &other.params.max_word;
-            x
-        };
+        let left_max_word = Self::product_max_word(&a, &b);
         let right_max_word = {
             let mut x = BigInt::from(std::cmp::min(quotient.limbs.len(), modulus.limbs.len()));
             x *= &quotient.params.max_word;
@@ -1180,6 +1583,65 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         Ok(remainder)
     }
 
+    /// Compute `BigNat`s `(q, r)` constrained to be the quotient and the remainder of
+    /// `self / divisor`, i.e. `self = q * divisor + r` with `r < divisor`.
+    /// Both numbers must be well formed, see `assert_well_formed`. A zero divisor is unsatisfiable.
+    pub fn div_rem<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        divisor: &Self,
+    ) -> Result<(BigNat<Scalar>, BigNat<Scalar>), SynthesisError> {
+        self.enforce_limb_width_agreement(divisor, "div_rem")?;
+        let limb_width = self.params.limb_width;
+        // divisor >= 2^(min_bits - 1)
+        let quotient_bits = self
+            .n_bits()
+            .saturating_sub(divisor.params.min_bits.saturating_sub(1));
+        let quotient_limbs = quotient_bits.saturating_sub(1) / limb_width + 1;
+        let divisor_value = || {
+            let d = divisor.value.grab()?;
+            if d.is_zero() {
+                Err(SynthesisError::DivisionByZero)
+            } else {
+                Ok(d)
+            }
+        };
+        let quotient = BigNat::alloc_from_nat(
+            cs.namespace(|| "quotient"),
+            || Ok(self.value.grab()? / divisor_value()?),
+            limb_width,
+            quotient_limbs,
+        )?;
+        quotient.assert_well_formed(cs.namespace(|| "quotient rangecheck"))?;
+        let remainder = BigNat::alloc_from_nat(
+            cs.namespace(|| "remainder"),
+            || Ok(self.value.grab()? % divisor_value()?),
+            limb_width,
+            divisor.limbs.len(),
+        )?;
+        remainder.assert_well_formed(cs.namespace(|| "remainder rangecheck"))?;
+        remainder.enforce_less_than(cs.namespace(|| "remainder < divisor"), divisor)?;
+
+        // q * d + r
+        let q_poly = Polynomial::from(quotient.clone());
+        let d_poly = Polynomial::from(divisor.clone());
+        let r_poly = Polynomial::from(remainder.clone());
+        let right_product = q_poly.alloc_product(cs.namespace(|| "right_product"), &d_poly)?;
+        let right = right_product.sum(&r_poly);
+
+        let right_max_word = {
+            let mut x = BigInt::from(min(quotient.limbs.len(), divisor.limbs.len()));
+            x *= &quotient.params.max_word;
+            x *= &divisor.params.max_word;
+            x += &remainder.params.max_word;
+            x
+        };
+
+        let right_int = BigNat::from_poly(right, limb_width, right_max_word);
+        self.equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int)?;
+        Ok((quotient, remainder))
+    }
+
     /// Combines limbs into groups.
     pub fn group_limbs(&self, limbs_per_group: usize) -> BigNat<Scalar> {
         let n_groups = (self.limbs.len() - 1) / limbs_per_group + 1;
@@ -1266,12 +1728,35 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
             params: BigNatParams {
                 min_bits: 1,
                 n_limbs: 1,
-                limb_width: limb_width,
+                limb_width,
                 max_word: BigInt::from(1),
             },
         }
     }
 
+    /// The constant `n`, in `n_limbs` limbs of width `limb_width`
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        n: &BigInt,
+        limb_width: usize,
+        n_limbs: usize,
+    ) -> Result<Self, SynthesisError> {
+        let limb_values = nat_to_limbs::<Scalar>(n, limb_width, n_limbs)?;
+        Ok(BigNat {
+            limbs: limb_values
+                .iter()
+                .map(|v| LinearCombination::zero() + (*v, CS::one()))
+                .collect(),
+            limb_values: Some(limb_values),
+            value: Some(n.clone()),
+            params: BigNatParams {
+                min_bits: n.bits() as usize,
+                n_limbs,
+                limb_width,
+                max_word: int_with_n_ones(limb_width),
+            },
+        })
+    }
+
     pub fn n_bits(&self) -> usize {
         assert!(self.params.n_limbs > 0);
         self.params.limb_width * (self.params.n_limbs - 1) + self.params.max_word.bits() as usize
@@ -1328,20 +1813,22 @@ impl<Scalar: PrimeField> Gadget for BigNat<Scalar> {
             eprintln!("Wire mis-match in BigNat mux");
             return Err(SynthesisError::Unsatisfiable);
         }
-        let value: Option<&Self::Value> = s
-            .value
-            .and_then(|b| if b { i1.value() } else { i0.value() });
-        let out: Self = Self::alloc(
+        // The limbs are selected as they are, so inputs need not be in canonical limbs
+        let limb_values = s.value.and_then(|b| {
+            if b {
+                i1.wire_values()
+            } else {
+                i0.wire_values()
+            }
+        });
+        let mut out = BigNat::alloc_from_limbs(
             cs.namespace(|| "out"),
-            value,
-            (),
-            &BigNatParams {
-                min_bits: min(i0.params.min_bits, i1.params.min_bits),
-                max_word: max(i0.params.max_word.clone(), i1.params.max_word.clone()),
-                limb_width: i0.params.limb_width,
-                n_limbs: i0.params.n_limbs,
-            },
+            || Ok(limb_values.grab()?.clone()),
+            Some(max(i0.params.max_word.clone(), i1.params.max_word.clone())),
+            i0.params.limb_width,
+            i0.params.n_limbs,
         )?;
+        out.params.min_bits = min(i0.params.min_bits, i1.params.min_bits);
         let out_wires = out.wires();
         for (i, ((i0w, i1w), out_w)) in i0_wires
             .into_iter()
@@ -1513,6 +2000,479 @@ mod tests {
         }
     }
 
+    #[derive(Debug)]
+    pub struct LessThanInputs {
+        pub a: BigInt,
+        pub b: BigInt,
+    }
+
+    pub struct LessThanParams {
+        pub limb_width: usize,
+        pub n_limbs_a: usize,
+        pub n_limbs_b: usize,
+    }
+
+    pub struct LessThan {
+        inputs: Option<LessThanInputs>,
+        params: LessThanParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for LessThan {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(self.inputs.grab()?.a.clone()),
+                self.params.limb_width,
+                self.params.n_limbs_a,
+            )?;
+            let b = BigNat::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(self.inputs.grab()?.b.clone()),
+                self.params.limb_width,
+                self.params.n_limbs_b,
+            )?;
+            a.assert_well_formed(cs.namespace(|| "a well formed"))?;
+            b.assert_well_formed(cs.namespace(|| "b well formed"))?;
+            a.enforce_less_than(cs.namespace(|| "a < b"), &b)?;
+            Ok(())
+        }
+    }
+
+    fn less_than(a: &str, b: &str, limb_width: usize, n_limbs: usize) -> LessThan {
+        LessThan {
+            inputs: Some(LessThanInputs {
+                a: BigInt::from_str_radix(a, 16).unwrap(),
+                b: BigInt::from_str_radix(b, 16).unwrap(),
+            }),
+            params: LessThanParams {
+                limb_width,
+                n_limbs_a: n_limbs,
+                n_limbs_b: n_limbs,
+            },
+        }
+    }
+
+    circuit_tests! {
+        less_than_1_2: (less_than("1", "2", 32, 1), true),
+        less_than_2_1: (less_than("2", "1", 32, 1), false),
+        less_than_equal: (less_than("ffff0000ffff", "ffff0000ffff", 32, 2), false),
+        less_than_high_limb: (less_than("1ffffffff", "200000000", 32, 2), true),
+        less_than_high_limb_wrong: (less_than("200000000", "1ffffffff", 32, 2), false),
+        less_than_low_limb: (less_than("500000003", "500000004", 32, 2), true),
+        // block 123456 hash against its target
+        less_than_pow: (less_than(
+            "2917ed80650c6174aac8dfc46f5fe36480aaef682ff6cd83c3ca",
+            "6a93b30000000000000000000000000000000000000000000000",
+            64,
+            4,
+        ), true),
+        less_than_pow_wrong: (less_than(
+            "6a93b30000000000000000000000000000000000000000000001",
+            "6a93b30000000000000000000000000000000000000000000000",
+            64,
+            4,
+        ), false),
+        less_than_max_256: (less_than(
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe",
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            128,
+            2,
+        ), true),
+        less_than_mixed_limbs: (LessThan {
+            inputs: Some(LessThanInputs {
+                a: BigInt::from(7usize),
+                b: BigInt::from(1usize) << 40,
+            }),
+            params: LessThanParams {
+                limb_width: 32,
+                n_limbs_a: 1,
+                n_limbs_b: 2,
+            },
+        }, true),
+    }
+
+    #[quickcheck]
+    fn big_nat_is_less_than(a: Vec<u8>, b: Vec<u8>, limb_width: u8) -> TestResult {
+        use crate::util::scalar::Fr;
+        let limb_width = limb_width as usize;
+        if !(4..=200).contains(&limb_width) {
+            return TestResult::discard();
+        }
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a);
+        let b = BigInt::from_bytes_le(num_bigint::Sign::Plus, &b);
+        let n_limbs = max(max(a.bits(), b.bits()) as usize, 1).div_ceil(limb_width);
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let alloc = |cs: &mut TestConstraintSystem<Fr>, name: &str, v: &BigInt| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(v.clone()), limb_width, n_limbs)
+                .unwrap()
+        };
+        let a_nat = alloc(&mut cs, "a", &a);
+        let b_nat = alloc(&mut cs, "b", &b);
+        let r = a_nat
+            .is_less_than(cs.namespace(|| "a < b"), &b_nat)
+            .unwrap();
+        TestResult::from_bool(cs.is_satisfied() && r.get_value() == Some(a < b))
+    }
+
+    #[derive(Debug)]
+    pub struct DivRemInputs {
+        pub a: BigInt,
+        pub b: BigInt,
+        pub q: BigInt,
+        pub r: BigInt,
+    }
+
+    pub struct DivRemParams {
+        pub limb_width: usize,
+        pub n_limbs_a: usize,
+        pub n_limbs_b: usize,
+    }
+
+    pub struct DivRem {
+        inputs: Option<DivRemInputs>,
+        params: DivRemParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for DivRem {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(self.inputs.grab()?.a.clone()),
+                self.params.limb_width,
+                self.params.n_limbs_a,
+            )?;
+            let b = BigNat::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(self.inputs.grab()?.b.clone()),
+                self.params.limb_width,
+                self.params.n_limbs_b,
+            )?;
+            a.assert_well_formed(cs.namespace(|| "a well formed"))?;
+            b.assert_well_formed(cs.namespace(|| "b well formed"))?;
+            let (q, r) = a.div_rem(cs.namespace(|| "a div b"), &b)?;
+            let q_expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "q"),
+                || Ok(self.inputs.grab()?.q.clone()),
+                self.params.limb_width,
+                q.limbs.len(),
+            )?;
+            let r_expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "r"),
+                || Ok(self.inputs.grab()?.r.clone()),
+                self.params.limb_width,
+                r.limbs.len(),
+            )?;
+            q.equal(cs.namespace(|| "qcheck"), &q_expected)?;
+            r.equal(cs.namespace(|| "rcheck"), &r_expected)?;
+            Ok(())
+        }
+    }
+
+    fn div_rem(a: &str, b: &str, q: &str, r: &str, limb_width: usize, n_limbs: usize) -> DivRem {
+        DivRem {
+            inputs: Some(DivRemInputs {
+                a: BigInt::from_str_radix(a, 16).unwrap(),
+                b: BigInt::from_str_radix(b, 16).unwrap(),
+                q: BigInt::from_str_radix(q, 16).unwrap(),
+                r: BigInt::from_str_radix(r, 16).unwrap(),
+            }),
+            params: DivRemParams {
+                limb_width,
+                n_limbs_a: n_limbs,
+                n_limbs_b: n_limbs,
+            },
+        }
+    }
+
+    circuit_tests! {
+        div_rem_7_2: (div_rem("7", "2", "3", "1", 32, 1), true),
+        div_rem_7_2_wrong_q: (div_rem("7", "2", "2", "3", 32, 1), false),
+        div_rem_exact: (div_rem("ffff0000ffff", "ffff", "100000001", "0", 32, 2), true),
+        div_rem_small_by_large: (div_rem("5", "100000000", "0", "5", 32, 2), true),
+        // block 123456 work: max target / target
+        div_rem_work: (div_rem(
+            "ffff0000000000000000000000000000000000000000000000000000",
+            "6a93b30000000000000000000000000000000000000000000000",
+            "266e8",
+            "2ad3c80000000000000000000000000000000000000000000000",
+            64,
+            4,
+        ), true),
+    }
+
+    #[test]
+    fn test_div_rem_by_zero() {
+        use crate::util::scalar::Fr;
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        assert!(div_rem("5", "0", "0", "5", 32, 1).synthesize(&mut cs).is_err());
+    }
+
+    #[quickcheck]
+    fn big_nat_div_rem(a: Vec<u8>, b: Vec<u8>, limb_width: u8) -> TestResult {
+        use crate::util::scalar::Fr;
+        let limb_width = limb_width as usize;
+        if !(4..=200).contains(&limb_width) {
+            return TestResult::discard();
+        }
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a);
+        let b = BigInt::from_bytes_le(num_bigint::Sign::Plus, &b);
+        if b.is_zero() {
+            return TestResult::discard();
+        }
+        let n_limbs = max(max(a.bits(), b.bits()) as usize, 1).div_ceil(limb_width);
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let alloc = |cs: &mut TestConstraintSystem<Fr>, name: &str, v: &BigInt| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(v.clone()), limb_width, n_limbs)
+                .unwrap()
+        };
+        let a_nat = alloc(&mut cs, "a", &a);
+        let b_nat = alloc(&mut cs, "b", &b);
+        let (q, r) = a_nat.div_rem(cs.namespace(|| "a div b"), &b_nat).unwrap();
+        TestResult::from_bool(
+            cs.is_satisfied() && q.value == Some(&a / &b) && r.value == Some(&a % &b),
+        )
+    }
+
+    #[quickcheck]
+    fn big_nat_min_max_clamp(a: Vec<u8>, b: Vec<u8>, c: Vec<u8>, limb_width: u8) -> TestResult {
+        use crate::util::scalar::Fr;
+        let limb_width = limb_width as usize;
+        if !(4..=200).contains(&limb_width) {
+            return TestResult::discard();
+        }
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a);
+        let b = BigInt::from_bytes_le(num_bigint::Sign::Plus, &b);
+        let c = BigInt::from_bytes_le(num_bigint::Sign::Plus, &c);
+        let (low, high) = (min(&b, &c).clone(), max(&b, &c).clone());
+        let n_bits = max(max(a.bits(), b.bits()), max(c.bits(), 1)) as usize;
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        // `a` gets an extra limb, so that the operands differ in limb count
+        let alloc = |cs: &mut TestConstraintSystem<Fr>, name: &str, v: &BigInt, n_limbs: usize| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(v.clone()), limb_width, n_limbs)
+                .unwrap()
+        };
+        let a_nat = alloc(&mut cs, "a", &a, n_bits.div_ceil(limb_width) + 1);
+        let low_nat = alloc(&mut cs, "low", &low, n_bits.div_ceil(limb_width));
+        let high_nat = alloc(&mut cs, "high", &high, n_bits.div_ceil(limb_width));
+        let min_nat = a_nat.min(cs.namespace(|| "min"), &low_nat).unwrap();
+        let max_nat = a_nat.max(cs.namespace(|| "max"), &low_nat).unwrap();
+        let clamp_nat = a_nat
+            .clamp(cs.namespace(|| "clamp"), &low_nat, &high_nat)
+            .unwrap();
+        TestResult::from_bool(
+            cs.is_satisfied()
+                && min_nat.value == Some(min(&a, &low).clone())
+                && max_nat.value == Some(max(&a, &low).clone())
+                && clamp_nat.value == Some(a.clone().clamp(low, high)),
+        )
+    }
+
+    #[test]
+    fn test_big_nat_max_forged() {
+        use crate::util::scalar::Fr;
+        // the output of max cannot be replaced by the lesser operand
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(3u64) << 40), 32, 2).unwrap();
+        let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(BigInt::from(5u64)), 32, 2).unwrap();
+        let m = a.max(cs.namespace(|| "max"), &b).unwrap();
+        assert_eq!(m.value, a.value);
+        assert!(cs.is_satisfied());
+        cs.set("max/select/out/limb 0", Fr::from(5u64));
+        cs.set("max/select/out/limb 1", Fr::from(0u64));
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_big_nat_equal_lengths() {
+        use crate::util::scalar::Fr;
+        // the limbs of the longer operand beyond the shorter one are compared with zero
+        for (high, equal) in [(0u64, true), (1, false)] {
+            for swap in [false, true] {
+                let mut cs = TestConstraintSystem::<Fr>::new();
+                let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(7u64)), 32, 1).unwrap();
+                let b_value = (BigInt::from(high) << 32) + 7u64;
+                let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(b_value), 32, 2).unwrap();
+                let (x, y) = if swap { (&b, &a) } else { (&a, &b) };
+                x.equal(cs.namespace(|| "equal"), y).unwrap();
+                assert_eq!(cs.is_satisfied(), equal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_big_nat_conditional_select() {
+        use crate::util::scalar::Fr;
+        for condition in [false, true] {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(7u64)), 32, 1).unwrap();
+            let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(BigInt::from(1u64) << 40), 32, 2).unwrap();
+            let c = Boolean::from(
+                AllocatedBit::alloc(cs.namespace(|| "condition"), Some(condition)).unwrap(),
+            );
+            let r = BigNat::conditional_select(cs.namespace(|| "select"), &a, &b, &c).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(r.value, if condition { a.value } else { b.value });
+        }
+    }
+
+    #[test]
+    fn test_big_nat_carry() {
+        use crate::util::scalar::Fr;
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(int_with_n_ones(64)), 32, 2).unwrap();
+        let mut sum = a.clone();
+        for _ in 0..7 {
+            sum = sum.add::<TestConstraintSystem<Fr>>(&sum).unwrap();
+        }
+        assert!(!sum.is_carried());
+        let carried = sum.carry(cs.namespace(|| "carry")).unwrap();
+        assert!(carried.is_carried());
+        assert_eq!(carried.params.n_limbs, 3);
+        assert_eq!(carried.value, Some(int_with_n_ones(64) << 7));
+        assert!(cs.is_satisfied());
+        cs.set("carry/carried/limb 2", Fr::from(0u64));
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_big_nat_add_carried() {
+        use crate::util::scalar::Fr;
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(int_with_n_ones(64)), 64, 1).unwrap();
+        // Plain additions refuse to wrap around the field
+        let limit = BigNat::<Fr>::max_word_bits_limit();
+        let full = a.scale::<TestConstraintSystem<Fr>>(nat_to_f(&(BigInt::from(1) << (limit - 64))).unwrap());
+        assert_eq!(full.headroom(), 0);
+        assert!(full.add::<TestConstraintSystem<Fr>>(&full).is_err());
+        assert!(std::panic::catch_unwind(|| full.scale::<TestConstraintSystem<Fr>>(Fr::from(2u64))).is_err());
+
+        // x <- x * 2^61 + a
+        let mut x = a.clone();
+        let mut expected = int_with_n_ones(64);
+        for i in 0..10 {
+            let scaled = x.scale_carried(cs.namespace(|| format!("scale {}", i)), Fr::from(1u64 << 61)).unwrap();
+            x = scaled.add_carried(cs.namespace(|| format!("add {}", i)), &a).unwrap();
+            expected = (expected << 61) + int_with_n_ones(64);
+        }
+        assert_eq!(x.value, Some(expected));
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_big_nat_mult_carries_operands() {
+        use crate::util::scalar::Fr;
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(int_with_n_ones(128)), 64, 2).unwrap();
+        let b = a.scale::<TestConstraintSystem<Fr>>(nat_to_f(&(BigInt::from(1) << 123)).unwrap());
+        // The limbs of b * b would not fit in the field without carrying b
+        assert!(BigNat::product_max_word(&b, &b).bits() as usize > BigNat::<Fr>::max_word_bits_limit());
+        let product = b.mult(cs.namespace(|| "product"), &b).unwrap();
+        let expected = int_with_n_ones(128) << 123;
+        assert_eq!(product.value, Some(&expected * &expected));
+        assert!(cs.is_satisfied());
+        assert!(BigNat::product_max_word(&product, &product).bits() as usize <= BigNat::<Fr>::max_word_bits_limit());
+    }
+
+    fn alloc_bits<CS: ConstraintSystem<crate::util::scalar::Fr>>(cs: &mut CS, name: &str, bits: &[bool]) -> Bitvector<crate::util::scalar::Fr> {
+        Bitvector::from_bits(
+            bits.iter()
+                .enumerate()
+                .map(|(i, b)| Bit::alloc(cs.namespace(|| format!("{} {}", name, i)), Some(*b)).unwrap())
+                .collect(),
+        )
+    }
+
+    #[quickcheck]
+    fn big_nat_shift_const(a: Vec<u8>, shift: u8, limb_width: u8) -> TestResult {
+        use crate::util::scalar::Fr;
+        let limb_width = limb_width as usize;
+        if !(4..=200).contains(&limb_width) {
+            return TestResult::discard();
+        }
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a);
+        let n_limbs = max(a.bits() as usize, 1).div_ceil(limb_width);
+        let shift = shift as usize;
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a_nat = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), limb_width, n_limbs).unwrap();
+        let shl = a_nat.shl_const(cs.namespace(|| "shl"), shift).unwrap();
+        let shr = a_nat.shr_const(cs.namespace(|| "shr"), shift).unwrap();
+        TestResult::from_bool(
+            cs.is_satisfied() && shl.value == Some(&a << shift) && shr.value == Some(&a >> shift),
+        )
+    }
+
+    #[quickcheck]
+    fn big_nat_shift_var(a: Vec<u8>, e: u8, limb_width: u8) -> TestResult {
+        use crate::util::scalar::Fr;
+        let limb_width = limb_width as usize;
+        if !(4..=200).contains(&limb_width) {
+            return TestResult::discard();
+        }
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a);
+        let n_limbs = max(a.bits() as usize, 1).div_ceil(limb_width);
+        let e = e as usize % 32;
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a_nat = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), limb_width, n_limbs).unwrap();
+        let e_bits = alloc_bits(&mut cs, "e", &(0..5).map(|i| (e >> i) & 1 == 1).collect::<Vec<_>>());
+        let shl = a_nat.shl_var(cs.namespace(|| "shl"), &e_bits, 8).unwrap();
+        let shr = a_nat.shr_var(cs.namespace(|| "shr"), &e_bits, 8).unwrap();
+        TestResult::from_bool(
+            cs.is_satisfied()
+                && shl.value == Some(&a << (8 * e))
+                && shr.value == Some(&a >> (8 * e)),
+        )
+    }
+
+    #[test]
+    fn test_big_nat_shift_var_forged() {
+        use crate::util::scalar::Fr;
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(0x81u64)), 8, 1).unwrap();
+        let e_bits = alloc_bits(&mut cs, "e", &[true, false]);
+        let shl = a.shl_var(cs.namespace(|| "shl"), &e_bits, 4).unwrap();
+        assert_eq!(shl.value, Some(BigInt::from(0x810u64)));
+        assert!(cs.is_satisfied());
+        // the shift cannot be undone for the lowest bit
+        cs.set("shl/shift/stage 0/bit 0", Fr::from(1u64));
+        assert!(!cs.is_satisfied());
+    }
+
+    #[quickcheck]
+    fn big_nat_le_bytes_bits(bytes: Vec<u8>) -> TestResult {
+        use crate::util::scalar::Fr;
+        if bytes.is_empty() {
+            return TestResult::discard();
+        }
+        // bytes as SHA-256 outputs them: most significant bit of every byte first
+        let bits = bytes
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+            .collect::<Vec<_>>();
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let bv = alloc_bits(&mut cs, "bytes", &bits);
+        let n = BigNat::from_le_bytes_bits(&bv, 32);
+        let round_trip = n.to_le_bytes_bits(cs.namespace(|| "round trip")).unwrap();
+        let mut round_trip_values = round_trip.values.unwrap();
+        round_trip_values.truncate(bits.len());
+        TestResult::from_bool(
+            cs.is_satisfied()
+                && n.value == Some(BigInt::from_bytes_le(num_bigint::Sign::Plus, &bytes))
+                && round_trip_values == bits,
+        )
+    }
+
     #[derive(Debug)]
     pub struct MultModInputs {
         pub a: BigInt,
@@ -1889,6 +2849,64 @@ mod tests {
         circuit.synthesize(&mut cs).expect("synthesis failed");
         TestResult::from_bool(cs.is_satisfied())
     }
+
+    #[test]
+    fn test_big_nat_soundness() {
+        use crate::util::fuzz::FuzzingConstraintSystem;
+        use crate::util::scalar::Fr;
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(0x1234_5678_9abcu64)), 32, 2).unwrap();
+        let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(BigInt::from(0x7_0000_0005u64)), 32, 2).unwrap();
+        a.assert_well_formed(cs.namespace(|| "a range")).unwrap();
+        b.assert_well_formed(cs.namespace(|| "b range")).unwrap();
+        for input in ["a", "b", "a range", "b range"] {
+            cs.fix(input);
+        }
+        let (q, r) = a.div_rem(cs.namespace(|| "a div b"), &b).unwrap();
+        let lt = a.is_less_than(cs.namespace(|| "a < b"), &b).unwrap();
+        let eq = a.is_equal(cs.namespace(|| "a == b"), &b).unwrap();
+        let min_nat = a.min(cs.namespace(|| "min"), &b).unwrap();
+        let max_nat = a.max(cs.namespace(|| "max"), &b).unwrap();
+        let product = a.mult(cs.namespace(|| "a * b"), &b).unwrap();
+        cs.output_bignat("quotient", &q);
+        cs.output_bignat("remainder", &r);
+        cs.output_boolean("a < b", &lt);
+        cs.output_boolean("a == b", &eq);
+        cs.output_bignat("min", &min_nat);
+        cs.output_bignat("max", &max_nat);
+        cs.output_bignat("product", &product);
+        cs.assert_sound();
+    }
+
+    #[test]
+    fn test_big_nat_shift_select_soundness() {
+        use crate::util::fuzz::FuzzingConstraintSystem;
+        use crate::util::scalar::Fr;
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(BigInt::from(0x1234_5678_9abcu64)), 32, 2).unwrap();
+        let low = BigNat::alloc_from_nat(cs.namespace(|| "low"), || Ok(BigInt::from(0x1000u64)), 32, 2).unwrap();
+        let high = BigNat::alloc_from_nat(cs.namespace(|| "high"), || Ok(BigInt::from(0x7_0000_0005u64)), 32, 2).unwrap();
+        a.assert_well_formed(cs.namespace(|| "a range")).unwrap();
+        low.assert_well_formed(cs.namespace(|| "low range")).unwrap();
+        high.assert_well_formed(cs.namespace(|| "high range")).unwrap();
+        let e_bits = alloc_bits(&mut cs, "e", &[true, false, true]);
+        let condition = Boolean::from(AllocatedBit::alloc(cs.namespace(|| "condition"), Some(true)).unwrap());
+        for input in ["a", "low", "high", "a range", "low range", "high range", "e 0", "e 1", "e 2", "condition"] {
+            cs.fix(input);
+        }
+        let shl = a.shl_var(cs.namespace(|| "shl"), &e_bits, 4).unwrap();
+        let shr = a.shr_var(cs.namespace(|| "shr"), &e_bits, 4).unwrap();
+        // a is above high, so it is clamped
+        let clamped = a.clamp(cs.namespace(|| "clamp"), &low, &high).unwrap();
+        let selected = BigNat::conditional_select(cs.namespace(|| "select"), &a, &low, &condition).unwrap();
+        assert_eq!(clamped.value, high.value);
+        assert_eq!(selected.value, a.value);
+        cs.output_bignat("shl", &shl);
+        cs.output_bignat("shr", &shr);
+        cs.output_bignat("clamped", &clamped);
+        cs.output_bignat("selected", &selected);
+        cs.assert_sound();
+    }
 }
 
 impl<Scalar: PrimeField> Display for BigNat<Scalar> {
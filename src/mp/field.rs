@@ -0,0 +1,617 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Num as BigNum, One, Zero};
+
+use std::cmp::{max, min};
+
+use super::bignat::{limbs_to_nat, nat_to_limbs, BigNat, BigNatParams};
+use super::poly::Polynomial;
+use crate::util::convert::nat_to_f;
+use crate::util::pack::{self, BitOrder};
+use crate::OptionExt;
+
+/// A prime field of order `modulus`, whose elements are represented by `BigNat`s of
+/// `n_limbs` limbs of width `limb_width`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldParams {
+    pub modulus: BigInt,
+    pub limb_width: usize,
+    pub n_limbs: usize,
+}
+
+impl FieldParams {
+    pub fn new(modulus: BigInt, limb_width: usize) -> Self {
+        assert!(
+            modulus > BigInt::from(2),
+            "the modulus must be an odd prime"
+        );
+        let n_limbs = (modulus.bits() as usize - 1) / limb_width + 1;
+        FieldParams {
+            modulus,
+            limb_width,
+            n_limbs,
+        }
+    }
+
+    /// The base field of secp256k1
+    pub fn secp256k1_base() -> Self {
+        let p = BigInt::from_str_radix(
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            16,
+        )
+        .unwrap();
+        Self::new(p, 64)
+    }
+
+    /// The scalar field of secp256k1, i.e. the order of its group
+    pub fn secp256k1_scalar() -> Self {
+        let n = BigInt::from_str_radix(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        )
+        .unwrap();
+        Self::new(n, 64)
+    }
+
+    /// The largest limb, in bits, that an unreduced element may have.
+    /// The product of two such elements still carries without overflowing `Scalar`.
+    pub fn max_word_bits<Scalar: PrimeField>(&self) -> usize {
+        let n_limbs_bits = usize::BITS - self.n_limbs.leading_zeros();
+        (Scalar::CAPACITY as usize - 2 - n_limbs_bits as usize) / 2
+    }
+
+    fn modulus_nat<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&self.modulus, self.limb_width, self.n_limbs)
+    }
+}
+
+/// An element of the field described by `params`.
+///
+/// Additions are lazy: the sum of two elements is a `BigNat` whose limbs may exceed
+/// `limb_width` bits, and which may exceed the modulus. It is reduced only once its limbs
+/// outgrow `FieldParams::max_word_bits`. Products and differences are reduced into
+/// `n_limbs` well-formed limbs, but not necessarily below the modulus.
+/// Use `reduce` to get the canonical representative.
+#[derive(Clone)]
+pub struct FieldElement<Scalar: PrimeField> {
+    pub nat: BigNat<Scalar>,
+    pub params: FieldParams,
+}
+
+impl<Scalar: PrimeField> FieldElement<Scalar> {
+    /// Allocates a field element, constrained to be canonical.
+    pub fn alloc<CS, F>(mut cs: CS, f: F, params: &FieldParams) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let nat = BigNat::alloc_from_nat(
+            cs.namespace(|| "value"),
+            f,
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        nat.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        nat.enforce_less_than(
+            cs.namespace(|| "canonical"),
+            &params.modulus_nat::<Scalar, CS>()?,
+        )?;
+        Ok(FieldElement {
+            nat,
+            params: params.clone(),
+        })
+    }
+
+    /// Allocates a field element in well-formed limbs, not necessarily below the modulus.
+    pub fn alloc_unreduced<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &FieldParams,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let nat = BigNat::alloc_from_nat(
+            cs.namespace(|| "value"),
+            f,
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        nat.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        Ok(FieldElement {
+            nat,
+            params: params.clone(),
+        })
+    }
+
+    /// The constant `n mod p`
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        n: &BigInt,
+        params: &FieldParams,
+    ) -> Result<Self, SynthesisError> {
+        Ok(FieldElement {
+            nat: BigNat::constant::<CS>(
+                &n.mod_floor(&params.modulus),
+                params.limb_width,
+                params.n_limbs,
+            )?,
+            params: params.clone(),
+        })
+    }
+
+    /// The canonical value of the element
+    pub fn value(&self) -> Option<BigInt> {
+        self.nat
+            .value
+            .as_ref()
+            .map(|v| v.mod_floor(&self.params.modulus))
+    }
+
+    /// Computes `self + other`, without reducing unless the limbs would grow too large.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let sum = FieldElement {
+            nat: self.nat.add::<CS>(&other.nat)?,
+            params: self.params.clone(),
+        };
+        sum.reduce_if_large(cs.namespace(|| "reduce"))
+    }
+
+    /// Computes `self - other`, without reducing unless the limbs would grow too large.
+    pub fn sub<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "sub")?;
+        let limb_width = self.params.limb_width;
+        let n_limbs = max(
+            self.params.n_limbs,
+            max(self.nat.limbs.len(), other.nat.limbs.len()),
+        );
+        // A multiple of the modulus whose limbs are all at least the largest limb of `other`,
+        // so that every limb of `self + offset - other` is a natural number
+        let floor = &other.nat.params.max_word;
+        let floors = (0..n_limbs).fold(BigInt::zero(), |acc, i| acc + (floor << (limb_width * i)));
+        let fill = nat_to_limbs::<Scalar>(
+            &(-&floors).mod_floor(&self.params.modulus),
+            limb_width,
+            n_limbs,
+        )?;
+        let floor_f = nat_to_f::<Scalar>(floor).ok_or(SynthesisError::Unsatisfiable)?;
+        let offset: Vec<Scalar> = fill.iter().map(|w| *w + floor_f).collect();
+        let self_nat = self.nat.with_n_limbs::<CS>(n_limbs);
+        let other_nat = other.nat.with_n_limbs::<CS>(n_limbs);
+        let limbs = (0..n_limbs)
+            .map(|i| self_nat.limbs[i].clone() + (offset[i], CS::one()) - &other_nat.limbs[i])
+            .collect();
+        let limb_values = self_nat.limb_values.as_ref().and_then(|xs| {
+            other_nat.limb_values.as_ref().map(|ys| {
+                (0..n_limbs)
+                    .map(|i| xs[i] + offset[i] - ys[i])
+                    .collect::<Vec<_>>()
+            })
+        });
+        let value = self_nat.value.as_ref().and_then(|x| {
+            other_nat
+                .value
+                .as_ref()
+                .map(|y| x + &floors + limbs_to_nat::<Scalar, _, _>(fill.iter(), limb_width) - y)
+        });
+        let difference = FieldElement {
+            nat: BigNat {
+                limbs,
+                limb_values,
+                value,
+                params: BigNatParams {
+                    min_bits: 0,
+                    max_word: &self.nat.params.max_word + floor + (BigInt::one() << limb_width),
+                    limb_width,
+                    n_limbs,
+                },
+            },
+            params: self.params.clone(),
+        };
+        difference.reduce_if_large(cs.namespace(|| "reduce"))
+    }
+
+    /// Computes `self * other`.
+    pub fn mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "mul")?;
+        let modulus = self.params.modulus_nat::<Scalar, CS>()?;
+        let (_, product) = self
+            .nat
+            .mult_mod(cs.namespace(|| "product"), &other.nat, &modulus)?;
+        Ok(FieldElement {
+            nat: product,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes `self^-1`. An element equal to zero is unsatisfiable.
+    pub fn inverse<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let modulus = &self.params.modulus;
+        let inverse = BigNat::alloc_from_nat(
+            cs.namespace(|| "inverse"),
+            || mod_inverse(self.nat.value.grab()?, modulus).ok_or(SynthesisError::DivisionByZero),
+            self.params.limb_width,
+            self.params.n_limbs,
+        )?;
+        inverse.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        let inverse = FieldElement {
+            nat: inverse,
+            params: self.params.clone(),
+        };
+        let one = FieldElement::constant::<CS>(&BigInt::one(), &self.params)?;
+        FieldElement::enforce_product(cs.namespace(|| "self * inverse = 1"), self, &inverse, &one)?;
+        Ok(inverse)
+    }
+
+    /// Computes `self / other`. A zero `other` is unsatisfiable.
+    pub fn div<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let inverse = other.inverse(cs.namespace(|| "inverse"))?;
+        self.mul(cs.namespace(|| "product"), &inverse)
+    }
+
+    /// Computes `-self`.
+    pub fn neg<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        FieldElement::constant::<CS>(&BigInt::zero(), &self.params)?
+            .sub(cs.namespace(|| "0 - self"), self)
+    }
+
+    /// Returns `a` if `condition` is true, otherwise `b`.
+    pub fn conditional_select<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        condition: &Boolean,
+    ) -> Result<Self, SynthesisError> {
+        a.enforce_params_agreement(b, "conditional_select")?;
+        Ok(FieldElement {
+            nat: BigNat::conditional_select(cs.namespace(|| "select"), &a.nat, &b.nat, condition)?,
+            params: a.params.clone(),
+        })
+    }
+
+    /// The `limb_width * n_limbs` bits of the canonical representative of `self`,
+    /// in the order `order`.
+    pub fn to_bits<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        order: BitOrder,
+    ) -> Result<Vec<Boolean>, SynthesisError> {
+        let reduced = self.reduce(cs.namespace(|| "reduce"))?;
+        pack::unpack_bignat(cs.namespace(|| "unpack"), &reduced.nat, order)
+    }
+
+    /// Computes the canonical representative of `self`, below the modulus.
+    pub fn reduce<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let reduced = self.red(cs.namespace(|| "red"))?;
+        reduced.nat.enforce_less_than(
+            cs.namespace(|| "canonical"),
+            &self.params.modulus_nat::<Scalar, CS>()?,
+        )?;
+        Ok(reduced)
+    }
+
+    /// Constrains `a * b = c` in the field.
+    /// This is cheaper than computing `a * b` when `c` is known or is a sum.
+    pub fn enforce_product<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        c: &Self,
+    ) -> Result<(), SynthesisError> {
+        a.enforce_params_agreement(b, "enforce_product")?;
+        a.enforce_params_agreement(c, "enforce_product")?;
+        let product = Polynomial::from(a.nat.clone())
+            .alloc_product(cs.namespace(|| "product"), &Polynomial::from(b.nat.clone()))?;
+        let max_word = {
+            let mut x = BigInt::from(min(a.nat.limbs.len(), b.nat.limbs.len()));
+            x *= &a.nat.params.max_word;
+            x *= &b.nat.params.max_word;
+            x
+        };
+        let product = BigNat::from_poly(product, a.params.limb_width, max_word);
+        enforce_congruent(
+            cs.namespace(|| "congruent"),
+            &product,
+            &c.nat,
+            &a.params.modulus,
+        )
+    }
+
+    /// Constrains `self = other` in the field.
+    pub fn equal<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(other, "equal")?;
+        enforce_congruent(
+            cs.namespace(|| "congruent"),
+            &self.nat,
+            &other.nat,
+            &self.params.modulus,
+        )
+    }
+
+    // Reduces `self` into well-formed limbs, not necessarily below the modulus
+    fn red<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let modulus = self.params.modulus_nat::<Scalar, CS>()?;
+        Ok(FieldElement {
+            nat: self.nat.red_mod(cs.namespace(|| "red_mod"), &modulus)?,
+            params: self.params.clone(),
+        })
+    }
+
+    // Reduces `self` if its limbs outgrew `FieldParams::max_word_bits`
+    fn reduce_if_large<CS: ConstraintSystem<Scalar>>(self, cs: CS) -> Result<Self, SynthesisError> {
+        if self.nat.params.max_word.bits() as usize > self.params.max_word_bits::<Scalar>() {
+            self.red(cs)
+        } else {
+            Ok(self)
+        }
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!(
+                "Field elements do not agree on their field in {}: {:?} vs {:?}",
+                location, self.params, other.params
+            );
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+}
+
+/// The inverse of `a` modulo the prime `modulus`, unless `a` is a multiple of it
+pub fn mod_inverse(a: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    let a = a.mod_floor(modulus);
+    if a.is_zero() {
+        None
+    } else {
+        Some(a.extended_gcd(modulus).x.mod_floor(modulus))
+    }
+}
+
+/// Constrains `lhs = rhs (mod modulus)` by enforcing `lhs + k * modulus = rhs + q * modulus`,
+/// where `k * modulus` is a constant above any value of `rhs`, so that `q` is a natural number.
+fn enforce_congruent<Scalar, CS>(
+    mut cs: CS,
+    lhs: &BigNat<Scalar>,
+    rhs: &BigNat<Scalar>,
+    modulus: &BigInt,
+) -> Result<(), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let limb_width = lhs.params.limb_width;
+    let n_limbs = |n: &BigInt| (n.bits() as usize).saturating_sub(1) / limb_width + 1;
+    let offset = ((BigInt::from(1) << rhs.n_bits()) / modulus + 1) * modulus;
+    let left = lhs.add::<CS>(&BigNat::constant::<CS>(
+        &offset,
+        limb_width,
+        n_limbs(&offset),
+    )?)?;
+    let modulus_nat = BigNat::constant::<CS>(modulus, limb_width, n_limbs(modulus))?;
+    let quotient_bits = left
+        .n_bits()
+        .saturating_sub(modulus_nat.params.min_bits.saturating_sub(1));
+    let quotient = BigNat::alloc_from_nat(
+        cs.namespace(|| "quotient"),
+        || Ok((left.value.grab()? - rhs.value.grab()?) / modulus),
+        limb_width,
+        quotient_bits.saturating_sub(1) / limb_width + 1,
+    )?;
+    quotient.assert_well_formed(cs.namespace(|| "quotient rangecheck"))?;
+
+    // q * m + rhs
+    let q_poly = Polynomial::from(quotient.clone());
+    let mod_poly = Polynomial::from(modulus_nat.clone());
+    let right_product = q_poly.alloc_product(cs.namespace(|| "right_product"), &mod_poly)?;
+    let right = right_product.sum(&Polynomial::from(rhs.clone()));
+    let right_max_word = {
+        let mut x = BigInt::from(min(quotient.limbs.len(), modulus_nat.limbs.len()));
+        x *= &quotient.params.max_word;
+        x *= &modulus_nat.params.max_word;
+        x += &rhs.params.max_word;
+        x
+    };
+    let right_int = BigNat::from_poly(right, limb_width, right_max_word);
+
+    let n = max(left.limbs.len(), right_int.limbs.len());
+    left.with_n_limbs::<CS>(n)
+        .equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int.with_n_limbs::<CS>(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use quickcheck::TestResult;
+
+    pub struct FieldOpsInputs {
+        pub a: BigInt,
+        pub b: BigInt,
+        pub result: BigInt,
+    }
+
+    /// Checks `((a + b) * a - b) / b = result`
+    pub struct FieldOps {
+        inputs: Option<FieldOpsInputs>,
+        params: FieldParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for FieldOps {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = FieldElement::alloc(
+                cs.namespace(|| "a"),
+                || Ok(self.inputs.grab()?.a.clone()),
+                &self.params,
+            )?;
+            let b = FieldElement::alloc(
+                cs.namespace(|| "b"),
+                || Ok(self.inputs.grab()?.b.clone()),
+                &self.params,
+            )?;
+            let result = FieldElement::alloc(
+                cs.namespace(|| "result"),
+                || Ok(self.inputs.grab()?.result.clone()),
+                &self.params,
+            )?;
+            let sum = a.add(cs.namespace(|| "a + b"), &b)?;
+            let prod = sum.mul(cs.namespace(|| "(a + b) * a"), &a)?;
+            let diff = prod.sub(cs.namespace(|| "(a + b) * a - b"), &b)?;
+            let quotient = diff.div(cs.namespace(|| "div b"), &b)?;
+            quotient.equal(cs.namespace(|| "check"), &result)?;
+            Ok(())
+        }
+    }
+
+    fn field_ops(a: &str, b: &str, result: &str) -> FieldOps {
+        FieldOps {
+            inputs: Some(FieldOpsInputs {
+                a: BigInt::from_str_radix(a, 16).unwrap(),
+                b: BigInt::from_str_radix(b, 16).unwrap(),
+                result: BigInt::from_str_radix(result, 16).unwrap(),
+            }),
+            params: FieldParams::secp256k1_base(),
+        }
+    }
+
+    fn expected(a: &BigInt, b: &BigInt, params: &FieldParams) -> BigInt {
+        let p = &params.modulus;
+        let b_inv = mod_inverse(b, p).unwrap();
+        (((a + b) * a - b) * b_inv).mod_floor(p)
+    }
+
+    circuit_tests! {
+        field_ops_small: (field_ops("4", "2", "b"), true),
+        field_ops_small_wrong: (field_ops("4", "2", "c"), false),
+        // (p - 1 + 1) * (p - 1) - 1 = -1
+        field_ops_wrap: (field_ops(
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+            "1",
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+        ), true),
+        // the modulus itself is not a canonical element
+        field_ops_not_canonical: (field_ops(
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            "1",
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+        ), false),
+    }
+
+    #[quickcheck]
+    fn field_ops_random(a: Vec<u8>, b: Vec<u8>, scalar: bool) -> TestResult {
+        let params = if scalar {
+            FieldParams::secp256k1_scalar()
+        } else {
+            FieldParams::secp256k1_base()
+        };
+        let a = BigInt::from_bytes_le(num_bigint::Sign::Plus, &a).mod_floor(&params.modulus);
+        let b = BigInt::from_bytes_le(num_bigint::Sign::Plus, &b).mod_floor(&params.modulus);
+        if b.is_zero() {
+            return TestResult::discard();
+        }
+        let result = expected(&a, &b, &params);
+        let circuit = FieldOps {
+            inputs: Some(FieldOpsInputs { a, b, result }),
+            params,
+        };
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        TestResult::from_bool(cs.is_satisfied())
+    }
+
+    #[test]
+    fn test_field_lazy_add() {
+        let params = FieldParams::secp256k1_base();
+        let a = BigInt::from_str_radix("deadbeef0123456789abcdef", 16).unwrap();
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let mut x = FieldElement::alloc(cs.namespace(|| "a"), || Ok(a.clone()), &params).unwrap();
+        let mut reductions = 0;
+        for i in 0..300 {
+            let before = cs.num_constraints();
+            x = x.add(cs.namespace(|| format!("double {}", i)), &x).unwrap();
+            if cs.num_constraints() > before {
+                reductions += 1;
+            }
+            assert!(x.nat.params.max_word.bits() as usize <= params.max_word_bits::<Fr>());
+        }
+        // doublings are free until the limbs fill the headroom
+        assert!(reductions > 0 && reductions < 10);
+        let x = x.reduce(cs.namespace(|| "reduce")).unwrap();
+        let expected_value = (a << 300usize).mod_floor(&params.modulus);
+        assert_eq!(x.nat.value, Some(expected_value));
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_field_inverse_of_zero() {
+        let params = FieldParams::secp256k1_base();
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let zero =
+            FieldElement::alloc(cs.namespace(|| "zero"), || Ok(BigInt::zero()), &params).unwrap();
+        assert!(zero.inverse(cs.namespace(|| "inverse")).is_err());
+    }
+
+    fn equal_to_five(representative: BigInt) -> bool {
+        let params = FieldParams::secp256k1_base();
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let nat = BigNat::alloc_from_nat(
+            cs.namespace(|| "x"),
+            || Ok(representative),
+            params.limb_width,
+            params.n_limbs + 1,
+        )
+        .unwrap();
+        let x = FieldElement {
+            nat,
+            params: params.clone(),
+        };
+        let five =
+            FieldElement::constant::<TestConstraintSystem<Fr>>(&BigInt::from(5), &params).unwrap();
+        x.equal(cs.namespace(|| "x = 5"), &five).unwrap();
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn test_field_equal_congruent() {
+        let p = FieldParams::secp256k1_base().modulus;
+        assert!(equal_to_five(BigInt::from(5)));
+        assert!(equal_to_five(&p + 5));
+        assert!(equal_to_five(&p * 3 + 5));
+        assert!(!equal_to_five(&p + 6));
+        assert!(!equal_to_five(&p - 5));
+    }
+}
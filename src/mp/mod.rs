@@ -1,2 +1,3 @@
 pub mod bignat;
-mod poly;
+pub mod field;
+pub mod poly;
@@ -33,26 +33,34 @@ impl<Scalar: PrimeField> Polynomial<Scalar> {
             acc
         })
     }
+    /// Computes the product of `self` and `other`. The `n + m - 1` product coefficients are
+    /// allocated and the product is checked at as many points.
+    /// If either polynomial is constant, the product is linear and takes no constraints.
     pub fn alloc_product<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+        other: &Self,
+    ) -> Result<Polynomial<Scalar>, SynthesisError> {
+        if let Some(constant) = self.constant_values() {
+            return Ok(other.scale_by_constant(&constant));
+        }
+        if let Some(constant) = other.constant_values() {
+            return Ok(self.scale_by_constant(&constant));
+        }
+        self.alloc_product_by_evaluation(cs, other)
+    }
+
+    fn alloc_product_by_evaluation<CS: ConstraintSystem<Scalar>>(
         &self,
         mut cs: CS,
         other: &Self,
     ) -> Result<Polynomial<Scalar>, SynthesisError> {
         let n_product_coeffs = self.coefficients.len() + other.coefficients.len() - 1;
         let values = self.values.as_ref().and_then(|self_vs| {
-            other.values.as_ref().map(|other_vs| {
-                let mut values: Vec<Scalar> = std::iter::repeat_with(|| Scalar::ZERO)
-                    .take(n_product_coeffs)
-                    .collect();
-                for (self_i, self_v) in self_vs.iter().enumerate() {
-                    for (other_i, other_v) in other_vs.iter().enumerate() {
-                        let mut v = self_v.clone();
-                        v.mul_assign(other_v);
-                        values[self_i + other_i].add_assign(&v);
-                    }
-                }
-                values
-            })
+            other
+                .values
+                .as_ref()
+                .map(|other_vs| product_values(self_vs, other_vs))
         });
         let coefficients = (0..n_product_coeffs)
             .map(|i| {
@@ -99,6 +107,42 @@ impl<Scalar: PrimeField> Polynomial<Scalar> {
         Ok(product)
     }
 
+    /// The coefficients, if they do not depend on any variable
+    fn constant_values(&self) -> Option<Vec<Scalar>> {
+        self.coefficients
+            .iter()
+            .map(|lc| {
+                if lc.iter_aux().next().is_some() {
+                    return None;
+                }
+                lc.iter_inputs().try_fold(
+                    Scalar::ZERO,
+                    |acc, (i, c)| {
+                        if *i == 0 {
+                            Some(acc + c)
+                        } else {
+                            None
+                        }
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn scale_by_constant(&self, constant: &[Scalar]) -> Self {
+        let n_product_coeffs = self.coefficients.len() + constant.len() - 1;
+        let mut coefficients = vec![LinearCombination::zero(); n_product_coeffs];
+        for (i, c) in constant.iter().enumerate() {
+            for (j, lc) in self.coefficients.iter().enumerate() {
+                coefficients[i + j] = coefficients[i + j].clone() + (*c, lc);
+            }
+        }
+        Polynomial {
+            coefficients,
+            values: self.values.as_ref().map(|vs| product_values(constant, vs)),
+        }
+    }
+
     pub fn sum(&self, other: &Self) -> Self {
         let n_coeffs = max(self.coefficients.len(), other.coefficients.len());
         let values = self.values.as_ref().and_then(|self_vs| {
@@ -136,6 +180,17 @@ impl<Scalar: PrimeField> Polynomial<Scalar> {
     }
 }
 
+/// Coefficients of the product of polynomials with coefficients `a` and `b`
+fn product_values<Scalar: PrimeField>(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
+    let mut values = vec![Scalar::ZERO; a.len() + b.len() - 1];
+    for (a_i, a_v) in a.iter().enumerate() {
+        for (b_i, b_v) in b.iter().enumerate() {
+            values[a_i + b_i] += *a_v * b_v;
+        }
+    }
+    values
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,4 +254,80 @@ mod tests {
             eprintln!("Error: {} is unsatisfied", token);
         }
     }
+
+    fn alloc_polynomial<CS: ConstraintSystem<Fr>>(mut cs: CS, values: &[Fr]) -> Polynomial<Fr> {
+        Polynomial {
+            coefficients: values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    LinearCombination::zero()
+                        + cs.alloc(|| format!("coeff {}", i), || Ok(*v)).unwrap()
+                })
+                .collect(),
+            values: Some(values.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_product() {
+        for (n, m) in [
+            (1, 1),
+            (2, 3),
+            (3, 3),
+            (4, 4),
+            (5, 3),
+            (7, 2),
+            (8, 8),
+            (9, 16),
+        ] {
+            let a = (0..n)
+                .map(|i| usize_to_f::<Fr>(7 * i + 3))
+                .collect::<Vec<_>>();
+            let b = (0..m)
+                .map(|i| usize_to_f::<Fr>(1000 - 11 * i))
+                .collect::<Vec<_>>();
+            let expected = product_values(&a, &b);
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let a_poly = alloc_polynomial(cs.namespace(|| "a"), &a);
+            let b_poly = alloc_polynomial(cs.namespace(|| "b"), &b);
+            let product = a_poly
+                .alloc_product(cs.namespace(|| "product"), &b_poly)
+                .unwrap();
+            assert_eq!(product.values.as_ref(), Some(&expected));
+            assert_eq!(product.coefficients.len(), n + m - 1);
+            for (i, (c, v)) in product.coefficients.iter().zip(&expected).enumerate() {
+                cs.enforce(
+                    || format!("expected {}", i),
+                    |lc| lc + c,
+                    |lc| lc + TestConstraintSystem::<Fr>::one(),
+                    |lc| lc + (*v, TestConstraintSystem::<Fr>::one()),
+                );
+            }
+            assert!(cs.is_satisfied(), "product of {} and {} coefficients", n, m);
+            assert_eq!(cs.num_constraints(), 2 * (n + m - 1));
+        }
+    }
+
+    #[test]
+    fn test_constant_product() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = alloc_polynomial(cs.namespace(|| "a"), &[usize_to_f(2), usize_to_f(3)]);
+        let constant = Polynomial {
+            coefficients: [5, 0, 7]
+                .iter()
+                .map(|v| {
+                    LinearCombination::zero()
+                        + (usize_to_f::<Fr>(*v), TestConstraintSystem::<Fr>::one())
+                })
+                .collect(),
+            values: None,
+        };
+        let expected = Some([10, 15, 14, 21].iter().map(|v| usize_to_f(*v)).collect());
+        let left = constant.alloc_product(cs.namespace(|| "left"), &a).unwrap();
+        let right = a.alloc_product(cs.namespace(|| "right"), &constant).unwrap();
+        assert_eq!(left.values, expected);
+        assert_eq!(right.values, expected);
+        assert_eq!(cs.num_constraints(), 0);
+    }
 }
@@ -0,0 +1,189 @@
+//! Proving header chains with Nova, generic over the cycle of curves
+
+use std::fmt::{self, Display, Formatter};
+
+use nova_snark::errors::NovaError;
+use nova_snark::traits::snark::RelaxedR1CSSNARKTrait;
+use nova_snark::{CompressedSNARK, ProverKey, PublicParams, RecursiveSNARK};
+use num_bigint::BigInt;
+
+use crate::btc_validation::header_step::BlockHeader;
+use crate::btc_validation::witness::{max_target, HeaderChainState, WitnessError};
+use crate::verifier::{z0_secondary, C1, C2};
+pub use crate::verifier::{
+    Bn256Grumpkin, CurveCycle, HeaderChainProof, HeaderChainSNARK, HeaderChainVerifierKey,
+    PallasVesta, Scalar,
+};
+
+pub type Params<C> = PublicParams<<C as CurveCycle>::E1, <C as CurveCycle>::E2, C1<C>, C2<C>>;
+
+pub type HeaderChainProverKey<C> = ProverKey<
+    <C as CurveCycle>::E1,
+    <C as CurveCycle>::E2,
+    C1<C>,
+    C2<C>,
+    <C as CurveCycle>::S1,
+    <C as CurveCycle>::S2,
+>;
+
+#[derive(Debug)]
+pub enum ProverError {
+    /// There are no headers to prove
+    Empty,
+    /// The state has another proof of work limit than the parameters
+    PowLimitMismatch,
+    Witness(WitnessError),
+    Nova(NovaError),
+}
+
+impl Display for ProverError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ProverError::Empty => write!(f, "no headers to prove"),
+            ProverError::PowLimitMismatch => write!(f, "state and parameters have different proof of work limits"),
+            ProverError::Witness(e) => write!(f, "invalid header: {}", e),
+            ProverError::Nova(e) => write!(f, "proving failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+impl From<WitnessError> for ProverError {
+    fn from(e: WitnessError) -> Self {
+        ProverError::Witness(e)
+    }
+}
+
+impl From<NovaError> for ProverError {
+    fn from(e: NovaError) -> Self {
+        ProverError::Nova(e)
+    }
+}
+
+/// Public parameters and compressed SNARK keys of the uniform header chain on the cycle `C`
+pub struct HeaderChainProver<C: CurveCycle> {
+    pp: Params<C>,
+    pk: HeaderChainProverKey<C>,
+    vk: HeaderChainVerifierKey<C>,
+    pow_limit: BigInt,
+}
+
+impl<C: CurveCycle> HeaderChainProver<C> {
+    /// Parameters of the mainnet header chain
+    pub fn setup() -> Result<Self, NovaError> {
+        Self::setup_with(max_target())
+    }
+
+    /// Parameters of a header chain whose proof of work limit is `pow_limit`
+    pub fn setup_with(pow_limit: BigInt) -> Result<Self, NovaError> {
+        let pp = PublicParams::setup(
+            &C1::<C>::blank(pow_limit.clone()),
+            &C2::<C>::default(),
+            &*C::S1::ck_floor(),
+            &*C::S2::ck_floor(),
+        )?;
+        let (pk, vk) = CompressedSNARK::setup(&pp)?;
+        Ok(Self { pp, pk, vk, pow_limit })
+    }
+
+    pub fn params(&self) -> &Params<C> {
+        &self.pp
+    }
+
+    pub fn verifier_key(&self) -> &HeaderChainVerifierKey<C> {
+        &self.vk
+    }
+
+    /// Proves that `headers` extend the chain from `state`, and compresses the proof
+    pub fn prove(
+        &self,
+        state: &HeaderChainState<Scalar<C>>,
+        headers: Vec<[u64; 10]>,
+    ) -> Result<HeaderChainProof<C>, ProverError> {
+        if state.pow_limit != self.pow_limit {
+            return Err(ProverError::PowLimitMismatch);
+        }
+        let z0 = state.z();
+        let steps = BlockHeader::new_blocks_from(&mut state.clone(), headers)?;
+        let first = steps.first().ok_or(ProverError::Empty)?;
+
+        let circuit_secondary = C2::<C>::default();
+        let mut recursive_snark = RecursiveSNARK::new(
+            &self.pp,
+            first,
+            &circuit_secondary,
+            &z0,
+            &z0_secondary::<C>(),
+        )?;
+        for step in &steps {
+            recursive_snark.prove_step(&self.pp, step, &circuit_secondary)?;
+        }
+        let snark = CompressedSNARK::prove(&self.pp, &self.pk, &recursive_snark)?;
+        Ok(HeaderChainProof {
+            z0,
+            num_steps: steps.len(),
+            snark,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+
+    use crate::btc_validation::synthetic::{regtest_pow_limit, SyntheticChain};
+    use crate::prover::*;
+    use crate::verifier::{verifier_key_from_bytes, verifier_key_to_bytes};
+
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+    const BLOCK_1: [u64; 10] = [0x010000006fe28c0a, 0xb6f1b372c1a6a246, 0xae63f74f931e8365, 0xe15a089c68d61900, 0x00000000982051fd, 0x1e4ba744bbbe680e, 0x1fee14677ba1a3c3, 0x540bf7b1cdb606e8, 0x57233e0e61bc6649, 0xffff001d01e36299];
+
+    fn prove_and_verify<C: CurveCycle>() {
+        let prover = HeaderChainProver::<C>::setup().unwrap();
+        let state = HeaderChainState::<Scalar<C>>::before_genesis();
+        let proof = prover.prove(&state, vec![GENESIS, BLOCK_1]).unwrap();
+
+        let mut expected = state.clone();
+        BlockHeader::new_blocks_from(&mut expected, vec![GENESIS, BLOCK_1]).unwrap();
+        assert_eq!(proof.verify(prover.verifier_key()).unwrap(), expected.z());
+
+        // the bundle and the verifier key are verified after a round trip through bytes
+        let vk = verifier_key_from_bytes::<C>(&verifier_key_to_bytes::<C>(prover.verifier_key()).unwrap()).unwrap();
+        let bundle = HeaderChainProof::<C>::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert_eq!(bundle.verify(&vk).unwrap(), expected.z());
+
+        // the proof does not hold from another state
+        let mut forged = proof;
+        forged.z0[15] += Scalar::<C>::ONE;
+        assert!(forged.verify(prover.verifier_key()).is_err());
+
+        // headers which do not extend the state are not proven
+        assert!(matches!(prover.prove(&state, vec![BLOCK_1]), Err(ProverError::Witness(WitnessError::PrevHashMismatch))));
+        assert!(matches!(prover.prove(&state, vec![]), Err(ProverError::Empty)));
+    }
+
+    #[test]
+    fn test_regtest_chain() {
+        // a synthetic chain is proven with the parameters of its proof of work limit
+        let mut chain = SyntheticChain::<Scalar<PallasVesta>>::regtest();
+        chain.mine(2, 600);
+        let prover = HeaderChainProver::<PallasVesta>::setup_with(regtest_pow_limit()).unwrap();
+        let proof = prover.prove(&chain.start, chain.headers.clone()).unwrap();
+        assert_eq!(proof.verify(prover.verifier_key()).unwrap(), chain.state.z());
+
+        // a mainnet state is not proven with them
+        let state = HeaderChainState::before_genesis();
+        assert!(matches!(prover.prove(&state, vec![GENESIS]), Err(ProverError::PowLimitMismatch)));
+    }
+
+    #[test]
+    fn test_pallas_vesta() {
+        prove_and_verify::<PallasVesta>();
+    }
+
+    #[test]
+    fn test_bn256_grumpkin() {
+        prove_and_verify::<Bn256Grumpkin>();
+    }
+}
@@ -1,5 +1,5 @@
 // (mostly from franklin-crypto)
-use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::boolean::{AllocatedBit, Boolean};
 use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
 use ff::PrimeField;
 
@@ -186,6 +186,21 @@ impl<Scalar: PrimeField> Bit<Scalar> {
         Self { bit, value }
     }
 
+    /// Allocates a `Boolean` equal to this bit.
+    pub fn alloc_boolean<CS>(&self, mut cs: CS) -> Result<Boolean, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        let b = AllocatedBit::alloc(cs.namespace(|| "boolean"), self.value)?;
+        cs.enforce(
+            || "equal",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + b.get_variable() - &self.bit,
+        );
+        Ok(Boolean::from(b))
+    }
+
     pub fn from_sapling<CS: ConstraintSystem<Scalar>>(b: Boolean) -> Self {
         Self::new(b.lc(CS::one(), Scalar::ONE), b.get_value())
     }
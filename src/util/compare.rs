@@ -0,0 +1,232 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+
+use super::bit::Bit;
+use super::num::Num;
+
+/// Widest operands supported by the comparisons in `Scalar`.
+/// `a - b + 2^n_bits` must not wrap around the field modulus.
+pub fn max_compare_bits<Scalar: PrimeField>() -> usize {
+    Scalar::CAPACITY as usize - 1
+}
+
+/// Enforces `a < 2^n_bits`.
+///
+/// Costs `n_bits` constraints.
+pub fn range_check<Scalar, CS>(cs: CS, a: &Num<Scalar>, n_bits: usize) -> Result<(), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    assert!(
+        n_bits > 0 && n_bits <= Scalar::CAPACITY as usize,
+        "cannot range check to {} bits",
+        n_bits
+    );
+    a.fits_in_bits(cs, n_bits)
+}
+
+/// Returns a bit which is set iff `a >= b`.
+/// Both operands must be known to be less than `2^n_bits`, see `range_check`.
+///
+/// Costs `n_bits + 2` constraints.
+pub fn geq<Scalar, CS>(
+    mut cs: CS,
+    a: &Num<Scalar>,
+    b: &Num<Scalar>,
+    n_bits: usize,
+) -> Result<Bit<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    assert!(
+        n_bits <= max_compare_bits::<Scalar>(),
+        "cannot compare {} bit operands",
+        n_bits
+    );
+    // a - b + 2^n_bits lies in [1, 2^(n_bits + 1)) and has bit n_bits set iff a >= b
+    let shift = Scalar::from(2u64).pow_vartime([n_bits as u64]);
+    let diff = Num::new(
+        a.value.and_then(|a| b.value.map(|b| a - b + shift)),
+        LinearCombination::zero() + &a.num - &b.num + (shift, CS::one()),
+    );
+    let bits = diff.decompose(cs.namespace(|| "difference bits"), n_bits + 1)?;
+    Ok(bits.allocations[n_bits].clone())
+}
+
+/// Returns a bit which is set iff `a < b`.
+/// Both operands must be known to be less than `2^n_bits`, see `range_check`.
+///
+/// Costs `n_bits + 2` constraints.
+pub fn lt<Scalar, CS>(
+    cs: CS,
+    a: &Num<Scalar>,
+    b: &Num<Scalar>,
+    n_bits: usize,
+) -> Result<Bit<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    Ok(geq(cs, a, b, n_bits)?.not::<CS>())
+}
+
+/// Returns a bit which is set iff `a <= b`.
+/// Both operands must be known to be less than `2^n_bits`, see `range_check`.
+///
+/// Costs `n_bits + 2` constraints.
+pub fn leq<Scalar, CS>(
+    cs: CS,
+    a: &Num<Scalar>,
+    b: &Num<Scalar>,
+    n_bits: usize,
+) -> Result<Bit<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    geq(cs, b, a, n_bits)
+}
+
+/// Returns a bit which is set iff `a > b`.
+/// Both operands must be known to be less than `2^n_bits`, see `range_check`.
+///
+/// Costs `n_bits + 2` constraints.
+pub fn gt<Scalar, CS>(
+    cs: CS,
+    a: &Num<Scalar>,
+    b: &Num<Scalar>,
+    n_bits: usize,
+) -> Result<Bit<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    lt(cs, b, a, n_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use num_bigint::BigUint;
+    use quickcheck::TestResult;
+
+    use crate::util::convert::nat_to_f;
+    use ff::Field;
+
+    fn alloc_num(cs: &mut TestConstraintSystem<Fr>, name: &str, v: Fr) -> Num<Fr> {
+        Num::alloc(cs.namespace(|| name), || Ok(v)).unwrap()
+    }
+
+    fn compare(a: Fr, b: Fr, n_bits: usize) -> (bool, bool, bool, bool, bool) {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = alloc_num(&mut cs, "a", a);
+        let b = alloc_num(&mut cs, "b", b);
+        (
+            lt(cs.namespace(|| "lt"), &a, &b, n_bits)
+                .unwrap()
+                .value
+                .unwrap(),
+            leq(cs.namespace(|| "leq"), &a, &b, n_bits)
+                .unwrap()
+                .value
+                .unwrap(),
+            gt(cs.namespace(|| "gt"), &a, &b, n_bits)
+                .unwrap()
+                .value
+                .unwrap(),
+            geq(cs.namespace(|| "geq"), &a, &b, n_bits)
+                .unwrap()
+                .value
+                .unwrap(),
+            cs.is_satisfied(),
+        )
+    }
+
+    #[test]
+    fn test_compare_small() {
+        let cases = [
+            (11u64, 14u64),
+            (14, 11),
+            (7, 7),
+            (0, 0),
+            (0, u32::MAX as u64),
+        ];
+        for (a, b) in cases {
+            let (lt, leq, gt, geq, sat) = compare(Fr::from(a), Fr::from(b), 32);
+            assert!(sat);
+            assert_eq!((lt, leq, gt, geq), (a < b, a <= b, a > b, a >= b));
+        }
+    }
+
+    #[quickcheck]
+    fn test_compare_wide(a: Vec<u8>, b: Vec<u8>) -> TestResult {
+        let n_bits = max_compare_bits::<Fr>();
+        let a = BigUint::from_bytes_le(&a) % (BigUint::from(1u8) << n_bits);
+        let b = BigUint::from_bytes_le(&b) % (BigUint::from(1u8) << n_bits);
+        let (lt, leq, gt, geq, sat) = compare(
+            nat_to_f(&a.clone().into()).unwrap(),
+            nat_to_f(&b.clone().into()).unwrap(),
+            n_bits,
+        );
+        TestResult::from_bool(sat && (lt, leq, gt, geq) == (a < b, a <= b, a > b, a >= b))
+    }
+
+    #[test]
+    fn test_compare_extremes() {
+        let n_bits = max_compare_bits::<Fr>();
+        assert_eq!(n_bits, 253);
+        let max = Fr::from(2u64).pow_vartime([n_bits as u64]) - Fr::from(1u64);
+        assert_eq!(
+            compare(Fr::from(0u64), max, n_bits),
+            (true, true, false, false, true)
+        );
+        assert_eq!(
+            compare(max, Fr::from(0u64), n_bits),
+            (false, false, true, true, true)
+        );
+        assert_eq!(compare(max, max, n_bits), (false, true, false, true, true));
+    }
+
+    #[test]
+    fn test_compare_constraints() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = alloc_num(&mut cs, "a", Fr::from(3u64));
+        let b = alloc_num(&mut cs, "b", Fr::from(5u64));
+        range_check(cs.namespace(|| "range a"), &a, 64).unwrap();
+        assert_eq!(cs.num_constraints(), 64);
+        lt(cs.namespace(|| "lt"), &a, &b, 64).unwrap();
+        assert_eq!(cs.num_constraints(), 64 + 66);
+    }
+
+    #[test]
+    fn test_range_check() {
+        for (v, n_bits, ok) in [
+            (255u64, 8, true),
+            (256, 8, false),
+            (0, 1, true),
+            (2, 1, false),
+        ] {
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let a = alloc_num(&mut cs, "a", Fr::from(v));
+            range_check(cs.namespace(|| "range"), &a, n_bits).unwrap();
+            assert_eq!(cs.is_satisfied(), ok);
+        }
+    }
+
+    #[test]
+    fn test_compare_forged_bit() {
+        // The top difference bit cannot be flipped without breaking the decomposition
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let a = alloc_num(&mut cs, "a", Fr::from(3u64));
+        let b = alloc_num(&mut cs, "b", Fr::from(5u64));
+        let r = lt(cs.namespace(|| "lt"), &a, &b, 8).unwrap();
+        assert!(r.value.unwrap());
+        assert!(cs.is_satisfied());
+        cs.set("lt/difference bits/bit8/boolean", Fr::from(1u64));
+        assert!(!cs.is_satisfied());
+    }
+}
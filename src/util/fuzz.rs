@@ -0,0 +1,659 @@
+//! Soundness fuzzing of gadgets with a malicious witness generator.
+//!
+//! A gadget is synthesized into a `FuzzingConstraintSystem`, which keeps a `TestConstraintSystem`
+//! with the honest witness and records the constraints. `fuzz` then changes one allocated value
+//! at a time and repairs the constraints this breaks, solving each for another variable, the way
+//! a cheating prover would. Whenever the repaired witness satisfies every constraint, as checked
+//! by the `TestConstraintSystem`, but changes one of the declared outputs, the gadget is
+//! under-constrained and the mutation is reported.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::num::AllocatedNum;
+use bellpepper_core::test_cs::TestConstraintSystem;
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use crate::mp::bignat::BigNat;
+use crate::util::convert::f_to_nat;
+
+/// Most constraints repaired after a single mutation
+const MAX_REPAIRS: usize = 10_000;
+
+type Constraint<Scalar> = (
+    LinearCombination<Scalar>,
+    LinearCombination<Scalar>,
+    LinearCombination<Scalar>,
+);
+
+/// A witness which satisfies the constraints of a gadget but changes its outputs
+#[derive(Clone, Debug)]
+pub struct Finding<Scalar: PrimeField> {
+    /// Path of the mutated variable
+    pub mutated: String,
+    pub value: Scalar,
+    /// Paths of the variables changed to satisfy the constraints again
+    pub repaired: Vec<String>,
+    /// Names of the changed outputs
+    pub outputs: Vec<String>,
+}
+
+impl<Scalar: PrimeField> Display for Finding<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "setting `{}` to {:?} and repairing {} variables changes {}",
+            self.mutated,
+            self.value,
+            self.repaired.len(),
+            self.outputs.join(", ")
+        )
+    }
+}
+
+/// A constraint system which records a gadget for `fuzz`.
+/// Witness values must be given, since mutations start from the honest witness.
+pub struct FuzzingConstraintSystem<Scalar: PrimeField> {
+    test_cs: TestConstraintSystem<Scalar>,
+    current_namespace: Vec<String>,
+    aux_paths: Vec<String>,
+    constraints: Vec<Constraint<Scalar>>,
+    outputs: Vec<(String, LinearCombination<Scalar>)>,
+    fixed: Vec<String>,
+}
+
+impl<Scalar: PrimeField> FuzzingConstraintSystem<Scalar> {
+    pub fn new() -> Self {
+        FuzzingConstraintSystem {
+            test_cs: TestConstraintSystem::new(),
+            current_namespace: vec![],
+            aux_paths: vec![],
+            constraints: vec![],
+            outputs: vec![],
+            fixed: vec![],
+        }
+    }
+
+    /// The constraint system holding the honest witness
+    pub fn test_cs(&self) -> &TestConstraintSystem<Scalar> {
+        &self.test_cs
+    }
+
+    /// Declares `lc` as an output of the gadget
+    pub fn output(&mut self, name: &str, lc: LinearCombination<Scalar>) {
+        self.outputs.push((name.to_string(), lc));
+    }
+
+    pub fn output_num(&mut self, name: &str, num: &AllocatedNum<Scalar>) {
+        self.output(name, LinearCombination::zero() + num.get_variable());
+    }
+
+    pub fn output_boolean(&mut self, name: &str, bit: &Boolean) {
+        self.output(name, bit.lc(Self::one(), Scalar::ONE));
+    }
+
+    /// Declares every limb of `nat` as an output
+    pub fn output_bignat(&mut self, name: &str, nat: &BigNat<Scalar>) {
+        for (i, limb) in nat.limbs.iter().enumerate() {
+            self.output(&format!("{} limb {}", name, i), limb.clone());
+        }
+    }
+
+    /// Marks the variables at `path` and below as inputs of the gadget, which are never mutated.
+    /// Variables allocated with `alloc_input` are always inputs.
+    pub fn fix(&mut self, path: &str) {
+        self.fixed.push(path.to_string());
+    }
+
+    /// Panics with the findings of `fuzz`, if there are any
+    pub fn assert_sound(&mut self) {
+        self.assert_sound_where(|_| true)
+    }
+
+    /// Panics with the findings of `fuzz_where`, if there are any
+    pub fn assert_sound_where<T: Fn(&str) -> bool>(&mut self, targets: T) {
+        let findings = self.fuzz_where(targets);
+        assert!(
+            findings.is_empty(),
+            "{} mutations change the outputs, e.g. {}",
+            findings.len(),
+            findings
+                .iter()
+                .take(3)
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+
+    /// Mutates every variable which is not an input, see `fuzz_where`
+    pub fn fuzz(&mut self) -> Vec<Finding<Scalar>> {
+        self.fuzz_where(|_| true)
+    }
+
+    /// Mutates every variable which is not an input and whose path satisfies `targets`,
+    /// and returns the mutations which change an output.
+    ///
+    /// Bits are flipped, other values are incremented, decremented, doubled, zeroed and set to one.
+    /// Every unsatisfied constraint is then solved for a variable which has not been changed yet,
+    /// or for a group of bits whose weights are distinct powers of two, preferring the variables
+    /// allocated after the mutated one and the latest of those.
+    /// The search is not exhaustive, so an empty result is evidence, not a proof, of soundness.
+    pub fn fuzz_where<T: Fn(&str) -> bool>(&mut self, targets: T) -> Vec<Finding<Scalar>> {
+        assert!(
+            self.test_cs.which_is_unsatisfied().is_none(),
+            "the honest witness is rejected at {:?}",
+            self.test_cs.which_is_unsatisfied()
+        );
+        let inputs = self.test_cs.scalar_inputs();
+        let honest = self.test_cs.scalar_aux();
+
+        let mut uses = vec![vec![]; honest.len()];
+        let mut is_bit = vec![false; honest.len()];
+        for (i, (a, b, c)) in self.constraints.iter().enumerate() {
+            let mut vars = HashSet::new();
+            for (var, _) in a.iter().chain(b.iter()).chain(c.iter()) {
+                if let Index::Aux(j) = var.get_unchecked() {
+                    vars.insert(j);
+                }
+            }
+            // A boolean constraint b * (1 - b) = 0 has a single variable, in both factors
+            if let [j] = vars.iter().copied().collect::<Vec<_>>()[..] {
+                if !coefficient(&coefficients(a), j).is_zero_vartime()
+                    && !coefficient(&coefficients(b), j).is_zero_vartime()
+                {
+                    is_bit[j] = honest[j] == Scalar::ZERO || honest[j] == Scalar::ONE;
+                }
+            }
+            for j in vars {
+                uses[j].push(i);
+            }
+        }
+        let fixed = self
+            .aux_paths
+            .iter()
+            .map(|path| {
+                self.fixed
+                    .iter()
+                    .any(|f| path == f || path.starts_with(&format!("{}/", f)))
+            })
+            .collect::<Vec<_>>();
+        let outputs = self.eval_outputs(&inputs, &honest);
+
+        let mut findings = vec![];
+        let mut aux = honest.clone();
+        for x in 0..honest.len() {
+            if fixed[x] || !targets(&self.aux_paths[x]) {
+                continue;
+            }
+            let v = honest[x];
+            let values = if is_bit[x] {
+                vec![Scalar::ONE - v]
+            } else {
+                vec![
+                    v + Scalar::ONE,
+                    v - Scalar::ONE,
+                    v.double(),
+                    Scalar::ZERO,
+                    Scalar::ONE,
+                ]
+            };
+            let mut candidates = vec![];
+            for value in values {
+                if value != v && !candidates.contains(&value) {
+                    candidates.push(value);
+                }
+            }
+            for value in candidates {
+                let mut repair = Repair {
+                    frozen: HashSet::new(),
+                    touched: vec![],
+                };
+                let repaired = self.repair(
+                    &mut repair,
+                    &inputs,
+                    &mut aux,
+                    x,
+                    value,
+                    &uses,
+                    &is_bit,
+                    &fixed,
+                );
+                if repaired {
+                    let changed = self
+                        .eval_outputs(&inputs, &aux)
+                        .into_iter()
+                        .zip(&outputs)
+                        .zip(&self.outputs)
+                        .filter(|((new, old), _)| new != *old)
+                        .map(|(_, (name, _))| name.clone())
+                        .collect::<Vec<_>>();
+                    if !changed.is_empty() {
+                        self.confirm(&repair.touched, &aux, &honest);
+                        findings.push(Finding {
+                            mutated: self.aux_paths[x].clone(),
+                            value,
+                            repaired: repair.touched[1..]
+                                .iter()
+                                .map(|&i| self.aux_paths[i].clone())
+                                .collect(),
+                            outputs: changed,
+                        });
+                    }
+                }
+                for i in repair.touched {
+                    aux[i] = honest[i];
+                }
+            }
+        }
+        findings
+    }
+
+    /// Sets `aux[x]` to `value` and solves the constraints this breaks.
+    /// Returns whether every constraint is satisfied again.
+    #[allow(clippy::too_many_arguments)]
+    fn repair(
+        &self,
+        repair: &mut Repair,
+        inputs: &[Scalar],
+        aux: &mut [Scalar],
+        x: usize,
+        value: Scalar,
+        uses: &[Vec<usize>],
+        is_bit: &[bool],
+        fixed: &[bool],
+    ) -> bool {
+        let mut queue = VecDeque::new();
+        let mut solved = vec![(x, value)];
+        for _ in 0..MAX_REPAIRS {
+            for (y, v) in solved.drain(..) {
+                aux[y] = v;
+                repair.frozen.insert(y);
+                repair.touched.push(y);
+                queue.extend(uses[y].iter().copied());
+            }
+            let next = loop {
+                match queue.pop_front() {
+                    Some(i) if self.is_satisfied(i, inputs, aux) => continue,
+                    next => break next,
+                }
+            };
+            let i = match next {
+                Some(i) => i,
+                None => return true,
+            };
+            // Prefer the variables computed after the mutated one, as a cheating prover would
+            let candidates = self.candidates(i, &repair.frozen, fixed);
+            let (later, earlier): (Vec<_>, Vec<_>) =
+                candidates.iter().copied().partition(|&y| y > x);
+            let solution = self
+                .solve_single(i, inputs, aux, &later, is_bit)
+                .map(|s| vec![s])
+                .or_else(|| self.solve_bits(i, inputs, aux, &later, is_bit))
+                .or_else(|| {
+                    self.solve_single(i, inputs, aux, &earlier, is_bit)
+                        .map(|s| vec![s])
+                })
+                .or_else(|| self.solve_bits(i, inputs, aux, &candidates, is_bit));
+            match solution {
+                Some(s) => solved = s,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    fn is_satisfied(&self, i: usize, inputs: &[Scalar], aux: &[Scalar]) -> bool {
+        let (a, b, c) = &self.constraints[i];
+        a.eval(inputs, aux) * b.eval(inputs, aux) == c.eval(inputs, aux)
+    }
+
+    /// Variables of constraint `i` which may still be changed, the latest first
+    fn candidates(&self, i: usize, frozen: &HashSet<usize>, fixed: &[bool]) -> Vec<usize> {
+        let (a, b, c) = &self.constraints[i];
+        let mut vars = a
+            .iter()
+            .chain(b.iter())
+            .chain(c.iter())
+            .filter_map(|(var, _)| match var.get_unchecked() {
+                Index::Aux(j) if !frozen.contains(&j) && !fixed[j] => Some(j),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        vars.sort_unstable_by(|x, y| y.cmp(x));
+        vars.dedup();
+        vars
+    }
+
+    /// Solves constraint `i` for the latest candidate which is not a bit and occurs linearly,
+    /// or in both factors of a product which must be zero
+    fn solve_single(
+        &self,
+        i: usize,
+        inputs: &[Scalar],
+        aux: &[Scalar],
+        candidates: &[usize],
+        is_bit: &[bool],
+    ) -> Option<(usize, Scalar)> {
+        let (a, b, c) = &self.constraints[i];
+        let (a_val, b_val, c_val) = (
+            a.eval(inputs, aux),
+            b.eval(inputs, aux),
+            c.eval(inputs, aux),
+        );
+        let (a_k, b_k, c_k) = (coefficients(a), coefficients(b), coefficients(c));
+        candidates.iter().filter(|&&y| !is_bit[y]).find_map(|&y| {
+            let (a_y, b_y, c_y) = (
+                coefficient(&a_k, y),
+                coefficient(&b_k, y),
+                coefficient(&c_k, y),
+            );
+            // (a0 + a_y t) (b0 + b_y t) = c0 + c_y t
+            let a0 = a_val - a_y * aux[y];
+            let b0 = b_val - b_y * aux[y];
+            let c0 = c_val - c_y * aux[y];
+            let slope = a_y * b0 + b_y * a0 - c_y;
+            if !(a_y * b_y).is_zero_vartime() {
+                // a * b = 0: zero the first factor
+                return (c_y.is_zero_vartime() && c0.is_zero_vartime())
+                    .then(|| (y, -a0 * a_y.invert().unwrap()));
+            }
+            if slope.is_zero_vartime() {
+                return None;
+            }
+            Some((y, (c0 - a0 * b0) * slope.invert().unwrap()))
+        })
+    }
+
+    /// Solves constraint `i` for the candidate bits, if it is linear in them and their weights
+    /// are distinct powers of two of the same sign, as in a bit decomposition.
+    /// Other candidates keep their values.
+    fn solve_bits(
+        &self,
+        i: usize,
+        inputs: &[Scalar],
+        aux: &[Scalar],
+        candidates: &[usize],
+        is_bit: &[bool],
+    ) -> Option<Vec<(usize, Scalar)>> {
+        let (a, b, c) = &self.constraints[i];
+        let bits = candidates
+            .iter()
+            .copied()
+            .filter(|&y| is_bit[y])
+            .collect::<Vec<_>>();
+        if bits.is_empty() {
+            return None;
+        }
+        let bit_coefficients = |lc: &LinearCombination<Scalar>| {
+            let k = coefficients(lc);
+            bits.iter().map(|&y| coefficient(&k, y)).collect::<Vec<_>>()
+        };
+        let (a_k, b_k, c_k) = (
+            bit_coefficients(a),
+            bit_coefficients(b),
+            bit_coefficients(c),
+        );
+        let without_bits = |lc: &LinearCombination<Scalar>, k: &[Scalar]| {
+            bits.iter()
+                .zip(k)
+                .fold(lc.eval(inputs, aux), |acc, (&y, k)| acc - *k * aux[y])
+        };
+        let (a0, b0, c0) = (
+            without_bits(a, &a_k),
+            without_bits(b, &b_k),
+            without_bits(c, &c_k),
+        );
+        let in_a = a_k.iter().any(|k| !k.is_zero_vartime());
+        let in_b = b_k.iter().any(|k| !k.is_zero_vartime());
+        // Linear equations sum(w_j * bit_j) = s which imply the constraint
+        let equations = match (in_a, in_b) {
+            (false, false) => vec![(c_k.iter().map(|k| -*k).collect::<Vec<_>>(), c0 - a0 * b0)],
+            (true, false) if bool::from(b0.invert().is_some()) => {
+                let b_inv = b0.invert().unwrap();
+                vec![(
+                    a_k.iter().zip(&c_k).map(|(a, c)| *a - *c * b_inv).collect(),
+                    c0 * b_inv - a0,
+                )]
+            }
+            (false, true) if bool::from(a0.invert().is_some()) => {
+                let a_inv = a0.invert().unwrap();
+                vec![(
+                    b_k.iter().zip(&c_k).map(|(b, c)| *b - *c * a_inv).collect(),
+                    c0 * a_inv - b0,
+                )]
+            }
+            // a * b = 0, as in the last bit of a range check: zero either factor
+            (true, true) if c0.is_zero_vartime() && c_k.iter().all(|k| k.is_zero_vartime()) => {
+                vec![(a_k, -a0), (b_k, -b0)]
+            }
+            _ => return None,
+        };
+        equations
+            .iter()
+            .find_map(|(weights, sum)| solve_weighted_bits(&bits, weights, *sum))
+    }
+
+    /// Checks a repaired witness against the `TestConstraintSystem`
+    fn confirm(&mut self, touched: &[usize], aux: &[Scalar], honest: &[Scalar]) {
+        for &i in touched {
+            self.test_cs.set(&self.aux_paths[i], aux[i]);
+        }
+        let unsatisfied = self.test_cs.which_is_unsatisfied().map(String::from);
+        for &i in touched {
+            self.test_cs.set(&self.aux_paths[i], honest[i]);
+        }
+        assert_eq!(unsatisfied, None, "a repaired witness is rejected");
+    }
+
+    fn eval_outputs(&self, inputs: &[Scalar], aux: &[Scalar]) -> Vec<Scalar> {
+        self.outputs
+            .iter()
+            .map(|(_, lc)| lc.eval(inputs, aux))
+            .collect()
+    }
+}
+
+impl<Scalar: PrimeField> Default for FuzzingConstraintSystem<Scalar> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Variables changed by a repair, in order, starting with the mutated one
+struct Repair {
+    frozen: HashSet<usize>,
+    touched: Vec<usize>,
+}
+
+/// The coefficients of the auxiliary variables of `lc`
+fn coefficients<Scalar: PrimeField>(lc: &LinearCombination<Scalar>) -> HashMap<usize, Scalar> {
+    let mut k = HashMap::new();
+    for (j, c) in lc.iter_aux() {
+        *k.entry(*j).or_insert(Scalar::ZERO) += c;
+    }
+    k
+}
+
+fn coefficient<Scalar: PrimeField>(coefficients: &HashMap<usize, Scalar>, j: usize) -> Scalar {
+    coefficients.get(&j).copied().unwrap_or(Scalar::ZERO)
+}
+
+/// Bits `b_j` with `sum(w_j * b_j) = sum`, if the nonzero weights `w_j` are distinct powers of two
+/// of the same sign. Bits of weight zero keep their values.
+fn solve_weighted_bits<Scalar: PrimeField>(
+    bits: &[usize],
+    weights: &[Scalar],
+    sum: Scalar,
+) -> Option<Vec<(usize, Scalar)>> {
+    let weighted = bits
+        .iter()
+        .zip(weights)
+        .filter(|(_, w)| !w.is_zero_vartime())
+        .collect::<Vec<_>>();
+    [Scalar::ONE, -Scalar::ONE].iter().find_map(|sign| {
+        let exponents = weighted
+            .iter()
+            .map(|(_, w)| power_of_two(&f_to_nat(&(**w * sign))))
+            .collect::<Option<Vec<_>>>()?;
+        if exponents.iter().collect::<HashSet<_>>().len() != exponents.len() {
+            return None;
+        }
+        let target = f_to_nat(&(sum * sign));
+        let covered = exponents
+            .iter()
+            .filter(|&&e| target.bit(e))
+            .fold(BigInt::zero(), |acc, &e| acc | (BigInt::one() << e));
+        (covered == target).then(|| {
+            weighted
+                .iter()
+                .zip(&exponents)
+                .map(|((&y, _), &e)| {
+                    (
+                        y,
+                        if target.bit(e) {
+                            Scalar::ONE
+                        } else {
+                            Scalar::ZERO
+                        },
+                    )
+                })
+                .collect()
+        })
+    })
+}
+
+/// The exponent of `n`, if it is a power of two
+fn power_of_two(n: &BigInt) -> Option<u64> {
+    let e = n.trailing_zeros()?;
+    (n == &(BigInt::one() << e)).then_some(e)
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for FuzzingConstraintSystem<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let annotation = annotation().into();
+        let var = self.test_cs.alloc(|| annotation.clone(), f)?;
+        let mut path = self.current_namespace.clone();
+        path.push(annotation);
+        self.aux_paths.push(path.join("/"));
+        Ok(var)
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.test_cs.alloc_input(annotation, f)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+        self.test_cs
+            .enforce(annotation, |_| a.clone(), |_| b.clone(), |_| c.clone());
+        self.constraints.push((a, b, c));
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        let name = name_fn().into();
+        self.test_cs.push_namespace(|| name.clone());
+        self.current_namespace.push(name);
+    }
+
+    fn pop_namespace(&mut self) {
+        self.test_cs.pop_namespace();
+        self.current_namespace.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::boolean::AllocatedBit;
+
+    #[test]
+    fn test_fuzz() {
+        // x * y = z, with z declared and y free: z can be changed by changing y
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(Fr::from(3u64))).unwrap();
+        let y = AllocatedNum::alloc(cs.namespace(|| "y"), || Ok(Fr::from(5u64))).unwrap();
+        let z = x.mul(cs.namespace(|| "z"), &y).unwrap();
+        cs.fix("x");
+        cs.output_num("z", &z);
+        let findings = cs.fuzz();
+        assert!(findings
+            .iter()
+            .any(|f| f.mutated == "y/num" && f.repaired == ["z/product num"]));
+        assert!(findings.iter().all(|f| f.outputs == ["z"]));
+
+        // z is determined once y is an input too
+        cs.fix("y");
+        cs.assert_sound();
+    }
+
+    #[test]
+    fn test_fuzz_bits() {
+        // a number and its unchecked bits: the bits are repaired through the packing constraint
+        let mut cs = FuzzingConstraintSystem::<Fr>::new();
+        let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(Fr::from(6u64))).unwrap();
+        let bits = (0..4)
+            .map(|i| {
+                AllocatedBit::alloc(
+                    cs.namespace(|| format!("bit {}", i)),
+                    Some((6 >> i) & 1 == 1),
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        cs.enforce(
+            || "packing",
+            |lc| {
+                bits.iter()
+                    .enumerate()
+                    .fold(lc, |lc, (i, b)| lc + (Fr::from(1 << i), b.get_variable()))
+            },
+            |lc| lc + TestConstraintSystem::<Fr>::one(),
+            |lc| lc + x.get_variable(),
+        );
+        cs.output_boolean("bit 3", &Boolean::from(bits[3].clone()));
+        // x = 7 is repaired by decomposing it again, x = 14 by setting bit 3
+        let findings = cs.fuzz();
+        assert!(findings.iter().all(|f| f.outputs == ["bit 3"]));
+        assert!(findings
+            .iter()
+            .any(|f| f.mutated == "bit 3/boolean" && f.repaired == ["x/num"]));
+
+        cs.fix("x");
+        cs.assert_sound();
+    }
+}
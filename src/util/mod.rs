@@ -1,8 +1,14 @@
 pub mod bit;
+pub mod compare;
 pub mod convert;
+#[cfg(test)]
+pub mod fuzz;
 pub mod gadget;
 pub mod lazy;
 pub mod num;
+pub mod pack;
+pub mod poseidon;
+pub mod profile;
 pub mod scalar;
 
 #[cfg(test)]
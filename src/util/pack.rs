@@ -0,0 +1,251 @@
+use bellpepper::gadgets::boolean::{AllocatedBit, Boolean};
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+
+use super::bit::{Bit, Bitvector};
+use super::num::Num;
+use crate::mp::bignat::BigNat;
+use crate::BitAccess;
+
+/// Order in which a slice of bits lists the bits of a number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Least significant bit first
+    LsbFirst,
+    /// Most significant bit first, as in big-endian byte strings
+    MsbFirst,
+    /// Little-endian bytes, each with its most significant bit first.
+    /// This is how hash digests and header fields are read as numbers.
+    LeBytes,
+}
+
+impl BitOrder {
+    /// Power of two carried by bit `i` of a slice of `len` bits
+    pub fn weight(&self, i: usize, len: usize) -> usize {
+        match self {
+            BitOrder::LsbFirst => i,
+            BitOrder::MsbFirst => len - 1 - i,
+            BitOrder::LeBytes => {
+                assert_eq!(len % 8, 0, "bit slice is not made of whole bytes");
+                8 * (i / 8) + 7 - i % 8
+            }
+        }
+    }
+}
+
+/// The number with the bits `bits`, as a linear combination of them.
+/// Nothing is allocated, and the packing is injective for up to `CAPACITY` bits only:
+/// wider values must be bounded by other constraints.
+pub fn pack_num<Scalar, CS>(bits: &[Boolean], order: BitOrder) -> Num<Scalar>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let two = Scalar::from(2u64);
+    let mut num = LinearCombination::zero();
+    let mut value = Some(Scalar::ZERO);
+    for (i, b) in bits.iter().enumerate() {
+        let weight = two.pow_vartime([order.weight(i, bits.len()) as u64]);
+        num = num + &b.lc(CS::one(), weight);
+        value = value.and_then(|v| b.get_value().map(|b| if b { v + weight } else { v }));
+    }
+    Num::new(value, num)
+}
+
+/// The number with the bits `bits`, in limbs of `limb_width` bits.
+/// Nothing is allocated and the limbs are well formed.
+pub fn pack_bignat<Scalar, CS>(bits: &[Boolean], order: BitOrder, limb_width: usize) -> BigNat<Scalar>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let mut lsb_first = vec![Bit::new_false::<CS>(); bits.len()];
+    for (i, b) in bits.iter().enumerate() {
+        lsb_first[order.weight(i, bits.len())] = Bit::from_sapling::<CS>(b.clone());
+    }
+    BigNat::recompose(&Bitvector::from_bits(lsb_first), limb_width)
+}
+
+/// The `n_bits` bits of `num` in the order `order`.
+/// Enforces that `num < 2^n_bits`, which must be at most `CAPACITY`.
+///
+/// Costs `n_bits + 1` constraints.
+pub fn unpack_num<Scalar, CS>(
+    mut cs: CS,
+    num: &Num<Scalar>,
+    n_bits: usize,
+    order: BitOrder,
+) -> Result<Vec<Boolean>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    assert!(n_bits <= Scalar::CAPACITY as usize, "cannot unpack {} bits", n_bits);
+    let lsb_first = (0..n_bits)
+        .map(|i| {
+            AllocatedBit::alloc(
+                cs.namespace(|| format!("bit {}", i)),
+                num.value.and_then(|v| v.get_bit(i)),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    cs.enforce(
+        || "packing",
+        |lc| {
+            let mut lc = lc;
+            let mut weight = Scalar::ONE;
+            for b in &lsb_first {
+                lc = lc + (weight, b.get_variable());
+                weight = weight.double();
+            }
+            lc
+        },
+        |lc| lc + CS::one(),
+        |lc| lc + &num.num,
+    );
+    Ok((0..n_bits)
+        .map(|i| Boolean::from(lsb_first[order.weight(i, n_bits)].clone()))
+        .collect())
+}
+
+/// The bits of all the limbs of `n` in the order `order`.
+/// Enforces that `n` is well formed.
+///
+/// Costs `limb_width + 1` constraints per limb.
+pub fn unpack_bignat<Scalar, CS>(
+    mut cs: CS,
+    n: &BigNat<Scalar>,
+    order: BitOrder,
+) -> Result<Vec<Boolean>, SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+{
+    let limb_width = n.params.limb_width;
+    let mut lsb_first = Vec::with_capacity(limb_width * n.limbs.len());
+    for (i, limb) in n.limbs.iter().enumerate() {
+        let limb = Num::new(n.limb_values.as_ref().map(|vs| vs[i]), limb.clone());
+        lsb_first.extend(unpack_num(
+            cs.namespace(|| format!("limb {}", i)),
+            &limb,
+            limb_width,
+            BitOrder::LsbFirst,
+        )?);
+    }
+    let len = lsb_first.len();
+    Ok((0..len)
+        .map(|i| lsb_first[order.weight(i, len)].clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use num_bigint::{BigInt, Sign};
+    use quickcheck::TestResult;
+
+    use crate::util::convert::{f_to_nat, nat_to_f};
+
+    const ORDERS: [BitOrder; 3] = [BitOrder::LsbFirst, BitOrder::MsbFirst, BitOrder::LeBytes];
+
+    // `bytes` as a bit string in the order `order`, and its value
+    fn bits_of(bytes: &[u8], order: BitOrder) -> (Vec<bool>, BigInt) {
+        let bits = match order {
+            BitOrder::LsbFirst => bytes
+                .iter()
+                .flat_map(|b| (0..8).map(move |i| (b >> i) & 1 == 1))
+                .collect(),
+            BitOrder::MsbFirst | BitOrder::LeBytes => bytes
+                .iter()
+                .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+                .collect(),
+        };
+        let value = match order {
+            BitOrder::MsbFirst => BigInt::from_bytes_be(Sign::Plus, bytes),
+            _ => BigInt::from_bytes_le(Sign::Plus, bytes),
+        };
+        (bits, value)
+    }
+
+    fn alloc_booleans(cs: &mut TestConstraintSystem<Fr>, bits: &[bool]) -> Vec<Boolean> {
+        bits.iter()
+            .enumerate()
+            .map(|(i, b)| {
+                Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("in {}", i)), Some(*b)).unwrap())
+            })
+            .collect()
+    }
+
+    #[quickcheck]
+    fn pack_num_round_trip(bytes: Vec<u8>) -> TestResult {
+        if bytes.is_empty() || bytes.len() > 31 {
+            return TestResult::discard();
+        }
+        for order in ORDERS {
+            let (bits, value) = bits_of(&bytes, order);
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let booleans = alloc_booleans(&mut cs, &bits);
+            let num = pack_num::<Fr, TestConstraintSystem<Fr>>(&booleans, order);
+            let unpacked = unpack_num(cs.namespace(|| "unpack"), &num, bits.len(), order).unwrap();
+            let ok = cs.is_satisfied()
+                && num.value.map(|v| f_to_nat(&v)) == Some(value)
+                && unpacked.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>() == bits;
+            if !ok {
+                return TestResult::failed();
+            }
+        }
+        TestResult::passed()
+    }
+
+    #[quickcheck]
+    fn pack_bignat_round_trip(bytes: Vec<u8>, limb_width: u8) -> TestResult {
+        let limb_width = limb_width as usize;
+        // Unpacking yields whole limbs, so only widths made of whole limbs round trip
+        if bytes.is_empty()
+            || !(8..=128).contains(&limb_width)
+            || !limb_width.is_multiple_of(8)
+            || !(8 * bytes.len()).is_multiple_of(limb_width)
+        {
+            return TestResult::discard();
+        }
+        for order in ORDERS {
+            let (bits, value) = bits_of(&bytes, order);
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let booleans = alloc_booleans(&mut cs, &bits);
+            let n = pack_bignat::<Fr, TestConstraintSystem<Fr>>(&booleans, order, limb_width);
+            let unpacked = unpack_bignat(cs.namespace(|| "unpack"), &n, order).unwrap();
+            let ok = cs.is_satisfied()
+                && n.value == Some(value)
+                && unpacked.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>() == bits;
+            if !ok {
+                return TestResult::failed();
+            }
+        }
+        TestResult::passed()
+    }
+
+    #[test]
+    fn test_unpack_out_of_range() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let num = Num::alloc(cs.namespace(|| "num"), || Ok(Fr::from(256u64))).unwrap();
+        unpack_num(cs.namespace(|| "unpack"), &num, 8, BitOrder::LsbFirst).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_unpack_forged_bit() {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let num = Num::alloc(cs.namespace(|| "num"), || Ok(nat_to_f(&BigInt::from(0x1234u64)).unwrap())).unwrap();
+        let bits = unpack_num(cs.namespace(|| "unpack"), &num, 16, BitOrder::LeBytes).unwrap();
+        // 0x1234 is read from the bytes 34 12
+        assert_eq!(
+            bits.iter().map(|b| b.get_value().unwrap() as u8).collect::<Vec<_>>(),
+            vec![0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0]
+        );
+        assert!(cs.is_satisfied());
+        cs.set("unpack/bit 0/boolean", Fr::from(1u64));
+        assert!(!cs.is_satisfied());
+    }
+}
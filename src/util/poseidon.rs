@@ -0,0 +1,51 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use ff::PrimeField;
+use neptune::poseidon::{Arity, PoseidonConstants};
+
+type ConstantsCache = Mutex<HashMap<(TypeId, bool), Arc<dyn Any + Send + Sync>>>;
+
+fn cached_constants<F, A>(constant_length: bool) -> Arc<PoseidonConstants<F, A>>
+where
+    F: PrimeField,
+    A: Arity<F> + Send + Sync + 'static,
+{
+    static CACHE: OnceLock<ConstantsCache> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap();
+    let entry = cache
+        .entry((TypeId::of::<(F, A)>(), constant_length))
+        .or_insert_with(|| {
+            if constant_length {
+                Arc::new(PoseidonConstants::<F, A>::new_constant_length(A::to_usize()))
+            } else {
+                Arc::new(PoseidonConstants::<F, A>::new())
+            }
+        });
+    entry.clone().downcast().unwrap()
+}
+
+/// Poseidon constants for hashing exactly `A` elements.
+/// Generating them is expensive for wide arities, so they are generated
+/// once per process for every field and arity.
+pub fn poseidon_constants<F, A>() -> Arc<PoseidonConstants<F, A>>
+where
+    F: PrimeField,
+    A: Arity<F> + Send + Sync + 'static,
+{
+    cached_constants(false)
+}
+
+/// Poseidon constants for hashing a fixed number of at most `A` elements,
+/// padded with zeros. Cached like `poseidon_constants`.
+pub fn constant_length_constants<F, A>(length: usize) -> PoseidonConstants<F, A>
+where
+    F: PrimeField,
+    A: Arity<F> + Send + Sync + 'static,
+{
+    cached_constants::<F, A>(true).with_length(length)
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign};
+
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use ff::PrimeField;
+
+/// Number of constraints and variables of a circuit or of a part of it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub constraints: usize,
+    pub aux: usize,
+    pub inputs: usize,
+    /// Nonzero entries of the constraint matrices, i.e. terms of the linear combinations
+    pub nonzeros: usize,
+}
+
+impl Add for Cost {
+    type Output = Cost;
+
+    fn add(self, other: Cost) -> Cost {
+        Cost {
+            constraints: self.constraints + other.constraints,
+            aux: self.aux + other.aux,
+            inputs: self.inputs + other.inputs,
+            nonzeros: self.nonzeros + other.nonzeros,
+        }
+    }
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, other: Cost) {
+        *self = *self + other;
+    }
+}
+
+/// A constraint system which records the cost of every namespace instead of the constraints.
+///
+/// Costs are grouped by their path truncated to `depth` components, where the annotation of
+/// a constraint or variable is the last component. With a depth of 1, everything allocated in
+/// a top-level namespace is charged to it, and every top-level constraint is charged to itself.
+/// Witness values are never computed, so circuits can be profiled without a witness.
+pub struct ProfilingConstraintSystem<Scalar: PrimeField> {
+    depth: usize,
+    current_namespace: Vec<String>,
+    costs: BTreeMap<String, Cost>,
+    total: Cost,
+    marker: PhantomData<Scalar>,
+}
+
+impl<Scalar: PrimeField> ProfilingConstraintSystem<Scalar> {
+    pub fn new(depth: usize) -> Self {
+        assert!(depth > 0, "the depth must be positive");
+        ProfilingConstraintSystem {
+            depth,
+            current_namespace: vec![],
+            costs: BTreeMap::new(),
+            total: Cost::default(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn total(&self) -> Cost {
+        self.total
+    }
+
+    /// The cost of every path truncated to `depth` components
+    pub fn costs(&self) -> &BTreeMap<String, Cost> {
+        &self.costs
+    }
+
+    /// The cost of the namespace or constraint at `path`, and of everything below it.
+    /// `path` must have at most `depth` components.
+    pub fn cost_of(&self, path: &str) -> Cost {
+        let prefix = format!("{}/", path);
+        self.costs
+            .iter()
+            .filter(|(k, _)| *k == path || k.starts_with(&prefix))
+            .fold(Cost::default(), |acc, (_, c)| acc + *c)
+    }
+
+    /// The costs of every path truncated to `depth` components, the most constraints first
+    pub fn report(&self) -> CostReport {
+        CostReport {
+            parts: self.costs.iter().map(|(k, c)| (k.clone(), *c)).collect(),
+            total: self.total,
+        }
+        .sorted()
+    }
+
+    fn charge<A, AR>(&mut self, annotation: A, cost: Cost)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let mut path = self
+            .current_namespace
+            .iter()
+            .take(self.depth)
+            .cloned()
+            .collect::<Vec<_>>();
+        if path.len() < self.depth {
+            path.push(annotation().into());
+        }
+        *self.costs.entry(path.join("/")).or_default() += cost;
+        self.total += cost;
+    }
+}
+
+/// Costs of the parts of a circuit, listed in order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CostReport {
+    pub parts: Vec<(String, Cost)>,
+    pub total: Cost,
+}
+
+impl CostReport {
+    /// Sorts the parts, the most constraints first
+    pub fn sorted(mut self) -> Self {
+        self.parts
+            .sort_by(|a, b| b.1.constraints.cmp(&a.1.constraints).then(a.0.cmp(&b.0)));
+        self
+    }
+}
+
+impl Display for CostReport {
+    /// A table of the costs, with their share of the constraints
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>7} {:>12} {:>12}  part",
+            "constraints", "share", "variables", "nonzeros"
+        )?;
+        let total = self.parts.iter().map(|(_, c)| *c).chain([self.total]);
+        let names = self.parts.iter().map(|(n, _)| n.as_str()).chain(["total"]);
+        for (name, cost) in names.zip(total) {
+            let share = 100.0 * cost.constraints as f64 / self.total.constraints.max(1) as f64;
+            writeln!(
+                f,
+                "{:>12} {:>6.2}% {:>12} {:>12}  {}",
+                cost.constraints,
+                share,
+                cost.aux + cost.inputs,
+                cost.nonzeros,
+                name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProfilingConstraintSystem<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.charge(
+            annotation,
+            Cost {
+                aux: 1,
+                ..Cost::default()
+            },
+        );
+        Ok(Variable::new_unchecked(Index::Aux(self.total.aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.charge(
+            annotation,
+            Cost {
+                inputs: 1,
+                ..Cost::default()
+            },
+        );
+        Ok(Variable::new_unchecked(Index::Input(self.total.inputs)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.charge(
+            annotation,
+            Cost {
+                constraints: 1,
+                nonzeros: a(LinearCombination::zero()).len()
+                    + b(LinearCombination::zero()).len()
+                    + c(LinearCombination::zero()).len(),
+                ..Cost::default()
+            },
+        );
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.current_namespace.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        self.current_namespace.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use bellpepper::gadgets::num::AllocatedNum;
+    use bellpepper::gadgets::sha256::sha256;
+    use bellpepper_core::boolean::{AllocatedBit, Boolean};
+    use bellpepper_core::test_cs::TestConstraintSystem;
+
+    fn synthesize<CS: ConstraintSystem<Fr>>(cs: &mut CS) {
+        let a = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Fr::from(3u64))).unwrap();
+        let b = a.square(cs.namespace(|| "square")).unwrap();
+        cs.enforce(|| "b = 9", |lc| lc + b.get_variable(), |lc| lc + CS::one(), |lc| lc + (Fr::from(9u64), CS::one()));
+        let bits = (0..512)
+            .map(|i| Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(false)).unwrap()))
+            .collect::<Vec<_>>();
+        sha256(cs.namespace(|| "sha256"), &bits).unwrap();
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut test_cs = TestConstraintSystem::<Fr>::new();
+        synthesize(&mut test_cs);
+        assert!(test_cs.is_satisfied());
+
+        let mut cs = ProfilingConstraintSystem::<Fr>::new(1);
+        synthesize(&mut cs);
+        assert_eq!(cs.total().constraints, test_cs.num_constraints());
+        assert_eq!(cs.cost_of("a"), Cost { constraints: 0, aux: 1, inputs: 0, nonzeros: 0 });
+        assert_eq!(cs.cost_of("square"), Cost { constraints: 1, aux: 1, inputs: 0, nonzeros: 3 });
+        assert_eq!(cs.cost_of("b = 9"), Cost { constraints: 1, aux: 0, inputs: 0, nonzeros: 3 });
+        assert_eq!(cs.costs().len(), 3 + 512 + 1);
+
+        // deeper profiles split namespaces, but add up to the same cost
+        let mut deep_cs = ProfilingConstraintSystem::<Fr>::new(3);
+        synthesize(&mut deep_cs);
+        assert!(deep_cs.costs().len() > cs.costs().len());
+        assert_eq!(deep_cs.cost_of("sha256"), cs.cost_of("sha256"));
+        let report = cs.report();
+        assert_eq!(report.parts[0].0, "sha256");
+        assert!(format!("{}", report).lines().last().unwrap().ends_with("total"));
+    }
+}
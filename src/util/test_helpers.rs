@@ -2,7 +2,25 @@ pub use bellpepper_core::test_cs::TestConstraintSystem;
 pub use bellpepper_core::Circuit;
 pub use ff::PrimeField;
 
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
 macro_rules! circuit_tests {
+    // Circuits which must be unsatisfied, first at a constraint whose path starts with `$unsat`
+    ($($name:ident: $value:expr => $unsat:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                use crate::util::scalar::Fr;
+                let circuit = $value;
+                let mut cs = TestConstraintSystem::<Fr>::new();
+
+                circuit.synthesize(&mut cs).expect("synthesis failed");
+                assert!(!cs.is_satisfied());
+                let unsat = cs.which_is_unsatisfied().unwrap();
+                assert!(unsat.starts_with($unsat), "{} is unsatisfied first, expected {}", unsat, $unsat);
+            }
+        )*
+    };
     ($($name:ident: $value:expr,)*) => {
         $(
             #[test]
@@ -21,3 +39,116 @@ macro_rules! circuit_tests {
         )*
     }
 }
+
+
+/// A constraint system which checks every constraint when it is enforced instead of storing it,
+/// for circuits too large for `TestConstraintSystem`.
+///
+/// Once a constraint is unsatisfied, allocations fail with `SynthesisError::Unsatisfiable`
+/// so that synthesis stops early.
+pub struct CheckingConstraintSystem<Scalar: PrimeField> {
+    inputs: Vec<Scalar>,
+    aux: Vec<Scalar>,
+    current_namespace: Vec<String>,
+    num_constraints: usize,
+    first_unsatisfied: Option<String>,
+}
+
+impl<Scalar: PrimeField> CheckingConstraintSystem<Scalar> {
+    pub fn new() -> Self {
+        CheckingConstraintSystem {
+            inputs: vec![Scalar::ONE],
+            aux: vec![],
+            current_namespace: vec![],
+            num_constraints: 0,
+            first_unsatisfied: None,
+        }
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.first_unsatisfied.is_none()
+    }
+
+    /// The path of the first unsatisfied constraint
+    pub fn which_is_unsatisfied(&self) -> Option<&str> {
+        self.first_unsatisfied.as_deref()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.num_constraints
+    }
+}
+
+impl<Scalar: PrimeField> Default for CheckingConstraintSystem<Scalar> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for CheckingConstraintSystem<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if self.first_unsatisfied.is_some() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        self.aux.push(f()?);
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if self.first_unsatisfied.is_some() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        self.inputs.push(f()?);
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.num_constraints += 1;
+        if self.first_unsatisfied.is_some() {
+            return;
+        }
+        let a = a(LinearCombination::zero()).eval(&self.inputs, &self.aux);
+        let b = b(LinearCombination::zero()).eval(&self.inputs, &self.aux);
+        let c = c(LinearCombination::zero()).eval(&self.inputs, &self.aux);
+        if a * b != c {
+            let mut path = self.current_namespace.clone();
+            path.push(annotation().into());
+            self.first_unsatisfied = Some(path.join("/"));
+        }
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.current_namespace.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        self.current_namespace.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
@@ -0,0 +1,294 @@
+//! Verifying compressed header chain proofs, without the proving code
+//!
+//! A proof bundle is the initial state `z0`, the number of steps and the compressed SNARK. It is
+//! encoded as the canonical representations of the elements of `z0`, the number of steps as a
+//! little-endian `u64`, then the SNARK serialized with `bincode`.
+
+use std::fmt::{self, Display, Formatter};
+
+use ff::{Field, PrimeField};
+use nova_snark::errors::NovaError;
+use nova_snark::provider::{
+    hyperkzg, ipa_pc, Bn256EngineKZG, GrumpkinEngine, PallasEngine, VestaEngine,
+};
+use nova_snark::spartan::{ppsnark, snark};
+use nova_snark::traits::circuit::TrivialCircuit;
+use nova_snark::traits::snark::RelaxedR1CSSNARKTrait;
+use nova_snark::traits::Engine;
+use nova_snark::{CompressedSNARK, VerifierKey};
+use num_bigint::BigInt;
+
+use crate::btc_validation::header_step::BlockHeader;
+use crate::btc_validation::median::MEDIAN_TIME_SPAN;
+use crate::btc_validation::witness::STATE_LEN;
+use crate::util::convert::f_to_nat;
+
+/// A cycle of curves, with the SNARKs compressing the IVC proofs on each of them
+pub trait CurveCycle {
+    type E1: Engine<Base = <Self::E2 as Engine>::Scalar>;
+    type E2: Engine<Base = <Self::E1 as Engine>::Scalar>;
+    type S1: RelaxedR1CSSNARKTrait<Self::E1>;
+    type S2: RelaxedR1CSSNARKTrait<Self::E2>;
+}
+
+/// Pallas and Vesta, with Spartan over IPA on both curves
+pub struct PallasVesta;
+
+impl CurveCycle for PallasVesta {
+    type E1 = PallasEngine;
+    type E2 = VestaEngine;
+    type S1 = snark::RelaxedR1CSSNARK<PallasEngine, ipa_pc::EvaluationEngine<PallasEngine>>;
+    type S2 = snark::RelaxedR1CSSNARK<VestaEngine, ipa_pc::EvaluationEngine<VestaEngine>>;
+}
+
+/// BN254 and Grumpkin, for proofs verified on EVM chains. The primary proof is compressed with
+/// preprocessing Spartan over HyperKZG, so its verifier needs BN254 pairings but does not
+/// evaluate the step circuit. Grumpkin has no pairing, so the secondary proof stays on IPA.
+pub struct Bn256Grumpkin;
+
+impl CurveCycle for Bn256Grumpkin {
+    type E1 = Bn256EngineKZG;
+    type E2 = GrumpkinEngine;
+    type S1 = ppsnark::RelaxedR1CSSNARK<Bn256EngineKZG, hyperkzg::EvaluationEngine<Bn256EngineKZG>>;
+    type S2 = ppsnark::RelaxedR1CSSNARK<GrumpkinEngine, ipa_pc::EvaluationEngine<GrumpkinEngine>>;
+}
+
+/// Scalar field of the primary curve, in which the header chain state lives
+pub type Scalar<C> = <<C as CurveCycle>::E1 as Engine>::Scalar;
+
+pub(crate) type C1<C> = BlockHeader<Scalar<C>>;
+pub(crate) type C2<C> = TrivialCircuit<<<C as CurveCycle>::E2 as Engine>::Scalar>;
+
+pub type HeaderChainVerifierKey<C> = VerifierKey<
+    <C as CurveCycle>::E1,
+    <C as CurveCycle>::E2,
+    C1<C>,
+    C2<C>,
+    <C as CurveCycle>::S1,
+    <C as CurveCycle>::S2,
+>;
+
+pub type HeaderChainSNARK<C> = CompressedSNARK<
+    <C as CurveCycle>::E1,
+    <C as CurveCycle>::E2,
+    C1<C>,
+    C2<C>,
+    <C as CurveCycle>::S1,
+    <C as CurveCycle>::S2,
+>;
+
+/// Reasons for which bytes or a state cannot be decoded
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The bytes end before the initial state and the number of steps
+    Truncated,
+    /// The element of the state at `index` is not a canonical field element
+    NonCanonical {
+        index: usize,
+    },
+    /// The state does not have `STATE_LEN` elements
+    StateLength {
+        len: usize,
+    },
+    /// The element of the state at `index` is too large for its meaning
+    OutOfRange {
+        index: usize,
+    },
+    /// The number of steps does not fit in a `usize`
+    TooManySteps(u64),
+    Bincode(bincode::Error),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "bundle is truncated"),
+            DecodeError::NonCanonical { index } => {
+                write!(
+                    f,
+                    "state element {} is not a canonical field element",
+                    index
+                )
+            }
+            DecodeError::StateLength { len } => {
+                write!(f, "state has {} elements, expected {}", len, STATE_LEN)
+            }
+            DecodeError::OutOfRange { index } => {
+                write!(f, "state element {} is out of range", index)
+            }
+            DecodeError::TooManySteps(n) => write!(f, "{} steps do not fit in a usize", n),
+            DecodeError::Bincode(e) => write!(f, "invalid encoding: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<bincode::Error> for DecodeError {
+    fn from(e: bincode::Error) -> Self {
+        DecodeError::Bincode(e)
+    }
+}
+
+/// Compressed proof that `num_steps` headers extend the chain from the state `z0`
+pub struct HeaderChainProof<C: CurveCycle> {
+    pub z0: Vec<Scalar<C>>,
+    pub num_steps: usize,
+    pub snark: HeaderChainSNARK<C>,
+}
+
+impl<C: CurveCycle> HeaderChainProof<C> {
+    /// Verifies the proof, returns the state after the last header
+    pub fn verify(&self, vk: &HeaderChainVerifierKey<C>) -> Result<Vec<Scalar<C>>, NovaError> {
+        let (z_out, _) = self
+            .snark
+            .verify(vk, self.num_steps, &self.z0, &z0_secondary::<C>())?;
+        Ok(z_out)
+    }
+
+    /// The proof as a bundle, see the module documentation for the encoding
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DecodeError> {
+        let mut bytes = Vec::new();
+        for x in &self.z0 {
+            bytes.extend_from_slice(x.to_repr().as_ref());
+        }
+        bytes.extend_from_slice(&(self.num_steps as u64).to_le_bytes());
+        bincode::serialize_into(&mut bytes, &self.snark)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let repr_len = <Scalar<C> as PrimeField>::Repr::default().as_ref().len();
+        let snark_offset = STATE_LEN * repr_len + 8;
+        if bytes.len() < snark_offset {
+            return Err(DecodeError::Truncated);
+        }
+        let z0 = bytes[..STATE_LEN * repr_len]
+            .chunks(repr_len)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut repr = <Scalar<C> as PrimeField>::Repr::default();
+                repr.as_mut().copy_from_slice(chunk);
+                Option::from(Scalar::<C>::from_repr(repr))
+                    .ok_or(DecodeError::NonCanonical { index })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let num_steps =
+            u64::from_le_bytes(bytes[snark_offset - 8..snark_offset].try_into().unwrap());
+        Ok(Self {
+            z0,
+            num_steps: usize::try_from(num_steps)
+                .map_err(|_| DecodeError::TooManySteps(num_steps))?,
+            snark: bincode::deserialize(&bytes[snark_offset..])?,
+        })
+    }
+}
+
+pub fn verifier_key_to_bytes<C: CurveCycle>(
+    vk: &HeaderChainVerifierKey<C>,
+) -> Result<Vec<u8>, DecodeError> {
+    Ok(bincode::serialize(vk)?)
+}
+
+pub fn verifier_key_from_bytes<C: CurveCycle>(
+    bytes: &[u8],
+) -> Result<HeaderChainVerifierKey<C>, DecodeError> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+pub(crate) fn z0_secondary<C: CurveCycle>() -> Vec<<C::E2 as Engine>::Scalar> {
+    vec![<C::E2 as Engine>::Scalar::ZERO]
+}
+
+/// The values of a state of the header chain, as laid out by `HeaderChainState::z`. The block
+/// hash MMR is only known by its root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedState<F: PrimeField> {
+    /// SHA256d of the last header, in the byte order it is produced
+    pub tip_hash: [u8; 32],
+    /// Timestamps of the last `MEDIAN_TIME_SPAN` blocks, oldest first
+    pub timestamps: [u32; MEDIAN_TIME_SPAN],
+    pub target: BigInt,
+    /// Timestamp of the first block of the current difficulty epoch
+    pub epoch_start_time: u32,
+    /// Height of the next block modulo `DIFFICULTY_ADJUSTMENT_INTERVAL`
+    pub counter: u64,
+    pub chain_work: BigInt,
+    pub mmr_root: F,
+    /// Number of blocks appended to the block hash MMR
+    pub block_count: u64,
+}
+
+impl<F: PrimeField> DecodedState<F> {
+    pub fn decode(z: &[F]) -> Result<Self, DecodeError> {
+        if z.len() != STATE_LEN {
+            return Err(DecodeError::StateLength { len: z.len() });
+        }
+        let nat = |index: usize, bits: u64| {
+            let n = f_to_nat(&z[index]);
+            if n.bits() > bits {
+                return Err(DecodeError::OutOfRange { index });
+            }
+            Ok(n)
+        };
+        let u64_at = |index: usize, bits: u64| -> Result<u64, DecodeError> {
+            Ok(u64::try_from(nat(index, bits)?).unwrap())
+        };
+
+        let mut tip_hash = [0u8; 32];
+        let (_, hash_bytes) = nat(0, 256)?.to_bytes_le();
+        tip_hash[..hash_bytes.len()].copy_from_slice(&hash_bytes);
+        let mut timestamps = [0u32; MEDIAN_TIME_SPAN];
+        for (i, t) in timestamps.iter_mut().enumerate() {
+            *t = u64_at(1 + i, 32)? as u32;
+        }
+        Ok(Self {
+            tip_hash,
+            timestamps,
+            target: nat(MEDIAN_TIME_SPAN + 1, 256)?,
+            epoch_start_time: u64_at(MEDIAN_TIME_SPAN + 2, 32)? as u32,
+            counter: u64_at(MEDIAN_TIME_SPAN + 3, 64)?,
+            chain_work: nat(MEDIAN_TIME_SPAN + 4, 256)?,
+            mmr_root: z[MEDIAN_TIME_SPAN + 5],
+            block_count: u64_at(MEDIAN_TIME_SPAN + 6, 64)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_validation::witness::HeaderChainState;
+    use crate::util::scalar::Fr;
+    use crate::verifier::*;
+
+    const GENESIS: [u64; 10] = [0x0100000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x000000003ba3edfd, 0x7a7b12b27ac72c3e, 0x67768f617fc81bc3, 0x888a51323a9fb8aa, 0x4b1e5e4a29ab5f49, 0xffff001d1dac2b7c];
+
+    #[test]
+    fn test_decode_state() {
+        let mut state = HeaderChainState::<Fr>::before_genesis();
+        BlockHeader::new_blocks_from(&mut state, vec![GENESIS]).unwrap();
+        let decoded = DecodedState::decode(&state.z()).unwrap();
+        assert_eq!(decoded.tip_hash, state.tip_hash);
+        assert_eq!(decoded.timestamps, state.timestamps);
+        assert_eq!(decoded.target, state.target);
+        assert_eq!(decoded.epoch_start_time, state.epoch_start_time);
+        assert_eq!(decoded.counter, state.counter);
+        assert_eq!(decoded.chain_work, state.chain_work);
+        assert_eq!(decoded.mmr_root, state.mmr.root());
+        assert_eq!(decoded.block_count, 1);
+
+        let mut z = state.z();
+        z[1] = Fr::from(1u64 << 32);
+        assert!(matches!(DecodedState::decode(&z), Err(DecodeError::OutOfRange { index: 1 })));
+        assert!(matches!(DecodedState::decode(&z[1..]), Err(DecodeError::StateLength { len: 17 })));
+    }
+
+    #[test]
+    fn test_bundle_header() {
+        // the SNARK is only decoded after the initial state and the number of steps
+        assert!(matches!(HeaderChainProof::<PallasVesta>::from_bytes(&[0; 32 * STATE_LEN + 7]), Err(DecodeError::Truncated)));
+        let mut bytes = vec![0; 32 * STATE_LEN + 8];
+        bytes[32..64].fill(0xff);
+        assert!(matches!(HeaderChainProof::<PallasVesta>::from_bytes(&bytes), Err(DecodeError::NonCanonical { index: 1 })));
+    }
+}